@@ -0,0 +1,147 @@
+//! A least recently used cache of prepared statements.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use stmt::Column;
+use types::{Oid, Type};
+
+pub type Key = (String, Vec<Oid>);
+
+#[derive(Clone)]
+pub struct CachedStatement {
+    pub name: String,
+    pub param_types: Vec<Type>,
+    pub columns: Arc<Vec<Column>>,
+    // Cloned into every `Statement` created from the entry, so an evicted
+    // statement is only closed once none of them are still alive.
+    pub handle: Arc<()>,
+}
+
+pub struct StatementCache {
+    entries: HashMap<Key, (CachedStatement, u64)>,
+    // Keys ordered by the tick of their last use.
+    order: BTreeMap<u64, Key>,
+    tick: u64,
+    capacity: usize,
+    evicted: Vec<CachedStatement>,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+impl StatementCache {
+    pub fn new(capacity: usize) -> StatementCache {
+        StatementCache {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+            capacity: capacity,
+            evicted: vec![],
+            hits: 0,
+            misses: 0,
+            evictions: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Looks up a statement, marking it as the most recently used.
+    pub fn get(&mut self, key: &Key) -> Option<CachedStatement> {
+        let tick = self.next_tick();
+        match self.entries.get_mut(key) {
+            Some(&mut (ref stmt, ref mut last_used)) => {
+                let key = self.order.remove(last_used).unwrap();
+                self.order.insert(tick, key);
+                *last_used = tick;
+                self.hits += 1;
+                Some(stmt.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Adds a statement, evicting the least recently used statements if the
+    /// cache is full.
+    pub fn insert(&mut self, key: Key, stmt: CachedStatement) {
+        let tick = self.next_tick();
+        self.order.insert(tick, key.clone());
+        if let Some((old, last_used)) = self.entries.insert(key, (stmt, tick)) {
+            self.order.remove(&last_used);
+            self.evicted.push(old);
+        }
+        self.evict_to(self.capacity);
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.evict_to(capacity);
+    }
+
+    /// Removes the statement with the given name, returning its key.
+    ///
+    /// Used when the server has invalidated the statement's plan. The
+    /// statement is closed once it is no longer in use, and its removal is
+    /// not counted as an eviction.
+    pub fn invalidate(&mut self, name: &str) -> Option<Key> {
+        let key = match self.entries.iter().find(|&(_, &(ref stmt, _))| stmt.name == name) {
+            Some((key, _)) => key.clone(),
+            None => return None,
+        };
+        let (stmt, last_used) = self.entries.remove(&key).unwrap();
+        self.order.remove(&last_used);
+        self.evicted.push(stmt);
+        Some(key)
+    }
+
+    /// Tracks a statement which is not part of the cache so that it is
+    /// closed once it is no longer in use.
+    pub fn retire(&mut self, stmt: CachedStatement) {
+        self.evicted.push(stmt);
+    }
+
+    /// Evicts every statement without counting them as evictions.
+    pub fn clear(&mut self) {
+        self.order.clear();
+        self.evicted.extend(self.entries.drain().map(|(_, (stmt, _))| stmt));
+    }
+
+    /// Returns the names of evicted statements which are no longer in use
+    /// and can be closed on the server.
+    pub fn take_closable(&mut self) -> Vec<String> {
+        let mut names = vec![];
+        let mut i = 0;
+        while i < self.evicted.len() {
+            if Arc::strong_count(&self.evicted[i].handle) == 1 {
+                names.push(self.evicted.swap_remove(i).name);
+            } else {
+                i += 1;
+            }
+        }
+        names
+    }
+
+    fn evict_to(&mut self, capacity: usize) {
+        while self.entries.len() > capacity {
+            let tick = *self.order.keys().next().unwrap();
+            let key = self.order.remove(&tick).unwrap();
+            let (stmt, _) = self.entries.remove(&key).unwrap();
+            self.evicted.push(stmt);
+            self.evictions += 1;
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
@@ -0,0 +1,341 @@
+//! Deserialization of rows with serde.
+
+use std::any;
+use std::fmt::Display;
+use std::str::FromStr;
+use serde::de::{self, Deserialize, DeserializeSeed, Deserializer, Visitor, MapVisitor,
+                SeqVisitor, Expected};
+use serde_json::Value;
+
+use Result;
+use error::{Error, ValueError, ValueLocation};
+use rows::Row;
+use literal;
+use types::{FromSql, Type, WasNull};
+use ValueErrorNew;
+
+impl<'a> Row<'a> {
+    /// Deserializes the row into a value of a type implementing serde's
+    /// `Deserialize` trait.
+    ///
+    /// Structs and maps are filled from the row's columns by name, while
+    /// tuples and sequences are filled from its columns in order. Struct fields
+    /// with no matching column are an error unless they are `Option`s, which
+    /// are left as `None`, while columns which match no field are ignored. `NULL`
+    /// values can be read into `Option`s, and `JSON` and `JSONB` values are
+    /// deserialized in place into the type of their field. Other values are
+    /// read with the existing `FromSql` implementations, so errors converting
+    /// them identify their column as with `get_opt`.
+    ///
+    /// Values of types serde has no equivalent for, such as `NUMERIC` or
+    /// `TIMESTAMP`, are read as strings in their text representation, though
+    /// `NUMERIC` values can also be read into numbers.
+    ///
+    /// A row which has more or fewer columns than a tuple or sequence expects
+    /// is an `Error::Conversion`.
+    ///
+    /// Requires the `serde` feature.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, SslMode};
+    /// # let conn = Connection::connect("", &SslMode::None).unwrap();
+    /// let rows = conn.query("SELECT id, name FROM person", &[]).unwrap();
+    /// for row in &rows {
+    ///     let (id, name): (i32, Option<String>) = row.deserialize().unwrap();
+    ///     println!("{}: {:?}", id, name);
+    /// }
+    /// ```
+    pub fn deserialize<T: Deserialize>(&self) -> Result<T> {
+        T::deserialize(RowDeserializer { row: self })
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Error {
+        Error::Conversion(msg.to_string().into())
+    }
+
+    fn invalid_length(len: usize, exp: &Expected) -> Error {
+        Error::Conversion(format!("the row has {} columns but expected {}", len, exp).into())
+    }
+
+    fn missing_field(field: &'static str) -> Error {
+        Error::Conversion(format!("the row has no column for field `{}`", field).into())
+    }
+}
+
+struct RowDeserializer<'a, 'b: 'a> {
+    row: &'a Row<'b>,
+}
+
+impl<'a, 'b> RowDeserializer<'a, 'b> {
+    fn visit_map<V>(self, visitor: V) -> Result<V::Value> where V: Visitor {
+        visitor.visit_map(Columns::new(self.row))
+    }
+
+    fn visit_seq<V>(self, visitor: V) -> Result<V::Value> where V: Visitor {
+        let mut columns = Columns::new(self.row);
+        let value = try!(visitor.visit_seq(&mut columns));
+        if columns.remaining() == 0 {
+            Ok(value)
+        } else {
+            Err(Error::Conversion(format!("the row has {} columns but expected {}",
+                                          self.row.len(),
+                                          columns.idx)
+                                      .into()))
+        }
+    }
+}
+
+impl<'a, 'b> Deserializer for RowDeserializer<'a, 'b> {
+    type Error = Error;
+
+    // Rows are self-describing by column name, so types which don't say what
+    // they expect are given a map.
+    fn deserialize<V>(self, visitor: V) -> Result<V::Value> where V: Visitor {
+        self.visit_map(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value> where V: Visitor {
+        self.visit_map(visitor)
+    }
+
+    fn deserialize_struct<V>(self,
+                             _: &'static str,
+                             _: &'static [&'static str],
+                             visitor: V)
+                             -> Result<V::Value>
+        where V: Visitor
+    {
+        self.visit_map(visitor)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value> where V: Visitor {
+        self.visit_seq(visitor)
+    }
+
+    fn deserialize_seq_fixed_size<V>(self, _: usize, visitor: V) -> Result<V::Value>
+        where V: Visitor
+    {
+        self.visit_seq(visitor)
+    }
+
+    fn deserialize_tuple<V>(self, _: usize, visitor: V) -> Result<V::Value> where V: Visitor {
+        self.visit_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(self, _: &'static str, _: usize, visitor: V) -> Result<V::Value>
+        where V: Visitor
+    {
+        self.visit_seq(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value> where V: Visitor {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _: &'static str, visitor: V) -> Result<V::Value>
+        where V: Visitor
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize! {
+        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string unit bytes byte_buf
+        unit_struct struct_field enum ignored_any
+    }
+}
+
+struct Columns<'a, 'b: 'a> {
+    row: &'a Row<'b>,
+    idx: usize,
+}
+
+impl<'a, 'b> Columns<'a, 'b> {
+    fn new(row: &'a Row<'b>) -> Columns<'a, 'b> {
+        Columns {
+            row: row,
+            idx: 0,
+        }
+    }
+
+    fn next_value<T: DeserializeSeed>(&mut self, seed: T) -> Result<T::Value> {
+        let idx = self.idx;
+        self.idx += 1;
+        seed.deserialize(ValueDeserializer { row: self.row, idx: idx })
+    }
+
+    fn remaining(&self) -> usize {
+        self.row.len() - self.idx
+    }
+}
+
+impl<'a, 'b> MapVisitor for Columns<'a, 'b> {
+    type Error = Error;
+
+    fn visit_key_seed<K: DeserializeSeed>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.remaining() == 0 {
+            return Ok(None);
+        }
+        let name = self.row.columns()[self.idx].name();
+        seed.deserialize(NameDeserializer { name: name }).map(Some)
+    }
+
+    fn visit_value_seed<V: DeserializeSeed>(&mut self, seed: V) -> Result<V::Value> {
+        self.next_value(seed)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining(), Some(self.remaining()))
+    }
+}
+
+impl<'a, 'b, 'c> SeqVisitor for &'c mut Columns<'a, 'b> {
+    type Error = Error;
+
+    fn visit_seed<T: DeserializeSeed>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        if self.remaining() == 0 {
+            return Ok(None);
+        }
+        self.next_value(seed).map(Some)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining(), Some(self.remaining()))
+    }
+}
+
+// Deserializes the name of a column as a map key or struct field name.
+struct NameDeserializer<'a> {
+    name: &'a str,
+}
+
+impl<'a> Deserializer for NameDeserializer<'a> {
+    type Error = Error;
+
+    fn deserialize<V>(self, visitor: V) -> Result<V::Value> where V: Visitor {
+        visitor.visit_str(self.name)
+    }
+
+    forward_to_deserialize! {
+        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string unit option
+        seq seq_fixed_size bytes byte_buf map unit_struct newtype_struct
+        tuple_struct struct struct_field tuple enum ignored_any
+    }
+}
+
+macro_rules! deserialize_number {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value> where V: Visitor {
+                self.visit_number(visitor)
+                    .map_err(|err| self.wrap(err, any::type_name::<V::Value>()))
+            }
+        )*
+    }
+}
+
+// Deserializes the value of a single column.
+struct ValueDeserializer<'a, 'b: 'a> {
+    row: &'a Row<'b>,
+    idx: usize,
+}
+
+impl<'a, 'b> ValueDeserializer<'a, 'b> {
+    fn get<T: FromSql>(&self) -> Result<T> {
+        self.row.get_opt(self.idx)
+    }
+
+    fn visit_value<V>(&self, visitor: V) -> Result<V::Value> where V: Visitor {
+        let ty = self.row.columns()[self.idx].type_();
+        if <bool as FromSql>::accepts(ty) {
+            visitor.visit_bool(try!(self.get::<bool>()))
+        } else if <i8 as FromSql>::accepts(ty) {
+            visitor.visit_i8(try!(self.get::<i8>()))
+        } else if <i16 as FromSql>::accepts(ty) {
+            visitor.visit_i16(try!(self.get::<i16>()))
+        } else if <i32 as FromSql>::accepts(ty) {
+            visitor.visit_i32(try!(self.get::<i32>()))
+        } else if <i64 as FromSql>::accepts(ty) {
+            visitor.visit_i64(try!(self.get::<i64>()))
+        } else if <u32 as FromSql>::accepts(ty) {
+            visitor.visit_u32(try!(self.get::<u32>()))
+        } else if <f32 as FromSql>::accepts(ty) {
+            visitor.visit_f32(try!(self.get::<f32>()))
+        } else if <f64 as FromSql>::accepts(ty) {
+            visitor.visit_f64(try!(self.get::<f64>()))
+        } else if <Vec<u8> as FromSql>::accepts(ty) {
+            visitor.visit_byte_buf(try!(self.get::<Vec<u8>>()))
+        } else if <Value as FromSql>::accepts(ty) {
+            let value = try!(self.get::<Value>());
+            value.deserialize(visitor).map_err(|err| Error::Conversion(Box::new(err)))
+        } else if <String as FromSql>::accepts(ty) {
+            visitor.visit_string(try!(self.get::<String>()))
+        } else {
+            // serde has no equivalent of types like NUMERIC or TIMESTAMP
+            visitor.visit_string(try!(self.text()))
+        }
+    }
+
+    fn text(&self) -> Result<String> {
+        let ty = self.row.columns()[self.idx].type_();
+        match self.row.get_bytes(self.idx) {
+            Some(raw) => literal::to_text(ty, raw),
+            None => Err(Error::Conversion(Box::new(WasNull))),
+        }
+    }
+
+    // NUMERIC values are read as strings unless a number is asked for.
+    fn visit_number<V>(&self, visitor: V) -> Result<V::Value> where V: Visitor {
+        if *self.row.columns()[self.idx].type_() != Type::Numeric {
+            return self.visit_value(visitor);
+        }
+        let text = try!(self.text());
+        match i64::from_str(&text) {
+            Ok(value) => visitor.visit_i64(value),
+            Err(_) => {
+                match f64::from_str(&text) {
+                    Ok(value) => visitor.visit_f64(value),
+                    Err(err) => Err(Error::Conversion(Box::new(err))),
+                }
+            }
+        }
+    }
+
+    fn wrap(&self, err: Error, rust_type: &'static str) -> Error {
+        let column = &self.row.columns()[self.idx];
+        let location = ValueLocation::Column {
+            index: self.idx,
+            name: column.name().to_owned(),
+        };
+        ValueError::wrap(location, column.type_().clone(), rust_type, err)
+    }
+}
+
+impl<'a, 'b> Deserializer for ValueDeserializer<'a, 'b> {
+    type Error = Error;
+
+    fn deserialize<V>(self, visitor: V) -> Result<V::Value> where V: Visitor {
+        self.visit_value(visitor).map_err(|err| self.wrap(err, any::type_name::<V::Value>()))
+    }
+
+    deserialize_number! {
+        deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64 deserialize_i8
+        deserialize_i16 deserialize_i32 deserialize_i64 deserialize_f32 deserialize_f64
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value> where V: Visitor {
+        if self.row.get_bytes(self.idx).is_none() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    forward_to_deserialize! {
+        bool char str string unit seq seq_fixed_size bytes byte_buf map unit_struct
+        newtype_struct tuple_struct struct struct_field tuple enum ignored_any
+    }
+}
@@ -9,7 +9,7 @@ use std::io;
 use std::result;
 use std::collections::HashMap;
 
-use {Result, DbErrorNew};
+use {Result, DbErrorNew, BatchErrorNew, ValueErrorNew};
 use types::Type;
 
 include!(concat!(env!("OUT_DIR"), "/sqlstate.rs"));
@@ -29,9 +29,9 @@ pub struct DbError {
     column: Option<String>,
     datatype: Option<String>,
     constraint: Option<String>,
-    file: String,
-    line: u32,
-    routine: String,
+    file: Option<String>,
+    line: Option<u32>,
+    routine: Option<String>,
 }
 
 impl DbErrorNew for DbError {
@@ -59,9 +59,12 @@ impl DbErrorNew for DbError {
             column: map.remove(&b'c'),
             datatype: map.remove(&b'd'),
             constraint: map.remove(&b'n'),
-            file: try!(map.remove(&b'F').ok_or(())),
-            line: try!(map.remove(&b'L').and_then(|l| l.parse().ok()).ok_or(())),
-            routine: try!(map.remove(&b'R').ok_or(())),
+            file: map.remove(&b'F'),
+            line: match map.remove(&b'L') {
+                Some(line) => Some(try!(line.parse().map_err(|_| ()))),
+                None => None,
+            },
+            routine: map.remove(&b'R'),
         })
     }
 
@@ -162,19 +165,25 @@ impl DbError {
     }
 
     /// The file name of the source-code location where the error was reported.
-    pub fn file(&self) -> &str {
-        &self.file
+    ///
+    /// Servers other than Postgres itself may not report this.
+    pub fn file(&self) -> Option<&str> {
+        self.file.as_ref().map(|s| &**s)
     }
 
     /// The line number of the source-code location where the error was
     /// reported.
-    pub fn line(&self) -> u32 {
+    ///
+    /// Servers other than Postgres itself may not report this.
+    pub fn line(&self) -> Option<u32> {
         self.line
     }
 
     /// The name of the source-code routine reporting the error.
-    pub fn routine(&self) -> &str {
-        &self.routine
+    ///
+    /// Servers other than Postgres itself may not report this.
+    pub fn routine(&self) -> Option<&str> {
+        self.routine.as_ref().map(|s| &**s)
     }
 }
 
@@ -297,6 +306,18 @@ pub enum Error {
     InvalidColumn,
     /// An error converting between Postgres and Rust types.
     Conversion(Box<error::Error+Sync+Send>),
+    /// A query expected to return at most one row returned the specified
+    /// number of rows instead.
+    RowCount(usize),
+    /// The API was used incorrectly, for example by passing the wrong number
+    /// of parameters to a statement or by using a connection while it is
+    /// already in use by this thread.
+    Misuse(String),
+    /// An error converting the value of a specific column or parameter.
+    ///
+    /// The `ValueError` identifies the value and wraps the underlying error,
+    /// typically a `WrongType` or `Conversion` error.
+    Value(Box<ValueError>),
 }
 
 impl fmt::Display for Error {
@@ -307,6 +328,9 @@ impl fmt::Display for Error {
             Error::IoError(ref err) => write!(fmt, ": {}", err),
             Error::WrongType(ref ty) => write!(fmt, ": saw type {:?}", ty),
             Error::Conversion(ref err) => write!(fmt, ": {}", err),
+            Error::RowCount(count) => write!(fmt, ": saw {} rows", count),
+            Error::Misuse(ref msg) => write!(fmt, ": {}", msg),
+            Error::Value(ref err) => write!(fmt, ": {}", err),
             _ => Ok(()),
         }
     }
@@ -320,6 +344,9 @@ impl error::Error for Error {
             Error::WrongType(_) => "Unexpected type",
             Error::InvalidColumn => "Invalid column",
             Error::Conversion(_) => "Error converting between Postgres and Rust types",
+            Error::RowCount(_) => "Unexpected number of rows",
+            Error::Misuse(_) => "Invalid use of the API",
+            Error::Value(_) => "Error converting a value",
         }
     }
 
@@ -328,6 +355,7 @@ impl error::Error for Error {
             Error::DbError(ref err) => Some(err),
             Error::IoError(ref err) => Some(err),
             Error::Conversion(ref err) => Some(&**err),
+            Error::Value(ref err) => Some(&**err),
             _ => None
         }
     }
@@ -356,3 +384,165 @@ impl From<Error> for io::Error {
         io::Error::new(io::ErrorKind::Other, err)
     }
 }
+
+/// An error from `Statement::execute_many`.
+#[derive(Debug)]
+pub struct BatchError {
+    index: usize,
+    error: Error,
+}
+
+impl BatchErrorNew for BatchError {
+    fn new(index: usize, error: Error) -> BatchError {
+        BatchError {
+            index: index,
+            error: error,
+        }
+    }
+}
+
+impl BatchError {
+    /// The index of the parameter set whose execution failed.
+    ///
+    /// If communication with the server failed, this is the index of the
+    /// first parameter set whose result was not received.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The error which caused the execution to fail.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// Consumes the `BatchError`, returning the error which caused the
+    /// execution to fail.
+    pub fn into_error(self) -> Error {
+        self.error
+    }
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "parameter set {}: {}", self.index, self.error)
+    }
+}
+
+impl error::Error for BatchError {
+    fn description(&self) -> &str {
+        error::Error::description(&self.error)
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        Some(&self.error)
+    }
+}
+
+impl From<BatchError> for Error {
+    fn from(err: BatchError) -> Error {
+        err.error
+    }
+}
+
+/// The column or parameter whose value could not be converted.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum ValueLocation {
+    /// A column of a row.
+    Column {
+        /// The 0-based index of the column.
+        index: usize,
+        /// The name of the column.
+        name: String,
+    },
+    /// A parameter of a statement, identified by its 0-based index.
+    Parameter(usize),
+}
+
+impl fmt::Display for ValueLocation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValueLocation::Column { index, ref name } => {
+                write!(fmt, "column {} ({:?})", index, name)
+            }
+            ValueLocation::Parameter(index) => write!(fmt, "parameter {}", index),
+        }
+    }
+}
+
+/// An error converting the value of a column or parameter between its
+/// Postgres and Rust types.
+#[derive(Debug)]
+pub struct ValueError {
+    location: ValueLocation,
+    pg_type: Type,
+    rust_type: &'static str,
+    error: Error,
+}
+
+impl ValueErrorNew for ValueError {
+    fn wrap(location: ValueLocation, pg_type: Type, rust_type: &'static str, error: Error)
+            -> Error {
+        // Only the innermost context is interesting
+        if let Error::Value(_) = error {
+            return error;
+        }
+        Error::Value(Box::new(ValueError {
+            location: location,
+            pg_type: pg_type,
+            rust_type: rust_type,
+            error: error,
+        }))
+    }
+}
+
+impl ValueError {
+    /// The column or parameter whose value could not be converted.
+    pub fn location(&self) -> &ValueLocation {
+        &self.location
+    }
+
+    /// The Postgres type of the value.
+    ///
+    /// This is `Type::Unknown` for a parameter whose type is not known, which
+    /// can happen in simple query mode.
+    pub fn pg_type(&self) -> &Type {
+        &self.pg_type
+    }
+
+    /// The name of the Rust type the value was being converted to or from.
+    pub fn rust_type(&self) -> &str {
+        self.rust_type
+    }
+
+    /// The error which caused the conversion to fail.
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// Consumes the `ValueError`, returning the error which caused the
+    /// conversion to fail.
+    pub fn into_error(self) -> Error {
+        self.error
+    }
+}
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt,
+               "{} of Postgres type {} and Rust type {}: {}",
+               self.location,
+               self.pg_type.name(),
+               self.rust_type,
+               self.error)
+    }
+}
+
+impl error::Error for ValueError {
+    fn description(&self) -> &str {
+        error::Error::description(&self.error)
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        Some(&self.error)
+    }
+}
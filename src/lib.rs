@@ -50,6 +50,11 @@ extern crate byteorder;
 extern crate log;
 extern crate phf;
 extern crate rustc_serialize as serialize;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
 #[cfg(feature = "unix_socket")]
 extern crate unix_socket;
 extern crate debug_builders;
@@ -59,20 +64,23 @@ use md5::Md5;
 use debug_builders::DebugStruct;
 use std::ascii::AsciiExt;
 use std::borrow::ToOwned;
-use std::cell::{Cell, RefCell};
+use std::cell::Cell;
 use std::collections::{VecDeque, HashMap};
 use std::fmt;
 use std::iter::IntoIterator;
 use std::io as std_io;
 use std::io::prelude::*;
 use std::mem;
+use std::ops::{Deref, DerefMut};
 use std::result;
+use std::sync::{Arc, Mutex, MutexGuard};
 #[cfg(feature = "unix_socket")]
 use std::path::PathBuf;
 
-pub use stmt::{Statement, Column};
+pub use stmt::{Statement, Column, CommandTag, CommandKind};
+use stmt::ColumnOrigin;
 
-use error::{Error, ConnectError, SqlState, DbError};
+use error::{Error, ConnectError, SqlState, DbError, BatchError, ValueLocation};
 use types::{ToSql, FromSql};
 use io::{StreamWrapper, NegotiateSsl};
 use types::{IsNull, Kind, Type, SessionInfo, Oid, Other};
@@ -81,20 +89,35 @@ use message::FrontendMessage::*;
 use message::{FrontendMessage, BackendMessage, RowDescriptionEntry};
 use message::{WriteMessage, ReadMessage};
 use url::Url;
-use rows::{Rows, LazyRows};
+use rows::{Rows, Row, LazyRows, RowStream, SimpleQueryResult};
+use named::NamedStatement;
+use pipeline::Pipeline;
+use typed::{TypedStatement, Params, RowType};
+use lock::{Lock, LockGuard};
+use cache::{CachedStatement, StatementCache};
 
 #[macro_use]
 mod macros;
 
+mod cache;
+#[cfg(feature = "serde")]
+mod de;
+mod literal;
+mod lock;
 mod md5;
 mod message;
 mod priv_io;
+mod sql;
 mod url;
 mod util;
 pub mod error;
 pub mod io;
+pub mod named;
+pub mod nonblocking;
+pub mod pipeline;
 pub mod rows;
 pub mod stmt;
+pub mod typed;
 pub mod types;
 
 const TYPEINFO_QUERY: &'static str = "t";
@@ -248,9 +271,14 @@ pub struct Notifications<'conn> {
 
 impl<'a> fmt::Debug for Notifications<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        DebugStruct::new(fmt, "Notifications")
-            .field("pending", &self.conn.conn.borrow().notifications.len())
-            .finish()
+        match self.conn.lock_inner() {
+            Some(conn) => {
+                DebugStruct::new(fmt, "Notifications")
+                    .field("pending", &conn.notifications.len())
+                    .finish()
+            }
+            None => DebugStruct::new(fmt, "Notifications").field("in_use", &true).finish(),
+        }
     }
 }
 
@@ -262,9 +290,11 @@ impl<'conn> Iterator for Notifications<'conn> {
     /// ## Note
     ///
     /// `next` may return `Some` notification after returning `None` if a new
-    /// notification was received.
+    /// notification was received. `None` is also returned while the
+    /// connection is in use by the current thread, for example by a
+    /// `CopyOutReader`.
     fn next(&mut self) -> Option<Notification> {
-        self.conn.conn.borrow_mut().notifications.pop_front()
+        self.conn.lock_inner().and_then(|mut conn| conn.notifications.pop_front())
     }
 }
 
@@ -277,7 +307,7 @@ impl<'conn> Notifications<'conn> {
             return Ok(notification);
         }
 
-        let mut conn = self.conn.conn.borrow_mut();
+        let mut conn = try!(self.conn.lock());
         check_desync!(conn);
         match try!(conn.read_message_with_notification()) {
             NotificationResponse { pid, channel, payload } => {
@@ -292,6 +322,27 @@ impl<'conn> Notifications<'conn> {
     }
 }
 
+/// The number of statements cached by `prepare_cached` before the least
+/// recently used is evicted, unless changed with
+/// `Connection::set_statement_cache_capacity`.
+pub const DEFAULT_STATEMENT_CACHE_CAPACITY: usize = 256;
+
+/// Statistics about a connection's prepared statement cache.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StatementCacheStats {
+    /// The number of statements currently cached.
+    pub len: usize,
+    /// The maximum number of statements cached.
+    pub capacity: usize,
+    /// The number of times a statement was found in the cache.
+    pub hits: u64,
+    /// The number of times a statement was not found in the cache and had
+    /// to be prepared.
+    pub misses: u64,
+    /// The number of statements evicted to stay within the capacity.
+    pub evictions: u64,
+}
+
 /// Contains information necessary to cancel queries for a session.
 #[derive(Copy, Clone, Debug)]
 pub struct CancelData {
@@ -343,11 +394,45 @@ pub fn cancel_query<T>(params: T, ssl: &SslMode, data: CancelData)
     Ok(())
 }
 
+fn startup_parameters(user: &UserInfo,
+                      database: Option<String>,
+                      mut options: Vec<(String, String)>)
+                      -> Vec<(String, String)> {
+    options.push(("client_encoding".to_owned(), "UTF8".to_owned()));
+    // Postgres uses the value of TimeZone as the time zone for TIMESTAMP
+    // WITH TIME ZONE values. Timespec converts to GMT internally.
+    options.push(("timezone".to_owned(), "GMT".to_owned()));
+    options.push(("user".to_owned(), user.user.clone()));
+    if let Some(database) = database {
+        options.push(("database".to_owned(), database));
+    }
+    options
+}
+
+fn md5_password(user: &str, password: &str, salt: &[u8; 4]) -> String {
+    let mut hasher = Md5::new();
+    let _ = hasher.input(password.as_bytes());
+    let _ = hasher.input(user.as_bytes());
+    let output = hasher.result_str();
+    hasher.reset();
+    let _ = hasher.input(output.as_bytes());
+    let _ = hasher.input(salt);
+    format!("md5{}", hasher.result_str())
+}
+
 fn bad_response() -> std_io::Error {
     std_io::Error::new(std_io::ErrorKind::InvalidInput,
                        "the server returned an unexpected response")
 }
 
+fn in_use() -> Error {
+    misuse("the connection is already in use by this thread")
+}
+
+fn misuse<S: Into<String>>(message: S) -> Error {
+    Error::Misuse(message.into())
+}
+
 fn desynchronized() -> std_io::Error {
     std_io::Error::new(std_io::ErrorKind::Other,
                        "communication with the server has desynchronized due to an earlier IO error")
@@ -427,23 +512,75 @@ impl fmt::Debug for SslMode {
     }
 }
 
-#[derive(Clone)]
-struct CachedStatement {
-    name: String,
-    param_types: Vec<Type>,
-    columns: Vec<Column>,
+/// The protocol used to execute queries.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QueryProtocol {
+    /// The extended query protocol.
+    ///
+    /// Statements are prepared on the server and parameters are sent
+    /// separately from the query in the binary format.
+    Extended,
+    /// The simple query protocol.
+    ///
+    /// Statements are not prepared on the server. Parameters are
+    /// interpolated into the query as escaped literals on the client side and
+    /// results are transferred in the text format. This is useful when
+    /// connecting through poolers or proxies which do not support the extended
+    /// protocol.
+    Simple,
+}
+
+/// How closely the server is expected to match the behavior of Postgres.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Compatibility {
+    /// The server is expected to be Postgres.
+    Strict,
+    /// The server speaks the Postgres wire protocol but may not be Postgres
+    /// itself, for example CockroachDB, Redshift, or a connection proxy.
+    ///
+    /// The system catalogs are not assumed to match those of Postgres. If the
+    /// definition of a type doesn't exist or the catalogs needed to look it
+    /// up are missing, it is reported as a `Type::Other` of kind
+    /// `Kind::Simple` named after its OID instead of causing an error. Other
+    /// errors, such as a lookup made in an aborted transaction, are returned
+    /// as usual.
+    Lenient,
 }
 
 struct InnerConnection {
     stream: BufStream<Box<StreamWrapper>>,
-    notice_handler: Box<HandleNotice>,
+    notice_handler: NoticeHandler,
+    // Shared with the `Connection`, which reads it while this is in use.
+    mirror: Mirror,
     notifications: VecDeque<Notification>,
     cancel_data: CancelData,
+    cancel_params: ConnectParams,
     unknown_types: HashMap<Oid, Type>,
-    cached_statements: HashMap<String, CachedStatement>,
-    parameters: HashMap<String, String>,
+    column_origins: HashMap<(Oid, i16), ColumnOrigin>,
+    statement_cache: StatementCache,
+    // Statements and portals to close ahead of the next batch of messages.
+    deferred_closes: DeferredCloses,
+    // The number of batches of deferred closes whose responses are pending.
+    deferred_close_syncs: u32,
+    // Set when nothing has been written since the last ReadyForQuery message.
+    idle: bool,
+    parameters: Arc<HashMap<String, String>>,
     next_stmt_id: u32,
     trans_depth: u32,
+    // Incremented whenever the server reports that no transaction is open,
+    // which closes every cursor not declared `WITH HOLD`.
+    transaction_ends: u64,
+    // The savepoints of the open nested transactions, innermost last, and
+    // those rolled back since the transaction began. Rolling back a savepoint
+    // closes the cursors declared after it.
+    savepoints: Vec<u64>,
+    rolled_back_savepoints: Vec<u64>,
+    next_savepoint_id: u64,
+    // The transaction status reported by the last ReadyForQuery message.
+    transaction_status: u8,
+    query_protocol: QueryProtocol,
+    compatibility: Compatibility,
+    has_typeinfo_query: bool,
     desynchronized: bool,
     finished: bool,
 }
@@ -462,33 +599,46 @@ impl InnerConnection {
         let params = try!(params.into_connect_params());
         let stream = try!(priv_io::initialize_stream(&params, ssl));
 
-        let ConnectParams { user, database, mut options, .. } = params;
+        let cancel_params = ConnectParams {
+            target: params.target.clone(),
+            port: params.port,
+            user: None,
+            database: None,
+            options: vec![],
+        };
+        let ConnectParams { user, database, options, .. } = params;
 
         let user = try!(user.ok_or(ConnectError::MissingUser));
 
         let mut conn = InnerConnection {
             stream: BufStream::new(stream),
             next_stmt_id: 0,
-            notice_handler: Box::new(LoggingNoticeHandler),
+            notice_handler: NoticeHandler::new(Box::new(LoggingNoticeHandler)),
+            mirror: Mirror::default(),
             notifications: VecDeque::new(),
             cancel_data: CancelData { process_id: 0, secret_key: 0 },
+            cancel_params: cancel_params,
             unknown_types: HashMap::new(),
-            cached_statements: HashMap::new(),
-            parameters: HashMap::new(),
+            column_origins: HashMap::new(),
+            statement_cache: StatementCache::new(DEFAULT_STATEMENT_CACHE_CAPACITY),
+            deferred_closes: DeferredCloses::default(),
+            deferred_close_syncs: 0,
+            idle: false,
+            parameters: Arc::new(HashMap::new()),
             desynchronized: false,
             finished: false,
             trans_depth: 0,
+            transaction_ends: 0,
+            savepoints: vec![],
+            rolled_back_savepoints: vec![],
+            next_savepoint_id: 0,
+            transaction_status: b'I',
+            query_protocol: QueryProtocol::Extended,
+            compatibility: Compatibility::Strict,
+            has_typeinfo_query: false,
         };
 
-        options.push(("client_encoding".to_owned(), "UTF8".to_owned()));
-        // Postgres uses the value of TimeZone as the time zone for TIMESTAMP
-        // WITH TIME ZONE values. Timespec converts to GMT internally.
-        options.push(("timezone".to_owned(), "GMT".to_owned()));
-        // We have to clone here since we need the user again for auth
-        options.push(("user".to_owned(), user.user.clone()));
-        if let Some(database) = database {
-            options.push(("database".to_owned(), database));
-        }
+        let options = startup_parameters(&user, database, options);
 
         try!(conn.write_messages(&[StartupMessage {
             version: message::PROTOCOL_VERSION,
@@ -509,45 +659,91 @@ impl InnerConnection {
             }
         }
 
-        try!(conn.setup_typeinfo_query());
-
+        conn.update_mirror();
         Ok(conn)
     }
 
-    fn setup_typeinfo_query(&mut self) -> result::Result<(), ConnectError> {
+    // The type info query is prepared the first time it's needed so that
+    // connections in simple query mode never use the extended protocol.
+    fn setup_typeinfo_query(&mut self) -> Result<()> {
+        if self.has_typeinfo_query {
+            return Ok(());
+        }
+
         match self.raw_prepare(TYPEINFO_QUERY,
                                "SELECT t.typname, t.typelem, r.rngsubtype \
                                 FROM pg_catalog.pg_type t \
                                 LEFT OUTER JOIN pg_catalog.pg_range r \
                                     ON r.rngtypid = t.oid \
-                                WHERE t.oid = $1") {
-            Ok(..) => return Ok(()),
-            Err(Error::IoError(e)) => return Err(ConnectError::IoError(e)),
-            // Range types weren't added until Postgres 9.2, so pg_range may not exist
-            Err(Error::DbError(ref e)) if e.code() == &SqlState::UndefinedTable => {}
-            Err(Error::DbError(e)) => return Err(ConnectError::DbError(e)),
-            _ => unreachable!()
+                                WHERE t.oid = $1",
+                               &[]) {
+            Ok(..) => {
+                self.has_typeinfo_query = true;
+                return Ok(());
+            }
+            Err(Error::DbError(ref e)) if self.is_missing_catalog(e) => {}
+            Err(e) => return Err(e),
         }
 
-        match self.raw_prepare(TYPEINFO_QUERY,
-                               "SELECT typname, typelem, NULL::OID \
-                                FROM pg_catalog.pg_type \
-                                WHERE oid = $1") {
-            Ok(..) => Ok(()),
-            Err(Error::IoError(e)) => Err(ConnectError::IoError(e)),
-            Err(Error::DbError(e)) => Err(ConnectError::DbError(e)),
-            _ => unreachable!()
-        }
+        try!(self.raw_prepare(TYPEINFO_QUERY,
+                              "SELECT typname, typelem, NULL::OID \
+                               FROM pg_catalog.pg_type \
+                               WHERE oid = $1",
+                              &[]));
+        self.has_typeinfo_query = true;
+        Ok(())
+    }
+
+    // Range types weren't added until Postgres 9.2, so pg_range may not exist,
+    // and other servers may not have it at all.
+    fn is_missing_catalog(&self, err: &DbError) -> bool {
+        err.code() == &SqlState::UndefinedTable || self.compatibility == Compatibility::Lenient
     }
 
     fn write_messages(&mut self, messages: &[FrontendMessage]) -> std_io::Result<()> {
-        debug_assert!(!self.desynchronized);
         for message in messages {
-            try_desync!(self, self.stream.write_message(message));
+            try!(self.write_message(message));
         }
         Ok(try_desync!(self, self.stream.flush()))
     }
 
+    // Writes a message without flushing it. Deferred closes are written ahead
+    // of the first message of each batch.
+    fn write_message(&mut self, message: &FrontendMessage) -> std_io::Result<()> {
+        debug_assert!(!self.desynchronized);
+        if self.idle {
+            self.idle = false;
+            try!(self.write_deferred_closes());
+        }
+        Ok(try_desync!(self, self.stream.write_message(message)))
+    }
+
+    // The closes are followed by their own Sync so that a failure doesn't
+    // abort the messages after them.
+    fn write_deferred_closes(&mut self) -> std_io::Result<()> {
+        let closes = self.deferred_closes.take();
+        if closes.is_empty() {
+            return Ok(());
+        }
+
+        debug!("closing dropped statements and portals: {:?}", closes);
+        for (variant, name) in closes {
+            try_desync!(self, self.stream.write_message(&Close {
+                variant: variant,
+                name: &name,
+            }));
+        }
+        try_desync!(self, self.stream.write_message(&Sync));
+        self.deferred_close_syncs += 1;
+        Ok(())
+    }
+
+    // Queues a statement or portal to be closed without a round trip of its
+    // own. A failure to close it is reported to the notice handler.
+    fn defer_close(&mut self, variant: u8, name: String) {
+        self.deferred_closes.push(variant, name);
+    }
+
     fn read_message_with_notification(&mut self) -> std_io::Result<BackendMessage> {
         debug_assert!(!self.desynchronized);
         loop {
@@ -558,7 +754,27 @@ impl InnerConnection {
                     }
                 }
                 ParameterStatus { parameter, value } => {
-                    self.parameters.insert(parameter, value);
+                    Arc::make_mut(&mut self.parameters).insert(parameter, value);
+                }
+                // Responses to deferred closes precede those to the batch
+                // they were written ahead of.
+                CloseComplete if self.deferred_close_syncs > 0 => {}
+                ErrorResponse { fields } if self.deferred_close_syncs > 0 => {
+                    if let Ok(err) = DbError::new_raw(fields) {
+                        self.notice_handler.handle_notice(err);
+                    }
+                }
+                ReadyForQuery { .. } if self.deferred_close_syncs > 0 => {
+                    self.deferred_close_syncs -= 1;
+                }
+                ReadyForQuery { state } => {
+                    if state == b'I' {
+                        self.transaction_ends += 1;
+                        self.rolled_back_savepoints.clear();
+                    }
+                    self.transaction_status = state;
+                    self.idle = true;
+                    return Ok(ReadyForQuery { state: state });
                 }
                 val => return Ok(val)
             }
@@ -591,14 +807,7 @@ impl InnerConnection {
             }
             AuthenticationMD5Password { salt } => {
                 let pass = try!(user.password.ok_or(ConnectError::MissingPassword));
-                let mut hasher = Md5::new();
-                let _ = hasher.input(pass.as_bytes());
-                let _ = hasher.input(user.user.as_bytes());
-                let output = hasher.result_str();
-                hasher.reset();
-                let _ = hasher.input(output.as_bytes());
-                let _ = hasher.input(&salt);
-                let output = format!("md5{}", hasher.result_str());
+                let output = md5_password(&user.user, &pass, &salt);
                 try!(self.write_messages(&[PasswordMessage {
                         password: &output
                     }]));
@@ -618,18 +827,48 @@ impl InnerConnection {
         }
     }
 
-    fn set_notice_handler(&mut self, handler: Box<HandleNotice>) -> Box<HandleNotice> {
-        mem::replace(&mut self.notice_handler, handler)
+    // Copies the state read by `Connection`'s accessors into the mirror.
+    fn update_mirror(&self) {
+        let mut mirror = self.mirror.lock();
+        mirror.parameters = self.parameters.clone();
+        mirror.trans_depth = self.trans_depth;
+        mirror.desynchronized = self.desynchronized;
+        mirror.query_protocol = self.query_protocol;
+        mirror.compatibility = self.compatibility;
+        mirror.cache_stats = StatementCacheStats {
+            len: self.statement_cache.len(),
+            capacity: self.statement_cache.capacity(),
+            hits: self.statement_cache.hits,
+            misses: self.statement_cache.misses,
+            evictions: self.statement_cache.evictions,
+        };
+    }
+
+    // Applies settings changed through the `Connection` since the last time
+    // the connection was locked.
+    fn apply_mirror(&mut self) {
+        let pending = mem::replace(&mut self.mirror.lock().pending, PendingSettings::default());
+        if let Some(protocol) = pending.query_protocol {
+            self.query_protocol = protocol;
+        }
+        if let Some(compatibility) = pending.compatibility {
+            self.compatibility = compatibility;
+        }
+        if let Some(capacity) = pending.statement_cache_capacity {
+            self.statement_cache.set_capacity(capacity);
+        }
     }
 
-    fn raw_prepare(&mut self, stmt_name: &str, query: &str) -> Result<(Vec<Type>, Vec<Column>)> {
+    fn raw_prepare(&mut self, stmt_name: &str, query: &str, types: &[Type])
+                   -> Result<(Vec<Type>, Vec<Column>)> {
         debug!("preparing query with name `{}`: {}", stmt_name, query);
 
+        let type_oids = types.iter().map(Type::oid).collect::<Vec<_>>();
         try!(self.write_messages(&[
             Parse {
                 name: stmt_name,
                 query: query,
-                param_types: &[]
+                param_types: &type_oids
             },
             Describe {
                 variant: b'S',
@@ -665,8 +904,9 @@ impl InnerConnection {
         }
 
         let mut columns = vec![];
-        for RowDescriptionEntry { name, type_oid, .. } in raw_columns {
-            columns.push(Column::new(name, try!(self.get_type(type_oid))));
+        for desc in raw_columns {
+            let type_ = try!(self.get_type(desc.type_oid));
+            columns.push(Column::new(desc, type_));
         }
 
         Ok((param_types, columns))
@@ -678,31 +918,78 @@ impl InnerConnection {
         stmt_name
     }
 
-    fn prepare<'a>(&mut self, query: &str, conn: &'a Connection) -> Result<Statement<'a>> {
+    fn prepare<'a>(&mut self, query: &str, types: &[Type], conn: ConnRef<'a>)
+                   -> Result<Statement<'a>> {
+        if self.query_protocol == QueryProtocol::Simple {
+            return Ok(Statement::new_simple(conn, query.to_owned(), types.to_owned()));
+        }
+
         let stmt_name = self.make_stmt_name();
-        let (param_types, columns) = try!(self.raw_prepare(&stmt_name, query));
-        Ok(Statement::new(conn, stmt_name, param_types, columns, Cell::new(0), false))
-    }
+        let (param_types, columns) = try!(self.raw_prepare(&stmt_name, query, types));
+        Ok(Statement::new(conn,
+                          stmt_name,
+                          query.to_owned(),
+                          param_types,
+                          columns,
+                          Cell::new(0),
+                          false))
+    }
+
+    fn prepare_cached<'a>(&mut self, query: &str, types: &[Type], conn: ConnRef<'a>)
+                          -> Result<Statement<'a>> {
+        if self.query_protocol == QueryProtocol::Simple {
+            return Ok(Statement::new_simple(conn, query.to_owned(), types.to_owned()));
+        }
 
-    fn prepare_cached<'a>(&mut self, query: &str, conn: &'a Connection) -> Result<Statement<'a>> {
-        let stmt = self.cached_statements.get(query).cloned();
+        self.close_evicted_statements();
 
-        let CachedStatement { name, param_types, columns } = match stmt {
+        let key = (query.to_owned(), types.iter().map(Type::oid).collect::<Vec<_>>());
+        let stmt = match self.statement_cache.get(&key) {
             Some(stmt) => stmt,
             None => {
                 let stmt_name = self.make_stmt_name();
-                let (param_types, columns) = try!(self.raw_prepare(&stmt_name, query));
+                let (param_types, columns) = try!(self.raw_prepare(&stmt_name, query, types));
                 let stmt = CachedStatement {
                     name: stmt_name,
                     param_types: param_types,
-                    columns: columns,
+                    columns: Arc::new(columns),
+                    handle: Arc::new(()),
                 };
-                self.cached_statements.insert(query.to_owned(), stmt.clone());
+                self.statement_cache.insert(key, stmt.clone());
                 stmt
             }
         };
 
-        Ok(Statement::new(conn, name, param_types, columns, Cell::new(0), true))
+        Ok(Statement::new_cached(conn, query.to_owned(), stmt))
+    }
+
+    // Prepares a replacement for a cached statement whose plan has been
+    // invalidated by the server. The replacement takes over the stale
+    // statement's cache entry, and the stale statement is closed once it is
+    // no longer in use.
+    fn reprepare_cached(&mut self, stale_name: &str, query: &str, types: &[Type])
+                        -> Result<CachedStatement> {
+        let key = self.statement_cache.invalidate(stale_name);
+        let stmt_name = self.make_stmt_name();
+        let (param_types, columns) = try!(self.raw_prepare(&stmt_name, query, types));
+        let stmt = CachedStatement {
+            name: stmt_name,
+            param_types: param_types,
+            columns: Arc::new(columns),
+            handle: Arc::new(()),
+        };
+        match key {
+            Some(key) => self.statement_cache.insert(key, stmt.clone()),
+            None => self.statement_cache.retire(stmt.clone()),
+        }
+        Ok(stmt)
+    }
+
+    // Closes statements evicted from the cache which are no longer in use.
+    fn close_evicted_statements(&mut self) {
+        for name in self.statement_cache.take_closable() {
+            self.defer_close(b'S', name);
+        }
     }
 
     fn close_statement(&mut self, name: &str, type_: u8) -> Result<()> {
@@ -730,9 +1017,161 @@ impl InnerConnection {
             return Ok(ty.clone());
         }
 
+        let info = match self.query_protocol {
+            QueryProtocol::Extended => self.read_type_info(oid),
+            QueryProtocol::Simple => self.read_type_info_simple(oid),
+        };
+        let (name, elem_oid, rngsubtype) = match info {
+            Ok(Some(info)) => info,
+            // The placeholder isn't cached, since the lookup may succeed later
+            Ok(None) if self.compatibility == Compatibility::Lenient => {
+                return Ok(placeholder_type(oid));
+            }
+            Err(Error::DbError(ref e)) if self.compatibility == Compatibility::Lenient &&
+                                          is_undefined(e) => {
+                return Ok(placeholder_type(oid));
+            }
+            Ok(None) => return Err(Error::IoError(bad_response())),
+            Err(err) => return Err(err),
+        };
+
+        let kind = if elem_oid != 0 {
+            Kind::Array(try!(self.get_type(elem_oid)))
+        } else {
+            match rngsubtype {
+                Some(oid) => Kind::Range(try!(self.get_type(oid))),
+                None => Kind::Simple
+            }
+        };
+
+        let type_ = Type::Other(Box::new(Other::new(name, oid, kind)));
+        self.unknown_types.insert(oid, type_.clone());
+        Ok(type_)
+    }
+
+    fn get_column_origins(&mut self, columns: &[Column]) -> Result<Vec<Option<ColumnOrigin>>> {
+        check_desync!(self);
+        let mut missing = columns.iter()
+                                 .filter_map(|c| match (c.table_oid(), c.column_id()) {
+                                     (Some(table), Some(column)) => Some((table, column)),
+                                     _ => None,
+                                 })
+                                 .filter(|key| !self.column_origins.contains_key(key))
+                                 .map(|(table, column)| format!("({}, {})", table, column))
+                                 .collect::<Vec<_>>();
+        missing.sort();
+        missing.dedup();
+
+        if !missing.is_empty() {
+            let rows = try!(self.quick_query(&format!("SELECT a.attrelid, a.attnum, n.nspname, \
+                                                           c.relname, a.attname, a.attnotnull \
+                                                       FROM pg_catalog.pg_attribute a \
+                                                       JOIN pg_catalog.pg_class c \
+                                                           ON c.oid = a.attrelid \
+                                                       JOIN pg_catalog.pg_namespace n \
+                                                           ON n.oid = c.relnamespace \
+                                                       WHERE (a.attrelid, a.attnum) IN ({})",
+                                                      missing.join(", "))));
+            for row in rows {
+                let mut row = row.into_iter();
+                let mut next = || match row.next() {
+                    Some(Some(value)) => Ok(value),
+                    _ => Err(Error::IoError(bad_response())),
+                };
+                let table = try!(try!(next()).parse().map_err(|_| Error::IoError(bad_response())));
+                let column = try!(try!(next()).parse().map_err(|_| Error::IoError(bad_response())));
+                let origin = ColumnOrigin::new(try!(next()),
+                                               try!(next()),
+                                               try!(next()),
+                                               try!(next()) == "t");
+                self.column_origins.insert((table, column), origin);
+            }
+        }
+
+        Ok(columns.iter()
+                  .map(|c| match (c.table_oid(), c.column_id()) {
+                      (Some(table), Some(column)) => {
+                          self.column_origins.get(&(table, column)).cloned()
+                      }
+                      _ => None,
+                  })
+                  .collect())
+    }
+
+    fn prepare_named<'a>(&mut self, query: &str, conn: ConnRef<'a>)
+                         -> Result<NamedStatement<'a>> {
+        let backslash_escapes = literal::backslash_escapes(&SessionInfo::new(&self.parameters));
+        let (query, names) = match sql::rewrite_named(query, backslash_escapes) {
+            Some(rewritten) => rewritten,
+            None => {
+                return Err(misuse("named and positional parameters cannot be mixed"));
+            }
+        };
+        let stmt = try!(self.prepare(&query, &[], conn));
+        Ok(NamedStatement::new(stmt, names))
+    }
+
+    fn prepare_checked<'a, P, R>(&mut self, query: &str, conn: ConnRef<'a>)
+                                 -> Result<TypedStatement<'a, P, R>>
+            where P: Params, R: RowType {
+        if self.query_protocol == QueryProtocol::Simple {
+            return Err(misuse("typed statements are not supported in simple query mode"));
+        }
+        TypedStatement::new(try!(self.prepare(query, &[], conn)))
+    }
+
+    fn type_by_name(&mut self, name: &str) -> Result<Type> {
+        let literal = try!(literal::render_param(&name, Some(&Type::Text),
+                                                 &SessionInfo::new(&self.parameters)));
+        let result = try!(self.quick_query(&format!("SELECT {}::REGTYPE::OID", literal)));
+        let oid = match result.into_iter().next().and_then(|r| r.into_iter().next()) {
+            Some(Some(oid)) => try!(oid.parse().map_err(|_| Error::IoError(bad_response()))),
+            _ => return Err(Error::IoError(bad_response())),
+        };
+        self.get_type(oid)
+    }
+
+    fn read_type_info_simple(&mut self, oid: Oid) -> Result<Option<(String, Oid, Option<Oid>)>> {
+        let result = match self.quick_query(&format!("SELECT t.typname, t.typelem, r.rngsubtype \
+                                                      FROM pg_catalog.pg_type t \
+                                                      LEFT OUTER JOIN pg_catalog.pg_range r \
+                                                          ON r.rngtypid = t.oid \
+                                                      WHERE t.oid = {}", oid)) {
+            Ok(result) => result,
+            Err(Error::DbError(ref e)) if self.is_missing_catalog(e) => {
+                try!(self.quick_query(&format!("SELECT typname, typelem, NULL::OID \
+                                                FROM pg_catalog.pg_type \
+                                                WHERE oid = {}", oid)))
+            }
+            Err(e) => return Err(e),
+        };
+
+        let row = match result.into_iter().next() {
+            Some(row) => row,
+            None => return Ok(None),
+        };
+        let mut row = row.into_iter();
+        let name = match row.next() {
+            Some(Some(name)) => name,
+            _ => return Err(Error::IoError(bad_response())),
+        };
+        let elem_oid = match row.next() {
+            Some(Some(oid)) => try!(oid.parse().map_err(|_| Error::IoError(bad_response()))),
+            _ => return Err(Error::IoError(bad_response())),
+        };
+        let rngsubtype = match row.next() {
+            Some(Some(oid)) => Some(try!(oid.parse().map_err(|_| Error::IoError(bad_response())))),
+            _ => None,
+        };
+        Ok(Some((name, elem_oid, rngsubtype)))
+    }
+
+    fn read_type_info(&mut self, oid: Oid) -> Result<Option<(String, Oid, Option<Oid>)>> {
+        try!(self.setup_typeinfo_query());
+
         // Ew @ doing this manually :(
         let mut buf = vec![];
-        let value = match try!(oid.to_sql_checked(&Type::Oid, &mut buf, &SessionInfo::new(self))) {
+        let value = match try!(oid.to_sql_checked(&Type::Oid, &mut buf, &SessionInfo::new(&self.parameters))) {
             IsNull::Yes => None,
             IsNull::No => Some(buf),
         };
@@ -757,10 +1196,9 @@ impl InnerConnection {
             }
             _ => bad_response!(self)
         }
-        let (name, elem_oid, rngsubtype): (String, Oid, Option<Oid>) =
-                match try!(self.read_message()) {
+        let info: (String, Oid, Option<Oid>) = match try!(self.read_message()) {
             DataRow { row } => {
-                let ctx = SessionInfo::new(self);
+                let ctx = SessionInfo::new(&self.parameters);
                 (try!(FromSql::from_sql_nullable(&Type::Name,
                                                  row[0].as_ref().map(|r| &**r).as_mut(),
                                                  &ctx)),
@@ -771,6 +1209,11 @@ impl InnerConnection {
                                                  row[2].as_ref().map(|r| &**r).as_mut(),
                                                  &ctx)))
             }
+            // No such type
+            CommandComplete { .. } => {
+                try!(self.wait_for_ready());
+                return Ok(None);
+            }
             ErrorResponse { fields } => {
                 try!(self.wait_for_ready());
                 return DbError::new(fields);
@@ -787,24 +1230,27 @@ impl InnerConnection {
         }
         try!(self.wait_for_ready());
 
-        let kind = if elem_oid != 0 {
-            Kind::Array(try!(self.get_type(elem_oid)))
-        } else {
-            match rngsubtype {
-                Some(oid) => Kind::Range(try!(self.get_type(oid))),
-                None => Kind::Simple
-            }
-        };
-
-        let type_ = Type::Other(Box::new(Other::new(name, oid, kind)));
-        self.unknown_types.insert(oid, type_.clone());
-        Ok(type_)
+        Ok(Some(info))
     }
 
     fn is_desynchronized(&self) -> bool {
         self.desynchronized
     }
 
+    fn transaction_scope(&self) -> TransactionScope {
+        TransactionScope {
+            transaction_ends: self.transaction_ends,
+            savepoints: self.savepoints.clone(),
+        }
+    }
+
+    // Determines if the transaction and savepoints open when the scope was
+    // taken are still open, or were released into a transaction which is.
+    fn in_scope(&self, scope: &TransactionScope) -> bool {
+        self.transaction_ends == scope.transaction_ends &&
+            !scope.savepoints.iter().any(|id| self.rolled_back_savepoints.contains(id))
+    }
+
     fn wait_for_ready(&mut self) -> Result<()> {
         match try!(self.read_message()) {
             ReadyForQuery { .. } => Ok(()),
@@ -843,6 +1289,57 @@ impl InnerConnection {
         Ok(result)
     }
 
+    fn simple_query(&mut self, query: &str) -> Result<Vec<SimpleQueryResult>> {
+        check_desync!(self);
+        debug!("executing query: {}", query);
+        try!(self.write_messages(&[Query { query: query }]));
+
+        let mut responses = vec![];
+        let mut descriptions = vec![];
+        let mut rows = vec![];
+        let mut error = None;
+        loop {
+            match try!(self.read_message()) {
+                ReadyForQuery { .. } => break,
+                RowDescription { descriptions: d } => descriptions = d,
+                DataRow { row } => {
+                    rows.push(row.into_iter().map(|opt| {
+                        opt.map(|b| String::from_utf8_lossy(&b).into_owned())
+                    }).collect());
+                }
+                CommandComplete { tag } => {
+                    responses.push((mem::replace(&mut descriptions, vec![]),
+                                    mem::replace(&mut rows, vec![]),
+                                    tag));
+                }
+                CopyInResponse { .. } => {
+                    try!(self.write_messages(&[
+                        CopyFail {
+                            message: "COPY queries cannot be directly executed",
+                        }]));
+                }
+                ErrorResponse { fields } => error = Some(fields),
+                _ => {}
+            }
+        }
+        if let Some(fields) = error {
+            return DbError::new(fields);
+        }
+
+        // Types can only be looked up once the server is ready for another
+        // query.
+        let mut results = Vec::with_capacity(responses.len());
+        for (descriptions, rows, tag) in responses {
+            let mut columns = Vec::with_capacity(descriptions.len());
+            for desc in descriptions {
+                let type_ = try!(self.get_type(desc.type_oid));
+                columns.push(Column::new(desc, type_));
+            }
+            results.push(SimpleQueryResult::new(columns, rows, CommandTag::new(tag)));
+        }
+        Ok(results)
+    }
+
     fn finish_inner(&mut self) -> Result<()> {
         check_desync!(self);
         try!(self.write_messages(&[Terminate]));
@@ -850,30 +1347,129 @@ impl InnerConnection {
     }
 }
 
+// The type reported for an OID whose definition can't be looked up on a
+// server which doesn't have the Postgres system catalogs.
+fn placeholder_type(oid: Oid) -> Type {
+    Type::Other(Box::new(Other::new(oid.to_string(), oid, Kind::Simple)))
+}
+
+fn is_undefined(err: &DbError) -> bool {
+    match *err.code() {
+        SqlState::UndefinedTable | SqlState::UndefinedObject => true,
+        _ => false,
+    }
+}
+
+// Prepares a query as the unnamed statement.
+fn prepare_unnamed<'a>(inner: &mut InnerConnection,
+                       conn: ConnRef<'a>,
+                       query: &str,
+                       types: &[Type])
+                       -> Result<Statement<'a>> {
+    if inner.query_protocol == QueryProtocol::Simple {
+        return Ok(Statement::new_simple(conn, query.to_owned(), types.to_owned()));
+    }
+
+    let (param_types, columns) = try!(inner.raw_prepare("", query, types));
+    Ok(Statement::new(conn,
+                      "".to_owned(),
+                      query.to_owned(),
+                      param_types,
+                      columns,
+                      Cell::new(0),
+                      true))
+}
+
+// Runs a query through the unnamed statement.
+fn query_unnamed<'a>(conn: ConnRef<'a>, query: &str, params: &[&ToSql]) -> Result<Rows<'a>> {
+    let mut inner = try!(conn.lock());
+    let stmt = try!(prepare_unnamed(&mut inner, conn.clone(), query, &[]));
+    stmt.query_locked(&mut inner, params)
+}
+
+fn single_row<'a>(rows: Rows<'a>) -> Result<Option<Row<'a>>> {
+    match rows.len() {
+        0 => Ok(None),
+        1 => Ok(rows.into_iter().next()),
+        count => Err(Error::RowCount(count)),
+    }
+}
+
 fn _ensure_send() {
     fn _is_send<T: Send>() {}
     _is_send::<Connection>();
 }
 
+fn _ensure_sync() {
+    fn _is_sync<T: ::std::marker::Sync>() {}
+    _is_sync::<Connection>();
+}
+
 /// A connection to a Postgres database.
+///
+/// A `Connection` may be shared between threads, for example by storing it in
+/// an `Arc`. Access to the underlying session is serialized by an internal
+/// lock, so a call made while another thread is using the connection blocks
+/// until that use completes.
+///
+/// A thread which is already using the connection, for example by holding a
+/// `CopyOutReader`, may not use it again until it is done. Methods called in
+/// that case return an `Error::Misuse` rather than blocking forever. Accessors
+/// such as `is_active` and `parameter` still work, and settings such as
+/// `set_query_protocol` take effect once the connection is next used.
+///
+/// A connection is a single session on the server, and it has at most one
+/// transaction open at a time. If one thread begins a transaction, every
+/// statement run on the connection by any thread until that transaction ends
+/// is part of it, and another thread calling `transaction` in the meantime
+/// receives an `Error::Misuse`. Threads which need independent transactions
+/// should use separate connections.
 pub struct Connection {
-    conn: RefCell<InnerConnection>
+    conn: Lock<InnerConnection>,
+    cancel_data: CancelData,
+    deferred_closes: DeferredCloses,
+    notice_handler: NoticeHandler,
+    mirror: Mirror,
 }
 
 impl fmt::Debug for Connection {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        let conn = self.conn.borrow();
+        let conn = match self.lock_inner() {
+            Some(conn) => conn,
+            None => return DebugStruct::new(fmt, "Connection").field("in_use", &true).finish(),
+        };
         DebugStruct::new(fmt, "Connection")
             .field("cancel_data", &conn.cancel_data)
             .field("notifications", &conn.notifications.len())
             .field("transaction_depth", &conn.trans_depth)
             .field("desynchronized", &conn.desynchronized)
-            .field("cached_statements", &conn.cached_statements.len())
+            .field("cached_statements", &conn.statement_cache.len())
             .finish()
     }
 }
 
 impl Connection {
+    fn lock_inner(&self) -> Option<ConnGuard> {
+        self.conn.lock().map(|mut conn| {
+            // A panic may have interrupted an exchange with the server
+            if conn.poisoned() {
+                conn.desynchronized = true;
+            }
+            conn.apply_mirror();
+            ConnGuard(conn)
+        })
+    }
+
+    fn lock(&self) -> Result<ConnGuard> {
+        self.lock_inner().ok_or_else(in_use)
+    }
+
+    // Like `InnerConnection::defer_close`, but doesn't need the connection's
+    // lock, so it works while the connection is in use.
+    fn defer_close(&self, variant: u8, name: String) {
+        self.deferred_closes.push(variant, name);
+    }
+
     /// Creates a new connection to a Postgres database.
     ///
     /// Most applications can use a URL string in the normal format:
@@ -933,13 +1529,23 @@ impl Connection {
     pub fn connect<T>(params: T, ssl: &SslMode) -> result::Result<Connection, ConnectError>
             where T: IntoConnectParams {
         InnerConnection::connect(params, ssl).map(|conn| {
-            Connection { conn: RefCell::new(conn) }
+            Connection {
+                cancel_data: conn.cancel_data,
+                deferred_closes: conn.deferred_closes.clone(),
+                notice_handler: conn.notice_handler.clone(),
+                mirror: conn.mirror.clone(),
+                conn: Lock::new(conn),
+            }
         })
     }
 
     /// Sets the notice handler for the connection, returning the old handler.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if called from within the connection's notice handler.
     pub fn set_notice_handler(&self, handler: Box<HandleNotice>) -> Box<HandleNotice> {
-        self.conn.borrow_mut().set_notice_handler(handler)
+        self.notice_handler.replace(handler)
     }
 
     /// Returns an iterator over asynchronous notification messages.
@@ -969,7 +1575,7 @@ impl Connection {
     ///     Err(err) => panic!("Error preparing statement: {:?}", err)
     /// };
     pub fn prepare<'a>(&'a self, query: &str) -> Result<Statement<'a>> {
-        self.conn.borrow_mut().prepare(query, self)
+        try!(self.lock()).prepare(query, &[], ConnRef::Borrowed(self))
     }
 
     /// Creates cached prepared statement.
@@ -979,6 +1585,16 @@ impl Connection {
     /// is going to be used frequently, caching it can improve performance by
     /// reducing the number of round trips to the Postgres backend.
     ///
+    /// The cache holds at most `statement_cache_capacity` statements. When it
+    /// is full, the least recently used statement is evicted, and closed on
+    /// the server once no `Statement` created from it remains.
+    ///
+    /// If the server reports that a cached statement's plan is no longer
+    /// valid, for example because a table it uses was altered or the
+    /// statement was removed by `DISCARD ALL`, the statement is dropped from
+    /// the cache. When this happens outside of a transaction, the statement
+    /// is prepared again and the execution retried once.
+    ///
     /// ## Example
     ///
     /// ```rust,no_run
@@ -993,7 +1609,87 @@ impl Connection {
     /// # Ok(()) };
     /// ```
     pub fn prepare_cached<'a>(&'a self, query: &str) -> Result<Statement<'a>> {
-        self.conn.borrow_mut().prepare_cached(query, self)
+        try!(self.lock()).prepare_cached(query, &[], ConnRef::Borrowed(self))
+    }
+
+    /// Like `prepare`, except that the types of the statement's parameters
+    /// are specified rather than inferred by the server.
+    ///
+    /// `types` may specify fewer types than the statement has parameters;
+    /// the server infers the types of the rest. `Type::Other` values for
+    /// custom types can be looked up with `type_by_name`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, SslMode};
+    /// # use postgres::types::Type;
+    /// # let conn = Connection::connect("", &SslMode::None).unwrap();
+    /// let stmt = conn.prepare_typed("SELECT $1", &[Type::Int8]).unwrap();
+    /// let value: i64 = stmt.query(&[&1i64]).unwrap().get(0).get(0);
+    /// # let _ = value;
+    /// ```
+    pub fn prepare_typed<'a>(&'a self, query: &str, types: &[Type]) -> Result<Statement<'a>> {
+        try!(self.lock()).prepare(query, types, ConnRef::Borrowed(self))
+    }
+
+    /// Like `prepare_cached`, except that the types of the statement's
+    /// parameters are specified as with `prepare_typed`.
+    ///
+    /// The same query prepared with different types is cached separately.
+    pub fn prepare_typed_cached<'a>(&'a self, query: &str, types: &[Type])
+                                    -> Result<Statement<'a>> {
+        try!(self.lock()).prepare_cached(query, types, ConnRef::Borrowed(self))
+    }
+
+    /// Creates a new prepared statement whose parameters are referred to by
+    /// name rather than position.
+    ///
+    /// See `NamedStatement` for the placeholder syntax.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, SslMode};
+    /// # use postgres::types::ToSql;
+    /// # use std::collections::HashMap;
+    /// # let conn = Connection::connect("", &SslMode::None).unwrap();
+    /// let stmt = conn.prepare_named("UPDATE foo SET bar = :bar WHERE baz = :baz").unwrap();
+    /// let mut params: HashMap<&str, &ToSql> = HashMap::new();
+    /// params.insert("bar", &"hello");
+    /// params.insert("baz", &1i32);
+    /// stmt.execute(&params).unwrap();
+    /// ```
+    pub fn prepare_named<'a>(&'a self, query: &str) -> Result<NamedStatement<'a>> {
+        try!(self.lock()).prepare_named(query, ConnRef::Borrowed(self))
+    }
+
+    /// Creates a new prepared statement whose parameter and row types are
+    /// checked against the tuple types `P` and `R`.
+    ///
+    /// See `TypedStatement` for details. Typed statements are not supported
+    /// in simple query mode.
+    pub fn prepare_checked<'a, P, R>(&'a self, query: &str) -> Result<TypedStatement<'a, P, R>>
+            where P: Params, R: RowType {
+        try!(self.lock()).prepare_checked(query, ConnRef::Borrowed(self))
+    }
+
+    /// Looks up a type by name.
+    ///
+    /// The name is interpreted as it would be in SQL, so it may be qualified
+    /// with a schema, and `[]` may be appended to look up an array type.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, SslMode};
+    /// # let conn = Connection::connect("", &SslMode::None).unwrap();
+    /// let mood = conn.type_by_name("mood").unwrap();
+    /// let stmt = conn.prepare_typed("SELECT $1::TEXT", &[mood]).unwrap();
+    /// # let _ = stmt;
+    /// ```
+    pub fn type_by_name(&self, name: &str) -> Result<Type> {
+        try!(self.lock()).type_by_name(name)
     }
 
     /// Begins a new transaction.
@@ -1006,9 +1702,7 @@ impl Connection {
     /// A transaction will roll back by default. The `set_commit`,
     /// `set_rollback`, and `commit` methods alter this behavior.
     ///
-    /// ## Panics
-    ///
-    /// Panics if a transaction is already active.
+    /// Returns `Error::Misuse` if a transaction is already active.
     ///
     /// ## Example
     ///
@@ -1025,17 +1719,7 @@ impl Connection {
     /// # }
     /// ```
     pub fn transaction<'a>(&'a self) -> Result<Transaction<'a>> {
-        let mut conn = self.conn.borrow_mut();
-        check_desync!(conn);
-        assert!(conn.trans_depth == 0, "`transaction` must be called on the active transaction");
-        try!(conn.quick_query("BEGIN"));
-        conn.trans_depth += 1;
-        Ok(Transaction {
-            conn: self,
-            commit: Cell::new(false),
-            depth: 1,
-            finished: false,
-        })
+        begin_transaction(ConnRef::Borrowed(self))
     }
 
     /// Sets the isolation level which will be used for future transactions.
@@ -1049,7 +1733,7 @@ impl Connection {
 
     /// Returns the isolation level which will be used for future transactions.
     pub fn transaction_isolation(&self) -> Result<IsolationLevel> {
-        let mut conn = self.conn.borrow_mut();
+        let mut conn = try!(self.lock());
         check_desync!(conn);
         let result = try!(conn.quick_query("SHOW TRANSACTION ISOLATION LEVEL"));
         IsolationLevel::parse(result[0][0].as_ref().unwrap())
@@ -1062,36 +1746,118 @@ impl Connection {
     ///
     /// On success, returns the number of rows modified or 0 if not applicable.
     ///
-    /// ## Panics
-    ///
-    /// Panics if the number of parameters provided does not match the number
-    /// expected.
+    /// Returns `Error::Misuse` if the number of parameters provided does not
+    /// match the number expected.
     pub fn execute(&self, query: &str, params: &[&ToSql]) -> Result<u64> {
-        let (param_types, columns) = try!(self.conn.borrow_mut().raw_prepare("", query));
-        let stmt = Statement::new(self, "".to_owned(), param_types, columns, Cell::new(0), true);
-        stmt.execute(params)
+        self.execute_tagged(query, params).map(|tag| tag.rows().unwrap_or(0))
     }
 
-    /// Execute a sequence of SQL statements.
-    ///
-    /// Statements should be separated by `;` characters. If an error occurs,
-    /// execution of the sequence will stop at that point. This is intended for
-    /// execution of batches of non-dynamic statements - for example, creation
-    /// of a schema for a fresh database.
-    ///
-    /// ## Warning
-    ///
-    /// Prepared statements should be used for any SQL statement which contains
-    /// user-specified data, as it provides functionality to safely embed that
-    /// data in the statement. Do not form statements via string concatenation
-    /// and feed them into this method.
+    /// Like `execute`, except that it returns the command tag sent by the
+    /// server.
     ///
     /// ## Example
     ///
     /// ```rust,no_run
-    /// # use postgres::{Connection, Result};
-    /// fn init_db(conn: &Connection) -> Result<()> {
-    ///     conn.batch_execute("
+    /// # use postgres::{Connection, SslMode, CommandKind};
+    /// # let conn = Connection::connect("", &SslMode::None).unwrap();
+    /// let tag = conn.execute_tagged("UPDATE foo SET bar = 1", &[]).unwrap();
+    /// assert_eq!(tag.kind(), CommandKind::Update);
+    /// println!("{} rows updated", tag.rows().unwrap());
+    /// ```
+    ///
+    /// Returns `Error::Misuse` if the number of parameters provided does not
+    /// match the number expected.
+    pub fn execute_tagged(&self, query: &str, params: &[&ToSql]) -> Result<CommandTag> {
+        let mut conn = try!(self.lock());
+        let stmt = try!(prepare_unnamed(&mut conn, ConnRef::Borrowed(self), query, &[]));
+        stmt.execute_locked(&mut conn, params)
+    }
+
+    /// A convenience function for queries that are only run once, returning
+    /// the resulting rows.
+    ///
+    /// The query is prepared as the unnamed statement, and the returned rows
+    /// do not borrow any `Statement`.
+    ///
+    /// If an error is returned, it could have come from either the preparation
+    /// or execution of the statement.
+    ///
+    /// Returns `Error::Misuse` if the number of parameters provided does not
+    /// match the number expected.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, SslMode};
+    /// # let conn = Connection::connect("", &SslMode::None).unwrap();
+    /// for row in &conn.query("SELECT foo FROM bar WHERE baz = $1", &[&1i32]).unwrap() {
+    ///     let foo: String = row.get(0);
+    ///     println!("foo: {}", foo);
+    /// }
+    /// ```
+    pub fn query<'a>(&'a self, query: &str, params: &[&ToSql]) -> Result<Rows<'a>> {
+        query_unnamed(ConnRef::Borrowed(self), query, params)
+    }
+
+    /// Like `query`, but returns the single row returned by the query.
+    ///
+    /// Returns an `Error::RowCount` if the query does not return exactly one
+    /// row.
+    ///
+    /// Returns `Error::Misuse` if the number of parameters provided does not
+    /// match the number expected.
+    pub fn query_one<'a>(&'a self, query: &str, params: &[&ToSql]) -> Result<Row<'a>> {
+        try!(single_row(try!(self.query(query, params)))).ok_or(Error::RowCount(0))
+    }
+
+    /// Like `query`, but returns the row returned by the query, if any.
+    ///
+    /// Returns an `Error::RowCount` if the query returns more than one row.
+    ///
+    /// Returns `Error::Misuse` if the number of parameters provided does not
+    /// match the number expected.
+    pub fn query_opt<'a>(&'a self, query: &str, params: &[&ToSql]) -> Result<Option<Row<'a>>> {
+        single_row(try!(self.query(query, params)))
+    }
+
+    /// Like `query_one`, but returns the value of the first column of the
+    /// row.
+    ///
+    /// Returns `Error::Misuse` if the number of parameters provided does not
+    /// match the number expected.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, SslMode};
+    /// # let conn = Connection::connect("", &SslMode::None).unwrap();
+    /// let count: i64 = conn.query_scalar("SELECT COUNT(*) FROM foo", &[]).unwrap();
+    /// # let _ = count;
+    /// ```
+    pub fn query_scalar<T>(&self, query: &str, params: &[&ToSql]) -> Result<T> where T: FromSql {
+        try!(self.query_one(query, params)).get_opt(0)
+    }
+
+    /// Execute a sequence of SQL statements.
+    ///
+    /// Statements should be separated by `;` characters. If an error occurs,
+    /// execution of the sequence will stop at that point. This is intended for
+    /// execution of batches of non-dynamic statements - for example, creation
+    /// of a schema for a fresh database.
+    ///
+    /// ## Warning
+    ///
+    /// Prepared statements should be used for any SQL statement which contains
+    /// user-specified data, as it provides functionality to safely embed that
+    /// data in the statement. Do not form statements via string concatenation
+    /// and feed them into this method.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, Result};
+    /// fn init_db(conn: &Connection) -> Result<()> {
+    ///     conn.batch_execute("
     ///         CREATE TABLE person (
     ///             id SERIAL PRIMARY KEY,
     ///             name NOT NULL
@@ -1108,7 +1874,84 @@ impl Connection {
     /// }
     /// ```
     pub fn batch_execute(&self, query: &str) -> Result<()> {
-        self.conn.borrow_mut().quick_query(query).map(|_| ())
+        try!(self.lock()).quick_query(query).map(|_| ())
+    }
+
+    /// Executes a sequence of SQL statements using the simple query protocol,
+    /// returning the results of each statement.
+    ///
+    /// Unlike `batch_execute`, the columns, rows and command tag of each
+    /// statement are returned, in the order the statements were executed.
+    /// Values are returned in Postgres's text format. Statements which do not
+    /// complete normally, such as empty statements, produce no result.
+    ///
+    /// If any statement fails, its error is returned and the results of the
+    /// statements before it are discarded.
+    ///
+    /// ## Warning
+    ///
+    /// As with `batch_execute`, prepared statements should be used for any
+    /// SQL statement which contains user-specified data.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, SslMode};
+    /// # let conn = Connection::connect("", &SslMode::None).unwrap();
+    /// for result in conn.simple_query("UPDATE foo SET bar = 1; SELECT * FROM foo").unwrap() {
+    ///     println!("{}: {} row(s)", result.tag(), result.rows().len());
+    /// }
+    /// ```
+    pub fn simple_query(&self, query: &str) -> Result<Vec<SimpleQueryResult>> {
+        try!(self.lock()).simple_query(query)
+    }
+
+    /// Returns a `Pipeline` which executes several statements with a single
+    /// round trip to the server.
+    ///
+    /// See the documentation of `Pipeline` for details.
+    pub fn pipeline<'a>(&'a self) -> Pipeline<'a> {
+        Pipeline::new(self)
+    }
+
+    /// Sets the protocol used to execute queries.
+    ///
+    /// Statements prepared before the protocol is changed continue to use the
+    /// protocol they were prepared with.
+    ///
+    /// In simple query mode, `prepare` and `prepare_cached` do not contact the
+    /// server. The parameter types of the resulting `Statement` are not known
+    /// and its columns are only known from the `Rows` returned by `query`.
+    /// Parameters are interpolated into the query as literals, so the
+    /// `client_encoding` of the session must be `UTF8`. If a query consists
+    /// of multiple statements, `query` returns the rows of the last one.
+    pub fn set_query_protocol(&self, protocol: QueryProtocol) {
+        let mut mirror = self.mirror.lock();
+        mirror.query_protocol = protocol;
+        mirror.pending.query_protocol = Some(protocol);
+    }
+
+    /// Returns the protocol used to execute queries.
+    pub fn query_protocol(&self) -> QueryProtocol {
+        self.mirror.lock().query_protocol
+    }
+
+    /// Sets how closely the server is expected to match the behavior of
+    /// Postgres.
+    ///
+    /// This should be set to `Compatibility::Lenient` when connecting to
+    /// servers which implement the Postgres wire protocol but are not Postgres
+    /// themselves.
+    pub fn set_compatibility(&self, compatibility: Compatibility) {
+        let mut mirror = self.mirror.lock();
+        mirror.compatibility = compatibility;
+        mirror.pending.compatibility = Some(compatibility);
+    }
+
+    /// Returns how closely the server is expected to match the behavior of
+    /// Postgres.
+    pub fn compatibility(&self) -> Compatibility {
+        self.mirror.lock().compatibility
     }
 
     /// Returns information used to cancel pending queries.
@@ -1116,13 +1959,13 @@ impl Connection {
     /// Used with the `cancel_query` function. The object returned can be used
     /// to cancel any query executed by the connection it was created from.
     pub fn cancel_data(&self) -> CancelData {
-        self.conn.borrow().cancel_data
+        self.cancel_data
     }
 
     /// Returns the value of the specified Postgres backend parameter, such as
     /// `timezone` or `server_version`.
     pub fn parameter(&self, param: &str) -> Option<String> {
-        self.conn.borrow().parameters.get(param).cloned()
+        self.mirror.lock().parameters.get(param).cloned()
     }
 
     /// Returns whether or not the stream has been desynchronized due to an
@@ -1131,7 +1974,7 @@ impl Connection {
     /// If this has occurred, all further queries will immediately return an
     /// error.
     pub fn is_desynchronized(&self) -> bool {
-        self.conn.borrow().is_desynchronized()
+        self.mirror.lock().desynchronized
     }
 
     /// Determines if the `Connection` is currently "active", that is, if there
@@ -1140,7 +1983,46 @@ impl Connection {
     /// The `transaction` method can only be called on the active `Connection`
     /// or `Transaction`.
     pub fn is_active(&self) -> bool {
-        self.conn.borrow().trans_depth == 0
+        self.mirror.lock().trans_depth == 0
+    }
+
+    /// Returns the maximum number of statements cached by `prepare_cached`.
+    ///
+    /// Defaults to `DEFAULT_STATEMENT_CACHE_CAPACITY`.
+    pub fn statement_cache_capacity(&self) -> usize {
+        self.mirror.lock().cache_stats.capacity
+    }
+
+    /// Sets the maximum number of statements cached by `prepare_cached`.
+    ///
+    /// If more statements are cached, the least recently used are evicted.
+    /// A capacity of 0 disables caching.
+    pub fn set_statement_cache_capacity(&self, capacity: usize) {
+        {
+            let mut mirror = self.mirror.lock();
+            mirror.cache_stats.capacity = capacity;
+            mirror.pending.statement_cache_capacity = Some(capacity);
+        }
+        // Evict any excess statements now unless the connection is in use
+        drop(self.lock_inner());
+    }
+
+    /// Returns statistics about the statements cached by `prepare_cached`.
+    pub fn statement_cache_stats(&self) -> StatementCacheStats {
+        self.mirror.lock().cache_stats
+    }
+
+    /// Removes every statement from the cache used by `prepare_cached`.
+    ///
+    /// Statements which are not in use are closed on the server along with
+    /// the next batch of messages sent on the connection, and the rest once
+    /// the last `Statement` using them is dropped.
+    pub fn clear_statement_cache(&self) -> Result<()> {
+        let mut conn = try!(self.lock());
+        check_desync!(conn);
+        conn.statement_cache.clear();
+        conn.close_evicted_statements();
+        Ok(())
     }
 
     /// Consumes the connection, closing it.
@@ -1148,17 +2030,233 @@ impl Connection {
     /// Functionally equivalent to the `Drop` implementation for `Connection`
     /// except that it returns any error encountered to the caller.
     pub fn finish(self) -> Result<()> {
-        let mut conn = self.conn.borrow_mut();
+        let mut conn = try!(self.lock());
         conn.finished = true;
         conn.finish_inner()
     }
 }
 
+fn begin_transaction<'a>(conn_ref: ConnRef<'a>) -> Result<Transaction<'a>> {
+    {
+        let mut conn = try!(conn_ref.lock());
+        check_desync!(conn);
+        if conn.trans_depth != 0 {
+            return Err(misuse("`transaction` must be called on the active transaction"));
+        }
+        try!(conn.quick_query("BEGIN"));
+        conn.trans_depth += 1;
+    }
+    Ok(Transaction {
+        conn: conn_ref,
+        commit: Cell::new(false),
+        depth: 1,
+        finished: false,
+    })
+}
+
+/// A queue of statements and portals to close ahead of the next batch of
+/// messages sent on a connection.
+///
+/// The queue has its own lock, which is only held briefly, so that handles
+/// can be dropped while the connection is in use by the current thread.
+#[derive(Clone, Default)]
+struct DeferredCloses(Arc<Mutex<Vec<(u8, String)>>>);
+
+impl DeferredCloses {
+    fn push(&self, variant: u8, name: String) {
+        self.lock().push((variant, name));
+    }
+
+    fn take(&self) -> Vec<(u8, String)> {
+        mem::replace(&mut *self.lock(), vec![])
+    }
+
+    fn lock(&self) -> MutexGuard<Vec<(u8, String)>> {
+        // The queue is left consistent even if a thread panics holding it
+        match self.0.lock() {
+            Ok(guard) => guard,
+            Err(err) => err.into_inner(),
+        }
+    }
+}
+
+/// The connection's notice handler, which can be replaced while the
+/// connection is in use.
+#[derive(Clone)]
+struct NoticeHandler(Arc<Lock<Box<HandleNotice>>>);
+
+impl NoticeHandler {
+    fn new(handler: Box<HandleNotice>) -> NoticeHandler {
+        NoticeHandler(Arc::new(Lock::new(handler)))
+    }
+
+    fn handle_notice(&self, notice: DbError) {
+        if let Some(mut handler) = self.0.lock() {
+            handler.handle_notice(notice);
+        }
+    }
+
+    fn replace(&self, handler: Box<HandleNotice>) -> Box<HandleNotice> {
+        match self.0.lock() {
+            Some(mut old) => mem::replace(&mut *old, handler),
+            None => panic!("the notice handler cannot be replaced from within itself"),
+        }
+    }
+}
+
+/// A copy of the connection state read by `Connection`'s accessors, so they
+/// work while the connection is in use. The `InnerConnection` updates it each
+/// time its lock is released, and picks up any settings changed in the
+/// meantime each time it is acquired.
+#[derive(Clone)]
+struct Mirror(Arc<Mutex<MirrorState>>);
+
+struct MirrorState {
+    parameters: Arc<HashMap<String, String>>,
+    trans_depth: u32,
+    desynchronized: bool,
+    query_protocol: QueryProtocol,
+    compatibility: Compatibility,
+    cache_stats: StatementCacheStats,
+    pending: PendingSettings,
+}
+
+#[derive(Default)]
+struct PendingSettings {
+    query_protocol: Option<QueryProtocol>,
+    compatibility: Option<Compatibility>,
+    statement_cache_capacity: Option<usize>,
+}
+
+impl Default for Mirror {
+    fn default() -> Mirror {
+        Mirror(Arc::new(Mutex::new(MirrorState {
+            parameters: Arc::new(HashMap::new()),
+            trans_depth: 0,
+            desynchronized: false,
+            query_protocol: QueryProtocol::Extended,
+            compatibility: Compatibility::Strict,
+            cache_stats: StatementCacheStats {
+                len: 0,
+                capacity: DEFAULT_STATEMENT_CACHE_CAPACITY,
+                hits: 0,
+                misses: 0,
+                evictions: 0,
+            },
+            pending: PendingSettings::default(),
+        })))
+    }
+}
+
+impl Mirror {
+    fn lock(&self) -> MutexGuard<MirrorState> {
+        // Each field is updated independently, so a panic can't leave the
+        // state inconsistent
+        match self.0.lock() {
+            Ok(guard) => guard,
+            Err(err) => err.into_inner(),
+        }
+    }
+}
+
+/// A locked `InnerConnection`, which updates the connection's `Mirror` when
+/// released.
+struct ConnGuard<'a>(LockGuard<'a, InnerConnection>);
+
+impl<'a> Drop for ConnGuard<'a> {
+    fn drop(&mut self) {
+        self.0.update_mirror();
+    }
+}
+
+impl<'a> Deref for ConnGuard<'a> {
+    type Target = InnerConnection;
+
+    fn deref(&self) -> &InnerConnection {
+        &self.0
+    }
+}
+
+impl<'a> DerefMut for ConnGuard<'a> {
+    fn deref_mut(&mut self) -> &mut InnerConnection {
+        &mut self.0
+    }
+}
+
+/// The transaction and savepoints open on a connection at some point.
+struct TransactionScope {
+    transaction_ends: u64,
+    savepoints: Vec<u64>,
+}
+
+/// A reference to a `Connection`, either borrowed or shared through an `Arc`.
+#[derive(Clone)]
+enum ConnRef<'a> {
+    Borrowed(&'a Connection),
+    Owned(Arc<Connection>),
+}
+
+impl<'a> Deref for ConnRef<'a> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        match *self {
+            ConnRef::Borrowed(conn) => conn,
+            ConnRef::Owned(ref conn) => conn,
+        }
+    }
+}
+
+/// Methods of an `Arc<Connection>` which create handles that share ownership
+/// of the connection rather than borrowing it.
+///
+/// The resulting `'static` statements and transactions can be stored
+/// alongside the connection or returned from the function that created them.
+///
+/// ## Example
+///
+/// ```rust,no_run
+/// # use std::sync::Arc;
+/// # use postgres::{Connection, SslMode, Statement, SharedConnection};
+/// struct UserStore {
+///     find_user: Statement<'static>,
+/// }
+///
+/// # let conn = Arc::new(Connection::connect("", &SslMode::None).unwrap());
+/// let store = UserStore {
+///     find_user: conn.prepare_owned("SELECT name FROM users WHERE id = $1").unwrap(),
+/// };
+/// ```
+pub trait SharedConnection {
+    /// Like `Connection::prepare`.
+    fn prepare_owned(&self, query: &str) -> Result<Statement<'static>>;
+
+    /// Like `Connection::prepare_cached`.
+    fn prepare_cached_owned(&self, query: &str) -> Result<Statement<'static>>;
+
+    /// Like `Connection::transaction`.
+    fn transaction_owned(&self) -> Result<OwnedTransaction>;
+}
+
+impl SharedConnection for Arc<Connection> {
+    fn prepare_owned(&self, query: &str) -> Result<Statement<'static>> {
+        try!(self.lock()).prepare(query, &[], ConnRef::Owned(self.clone()))
+    }
+
+    fn prepare_cached_owned(&self, query: &str) -> Result<Statement<'static>> {
+        try!(self.lock()).prepare_cached(query, &[], ConnRef::Owned(self.clone()))
+    }
+
+    fn transaction_owned(&self) -> Result<OwnedTransaction> {
+        begin_transaction(ConnRef::Owned(self.clone())).map(OwnedTransaction)
+    }
+}
+
 /// Represents a transaction on a database connection.
 ///
 /// The transaction will roll back by default.
 pub struct Transaction<'conn> {
-    conn: &'conn Connection,
+    conn: ConnRef<'conn>,
     depth: u32,
     commit: Cell<bool>,
     finished: bool,
@@ -1183,7 +2281,7 @@ impl<'conn> Drop for Transaction<'conn> {
 
 impl<'conn> Transaction<'conn> {
     fn finish_inner(&mut self) -> Result<()> {
-        let mut conn = self.conn.conn.borrow_mut();
+        let mut conn = try!(self.conn.lock());
         debug_assert!(self.depth == conn.trans_depth);
         let query = match (self.commit.get(), self.depth != 1) {
             (false, true) => "ROLLBACK TO sp",
@@ -1192,12 +2290,17 @@ impl<'conn> Transaction<'conn> {
             (true, false) => "COMMIT",
         };
         conn.trans_depth -= 1;
+        if let Some(id) = conn.savepoints.pop() {
+            if !self.commit.get() {
+                conn.rolled_back_savepoints.push(id);
+            }
+        }
         conn.quick_query(query).map(|_| ())
     }
 
     /// Like `Connection::prepare`.
     pub fn prepare(&self, query: &str) -> Result<Statement<'conn>> {
-        self.conn.prepare(query)
+        try!(self.conn.lock()).prepare(query, &[], self.conn.clone())
     }
 
     /// Like `Connection::prepare_cached`.
@@ -1205,7 +2308,32 @@ impl<'conn> Transaction<'conn> {
     /// Note that the statement will be cached for the duration of the
     /// connection, not just the duration of this transaction.
     pub fn prepare_cached(&self, query: &str) -> Result<Statement<'conn>> {
-        self.conn.prepare_cached(query)
+        try!(self.conn.lock()).prepare_cached(query, &[], self.conn.clone())
+    }
+
+    /// Like `Connection::prepare_typed`.
+    pub fn prepare_typed(&self, query: &str, types: &[Type]) -> Result<Statement<'conn>> {
+        try!(self.conn.lock()).prepare(query, types, self.conn.clone())
+    }
+
+    /// Like `Connection::prepare_typed_cached`.
+    ///
+    /// Note that the statement will be cached for the duration of the
+    /// connection, not just the duration of this transaction.
+    pub fn prepare_typed_cached(&self, query: &str, types: &[Type])
+                                -> Result<Statement<'conn>> {
+        try!(self.conn.lock()).prepare_cached(query, types, self.conn.clone())
+    }
+
+    /// Like `Connection::prepare_checked`.
+    pub fn prepare_checked<P, R>(&self, query: &str) -> Result<TypedStatement<'conn, P, R>>
+            where P: Params, R: RowType {
+        try!(self.conn.lock()).prepare_checked(query, self.conn.clone())
+    }
+
+    /// Like `Connection::prepare_named`.
+    pub fn prepare_named(&self, query: &str) -> Result<NamedStatement<'conn>> {
+        try!(self.conn.lock()).prepare_named(query, self.conn.clone())
     }
 
     /// Like `Connection::execute`.
@@ -1213,25 +2341,62 @@ impl<'conn> Transaction<'conn> {
         self.conn.execute(query, params)
     }
 
+    /// Like `Connection::execute_tagged`.
+    pub fn execute_tagged(&self, query: &str, params: &[&ToSql]) -> Result<CommandTag> {
+        self.conn.execute_tagged(query, params)
+    }
+
+    /// Like `Connection::query`.
+    pub fn query(&self, query: &str, params: &[&ToSql]) -> Result<Rows<'conn>> {
+        query_unnamed(self.conn.clone(), query, params)
+    }
+
+    /// Like `Connection::query_one`.
+    pub fn query_one(&self, query: &str, params: &[&ToSql]) -> Result<Row<'conn>> {
+        try!(single_row(try!(self.query(query, params)))).ok_or(Error::RowCount(0))
+    }
+
+    /// Like `Connection::query_opt`.
+    pub fn query_opt(&self, query: &str, params: &[&ToSql]) -> Result<Option<Row<'conn>>> {
+        single_row(try!(self.query(query, params)))
+    }
+
+    /// Like `Connection::query_scalar`.
+    pub fn query_scalar<T>(&self, query: &str, params: &[&ToSql]) -> Result<T> where T: FromSql {
+        try!(self.query_one(query, params)).get_opt(0)
+    }
+
     /// Like `Connection::batch_execute`.
     pub fn batch_execute(&self, query: &str) -> Result<()> {
         self.conn.batch_execute(query)
     }
 
+    /// Like `Connection::simple_query`.
+    pub fn simple_query(&self, query: &str) -> Result<Vec<SimpleQueryResult>> {
+        self.conn.simple_query(query)
+    }
+
+    /// Like `Connection::pipeline`.
+    pub fn pipeline<'a>(&'a self) -> Pipeline<'a> {
+        Pipeline::new(&self.conn)
+    }
+
     /// Like `Connection::transaction`.
     ///
-    /// ## Panics
-    ///
-    /// Panics if there is an active nested transaction.
+    /// Returns `Error::Misuse` if there is an active nested transaction.
     pub fn transaction<'a>(&'a self) -> Result<Transaction<'a>> {
-        let mut conn = self.conn.conn.borrow_mut();
+        let mut conn = try!(self.conn.lock());
         check_desync!(conn);
-        assert!(conn.trans_depth == self.depth,
-                "`transaction` may only be called on the active transaction");
+        if conn.trans_depth != self.depth {
+            return Err(misuse("`transaction` may only be called on the active transaction"));
+        }
         try!(conn.quick_query("SAVEPOINT sp"));
         conn.trans_depth += 1;
+        let id = conn.next_savepoint_id;
+        conn.next_savepoint_id += 1;
+        conn.savepoints.push(id);
         Ok(Transaction {
-            conn: self.conn,
+            conn: ConnRef::Borrowed(&self.conn),
             commit: Cell::new(false),
             depth: self.depth + 1,
             finished: false,
@@ -1240,12 +2405,17 @@ impl<'conn> Transaction<'conn> {
 
     /// Returns a reference to the `Transaction`'s `Connection`.
     pub fn connection(&self) -> &'conn Connection {
-        self.conn
+        match self.conn {
+            ConnRef::Borrowed(conn) => conn,
+            // transactions owning their connection are only reachable
+            // through `OwnedTransaction`, which doesn't expose them
+            ConnRef::Owned(_) => unreachable!(),
+        }
     }
 
     /// Like `Connection::is_active`.
     pub fn is_active(&self) -> bool {
-        self.conn.conn.borrow().trans_depth == self.depth
+        self.conn.mirror.lock().trans_depth == self.depth
     }
 
     /// Determines if the transaction is currently set to commit or roll back.
@@ -1279,16 +2449,150 @@ impl<'conn> Transaction<'conn> {
     }
 }
 
-fn read_rows(conn: &mut InnerConnection, buf: &mut VecDeque<Vec<Option<Vec<u8>>>>) -> Result<bool> {
-    let more_rows;
+/// A transaction which shares ownership of its connection.
+///
+/// Created by `SharedConnection::transaction_owned`. It behaves like a
+/// `Transaction`, but the statements and rows it creates are `'static`.
+#[derive(Debug)]
+pub struct OwnedTransaction(Transaction<'static>);
+
+impl OwnedTransaction {
+    /// Like `Transaction::prepare`.
+    pub fn prepare(&self, query: &str) -> Result<Statement<'static>> {
+        self.0.prepare(query)
+    }
+
+    /// Like `Transaction::prepare_cached`.
+    pub fn prepare_cached(&self, query: &str) -> Result<Statement<'static>> {
+        self.0.prepare_cached(query)
+    }
+
+    /// Like `Transaction::prepare_typed`.
+    pub fn prepare_typed(&self, query: &str, types: &[Type]) -> Result<Statement<'static>> {
+        self.0.prepare_typed(query, types)
+    }
+
+    /// Like `Transaction::prepare_typed_cached`.
+    pub fn prepare_typed_cached(&self, query: &str, types: &[Type])
+                                -> Result<Statement<'static>> {
+        self.0.prepare_typed_cached(query, types)
+    }
+
+    /// Like `Transaction::prepare_checked`.
+    pub fn prepare_checked<P, R>(&self, query: &str) -> Result<TypedStatement<'static, P, R>>
+            where P: Params, R: RowType {
+        self.0.prepare_checked(query)
+    }
+
+    /// Like `Transaction::prepare_named`.
+    pub fn prepare_named(&self, query: &str) -> Result<NamedStatement<'static>> {
+        self.0.prepare_named(query)
+    }
+
+    /// Like `Transaction::execute`.
+    pub fn execute(&self, query: &str, params: &[&ToSql]) -> Result<u64> {
+        self.0.execute(query, params)
+    }
+
+    /// Like `Transaction::execute_tagged`.
+    pub fn execute_tagged(&self, query: &str, params: &[&ToSql]) -> Result<CommandTag> {
+        self.0.execute_tagged(query, params)
+    }
+
+    /// Like `Transaction::query`.
+    pub fn query(&self, query: &str, params: &[&ToSql]) -> Result<Rows<'static>> {
+        self.0.query(query, params)
+    }
+
+    /// Like `Transaction::query_one`.
+    pub fn query_one(&self, query: &str, params: &[&ToSql]) -> Result<Row<'static>> {
+        self.0.query_one(query, params)
+    }
+
+    /// Like `Transaction::query_opt`.
+    pub fn query_opt(&self, query: &str, params: &[&ToSql]) -> Result<Option<Row<'static>>> {
+        self.0.query_opt(query, params)
+    }
+
+    /// Like `Transaction::query_scalar`.
+    pub fn query_scalar<T>(&self, query: &str, params: &[&ToSql]) -> Result<T> where T: FromSql {
+        self.0.query_scalar(query, params)
+    }
+
+    /// Like `Transaction::batch_execute`.
+    pub fn batch_execute(&self, query: &str) -> Result<()> {
+        self.0.batch_execute(query)
+    }
+
+    /// Like `Transaction::simple_query`.
+    pub fn simple_query(&self, query: &str) -> Result<Vec<SimpleQueryResult>> {
+        self.0.simple_query(query)
+    }
+
+    /// Like `Transaction::pipeline`.
+    pub fn pipeline<'a>(&'a self) -> Pipeline<'a> {
+        self.0.pipeline()
+    }
+
+    /// Like `Transaction::transaction`.
+    pub fn transaction<'a>(&'a self) -> Result<Transaction<'a>> {
+        self.0.transaction()
+    }
+
+    /// Returns a reference to the `OwnedTransaction`'s `Connection`.
+    pub fn connection(&self) -> &Connection {
+        &self.0.conn
+    }
+
+    /// Like `Transaction::is_active`.
+    pub fn is_active(&self) -> bool {
+        self.0.is_active()
+    }
+
+    /// Like `Transaction::will_commit`.
+    pub fn will_commit(&self) -> bool {
+        self.0.will_commit()
+    }
+
+    /// Like `Transaction::set_commit`.
+    pub fn set_commit(&self) {
+        self.0.set_commit()
+    }
+
+    /// Like `Transaction::set_rollback`.
+    pub fn set_rollback(&self) {
+        self.0.set_rollback()
+    }
+
+    /// Like `Transaction::commit`.
+    pub fn commit(self) -> Result<()> {
+        self.0.commit()
+    }
+
+    /// Like `Transaction::finish`.
+    pub fn finish(self) -> Result<()> {
+        self.0.finish()
+    }
+}
+
+// Reads rows into `buf`, returning the command tag, or `None` if the portal
+// was suspended with more rows remaining.
+fn read_rows(conn: &mut InnerConnection,
+             buf: &mut VecDeque<Vec<Option<Vec<u8>>>>)
+             -> Result<Option<CommandTag>> {
+    let tag;
     loop {
         match try!(conn.read_message()) {
-            EmptyQueryResponse | CommandComplete { .. } => {
-                more_rows = false;
+            EmptyQueryResponse => {
+                tag = Some(CommandTag::new(String::new()));
+                break;
+            }
+            CommandComplete { tag: t } => {
+                tag = Some(CommandTag::new(t));
                 break;
             }
             PortalSuspended => {
-                more_rows = true;
+                tag = None;
                 break;
             }
             DataRow { row } => buf.push_back(row),
@@ -1321,7 +2625,7 @@ fn read_rows(conn: &mut InnerConnection, buf: &mut VecDeque<Vec<Option<Vec<u8>>>
         }
     }
     try!(conn.wait_for_ready());
-    Ok(more_rows)
+    Ok(tag)
 }
 
 /// A trait allowing abstraction over connections and transactions
@@ -1335,6 +2639,25 @@ pub trait GenericConnection {
     /// Like `Connection::execute`.
     fn execute(&self, query: &str, params: &[&ToSql]) -> Result<u64>;
 
+    /// Like `Connection::query`.
+    fn query<'a>(&'a self, query: &str, params: &[&ToSql]) -> Result<Rows<'a>>;
+
+    /// Like `Connection::query_one`.
+    fn query_one<'a>(&'a self, query: &str, params: &[&ToSql]) -> Result<Row<'a>> {
+        try!(single_row(try!(self.query(query, params)))).ok_or(Error::RowCount(0))
+    }
+
+    /// Like `Connection::query_opt`.
+    fn query_opt<'a>(&'a self, query: &str, params: &[&ToSql]) -> Result<Option<Row<'a>>> {
+        single_row(try!(self.query(query, params)))
+    }
+
+    /// Like `Connection::query_scalar`.
+    fn query_scalar<T>(&self, query: &str, params: &[&ToSql]) -> Result<T>
+            where T: FromSql, Self: Sized {
+        try!(self.query_one(query, params)).get_opt(0)
+    }
+
     /// Like `Connection::transaction`.
     fn transaction<'a>(&'a self) -> Result<Transaction<'a>>;
 
@@ -1358,6 +2681,10 @@ impl GenericConnection for Connection {
         self.execute(query, params)
     }
 
+    fn query<'a>(&'a self, query: &str, params: &[&ToSql]) -> Result<Rows<'a>> {
+        self.query(query, params)
+    }
+
     fn transaction<'a>(&'a self) -> Result<Transaction<'a>> {
         self.transaction()
     }
@@ -1384,6 +2711,40 @@ impl<'a> GenericConnection for Transaction<'a> {
         self.execute(query, params)
     }
 
+    fn query<'b>(&'b self, query: &str, params: &[&ToSql]) -> Result<Rows<'b>> {
+        self.query(query, params)
+    }
+
+    fn transaction<'b>(&'b self) -> Result<Transaction<'b>> {
+        self.transaction()
+    }
+
+    fn batch_execute(&self, query: &str) -> Result<()> {
+        self.batch_execute(query)
+    }
+
+    fn is_active(&self) -> bool {
+        self.is_active()
+    }
+}
+
+impl GenericConnection for OwnedTransaction {
+    fn prepare<'b>(&'b self, query: &str) -> Result<Statement<'b>> {
+        self.prepare(query)
+    }
+
+    fn prepare_cached<'b>(&'b self, query: &str) -> Result<Statement<'b>> {
+        self.prepare_cached(query)
+    }
+
+    fn execute(&self, query: &str, params: &[&ToSql]) -> Result<u64> {
+        self.execute(query, params)
+    }
+
+    fn query<'b>(&'b self, query: &str, params: &[&ToSql]) -> Result<Rows<'b>> {
+        self.query(query, params)
+    }
+
     fn transaction<'b>(&'b self) -> Result<Transaction<'b>> {
         self.transaction()
     }
@@ -1407,16 +2768,43 @@ trait DbErrorNew {
     fn new<T>(fields: Vec<(u8, String)>) -> Result<T>;
 }
 
+trait BatchErrorNew {
+    fn new(index: usize, error: Error) -> BatchError;
+}
+
+trait ValueErrorNew {
+    fn wrap(location: ValueLocation, pg_type: Type, rust_type: &'static str, error: Error)
+            -> Error;
+}
+
 trait TypeNew {
     fn new(oid: Oid) -> Option<Type>;
 }
 
 trait RowsNew<'a> {
-    fn new(stmt: &'a Statement<'a>, data: Vec<Vec<Option<Vec<u8>>>>) -> Rows<'a>;
+    fn new(info: Arc<HashMap<String, String>>,
+           columns: Arc<Vec<Column>>,
+           data: Vec<Vec<Option<Vec<u8>>>>,
+           tag: Option<CommandTag>) -> Rows<'a>;
+}
+
+trait CommandTagNew {
+    fn new(tag: String) -> CommandTag;
+}
+
+trait SimpleQueryResultNew {
+    fn new(columns: Vec<Column>, rows: Vec<Vec<Option<String>>>, tag: CommandTag)
+           -> SimpleQueryResult;
+}
+
+trait RowStreamNew<'a> {
+    fn new(conn: ConnGuard<'a>, columns: Arc<Vec<Column>>) -> RowStream<'a>;
 }
 
 trait LazyRowsNew<'trans, 'stmt> {
-    fn new(stmt: &'stmt Statement<'stmt>,
+    fn new(conn: ConnRef<'stmt>,
+           info: Arc<HashMap<String, String>>,
+           columns: Arc<Vec<Column>>,
            data: VecDeque<Vec<Option<Vec<u8>>>>,
            name: String,
            row_limit: i32,
@@ -1425,21 +2813,50 @@ trait LazyRowsNew<'trans, 'stmt> {
            trans: &'trans Transaction<'trans>) -> LazyRows<'trans, 'stmt>;
 }
 
+trait NamedStatementNew<'conn> {
+    fn new(stmt: Statement<'conn>, names: Vec<String>) -> NamedStatement<'conn>;
+}
+
+trait PipelineNew<'a> {
+    fn new(conn: &'a Connection) -> Pipeline<'a>;
+}
+
 trait SessionInfoNew<'a> {
-    fn new(conn: &'a InnerConnection) -> SessionInfo<'a>;
+    fn new(parameters: &'a HashMap<String, String>) -> SessionInfo<'a>;
 }
 
 trait StatementInternals<'conn> {
-    fn new(conn: &'conn Connection,
+    fn new(conn: ConnRef<'conn>,
            name: String,
+           query: String,
            param_types: Vec<Type>,
            columns: Vec<Column>,
            next_portal_id: Cell<u32>,
            finished: bool) -> Statement<'conn>;
 
-    fn conn(&self) -> &'conn Connection;
+    fn new_simple(conn: ConnRef<'conn>, query: String, param_types: Vec<Type>)
+                  -> Statement<'conn>;
+
+    fn new_cached(conn: ConnRef<'conn>, query: String, stmt: CachedStatement)
+                  -> Statement<'conn>;
+
+    fn columns_arc(&self) -> Arc<Vec<Column>>;
+
+    fn conn(&self) -> &Connection;
+
+    fn write_execution(&self, conn: &mut InnerConnection, params: &[&ToSql]) -> Result<()>;
+
+    fn query_locked(&self, conn: &mut InnerConnection, params: &[&ToSql])
+                    -> Result<Rows<'conn>>;
+
+    fn execute_locked(&self, conn: &mut InnerConnection, params: &[&ToSql])
+                      -> Result<CommandTag>;
 }
 
 trait ColumnNew {
-    fn new(name: String, type_: Type) -> Column;
+    fn new(desc: RowDescriptionEntry, type_: Type) -> Column;
+}
+
+trait ColumnOriginNew {
+    fn new(schema: String, table: String, column: String, not_null: bool) -> ColumnOrigin;
 }
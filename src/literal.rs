@@ -0,0 +1,891 @@
+//! Conversions between the binary format of Postgres values and their SQL
+//! text representations.
+//!
+//! In simple query mode parameters can't be sent separately from the query,
+//! so they are rendered as literals, and results arrive in the text format.
+//! Both directions go through the binary format so that the normal `ToSql`
+//! and `FromSql` implementations can be reused.
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::ascii::AsciiExt;
+use std::error;
+use std::i32;
+use std::i64;
+
+use {Result, InnerConnection, ColumnNew};
+use error::Error;
+use message::RowDescriptionEntry;
+use stmt::Column;
+use types::{ToSql, Type, Kind, IsNull, SessionInfo};
+
+const USEC_PER_SEC: i64 = 1_000_000;
+const USEC_PER_DAY: i64 = 86_400 * USEC_PER_SEC;
+// Days between 0000-03-01 and 2000-01-01, the Postgres epoch
+const EPOCH_DAYS: i64 = 730_425;
+
+// The types tried, in order, when the type of a parameter isn't known.
+static PROBE_TYPES: &'static [Type] = &[
+    Type::Bool, Type::Char, Type::Int2, Type::Int4, Type::Int8, Type::Oid, Type::Float4,
+    Type::Float8, Type::Text, Type::Bytea, Type::Json, Type::Uuid, Type::TimestampTZ,
+    Type::Timestamp, Type::Date, Type::Time,
+    Type::BoolArray, Type::CharArray, Type::Int2Array, Type::Int4Array, Type::Int8Array,
+    Type::OidArray, Type::Float4Array, Type::Float8Array, Type::TextArray, Type::ByteaArray,
+    Type::JsonArray, Type::UuidArray, Type::TimestampTZArray, Type::TimestampArray,
+    Type::DateArray, Type::TimeArray,
+];
+
+fn conversion_error<T>(msg: &str) -> Result<T> {
+    let err: Box<error::Error+Sync+Send> = msg.into();
+    Err(Error::Conversion(err))
+}
+
+/// Ensures that the session will interpret the text we send and receive
+/// as UTF-8.
+pub fn check_encoding(info: &SessionInfo) -> Result<()> {
+    match info.parameter("client_encoding") {
+        Some(enc) if enc.eq_ignore_ascii_case("UTF8") || enc.eq_ignore_ascii_case("UNICODE") => {
+            Ok(())
+        }
+        _ => conversion_error("simple query mode requires a client_encoding of UTF8"),
+    }
+}
+
+/// Determines if the session treats backslashes in ordinary string literals
+/// as escapes.
+pub fn backslash_escapes(info: &SessionInfo) -> bool {
+    info.parameter("standard_conforming_strings") != Some("on")
+}
+
+/// Renders a parameter as an SQL literal.
+///
+/// If the parameter's type is not known, a set of common types is tried
+/// until the value accepts one of them.
+pub fn render_param(param: &ToSql, ty: Option<&Type>, info: &SessionInfo) -> Result<String> {
+    let mut buf = vec![];
+    let (ty, is_null) = match ty {
+        Some(ty) => (ty.clone(), try!(param.to_sql_checked(ty, &mut buf, info))),
+        None => try!(probe(param, &mut buf, info)),
+    };
+
+    let cast = if is_untyped(&ty) {
+        None
+    } else {
+        Some(try!(type_name(&ty)))
+    };
+
+    let mut literal = match is_null {
+        IsNull::Yes => "NULL".to_owned(),
+        IsNull::No => try!(quote_literal(&try!(to_text(&ty, &buf)), info)),
+    };
+    if let Some(cast) = cast {
+        literal.push_str("::");
+        literal.push_str(&cast);
+    }
+    Ok(literal)
+}
+
+fn probe(param: &ToSql, buf: &mut Vec<u8>, info: &SessionInfo) -> Result<(Type, IsNull)> {
+    for ty in PROBE_TYPES {
+        match param.to_sql_checked(ty, buf, info) {
+            Ok(is_null) => return Ok((ty.clone(), is_null)),
+            Err(Error::WrongType(_)) => buf.clear(),
+            Err(err) => return Err(err),
+        }
+    }
+    conversion_error("unable to determine the Postgres type of a parameter")
+}
+
+// Types rendered as untyped literals, leaving the server to infer the type
+// from context as it would for a parameter.
+fn is_untyped(ty: &Type) -> bool {
+    match *ty {
+        Type::Text | Type::Varchar | Type::Bpchar | Type::Name | Type::Json | Type::Jsonb => true,
+        _ => false,
+    }
+}
+
+fn type_name(ty: &Type) -> Result<String> {
+    let name = match *ty {
+        Type::Bool => "bool",
+        Type::Char => "\"char\"",
+        Type::Int2 => "int2",
+        Type::Int4 => "int4",
+        Type::Int8 => "int8",
+        Type::Oid => "oid",
+        Type::Float4 => "float4",
+        Type::Float8 => "float8",
+        Type::Bytea => "bytea",
+        Type::Text => "text",
+        Type::Varchar => "varchar",
+        Type::Bpchar => "bpchar",
+        Type::Name => "name",
+        Type::Json => "json",
+        Type::Jsonb => "jsonb",
+        Type::Uuid => "uuid",
+        Type::Timestamp => "timestamp",
+        Type::TimestampTZ => "timestamptz",
+        Type::Date => "date",
+        Type::Time => "time",
+        Type::Other(ref other) if *other.kind() == Kind::Simple => {
+            return Ok(quote_ident(other.name()));
+        }
+        _ => match *ty.kind() {
+            Kind::Array(ref member) => return Ok(format!("{}[]", try!(type_name(member)))),
+            _ => return conversion_error("unsupported type in simple query mode"),
+        },
+    };
+    Ok(name.to_owned())
+}
+
+fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace("\"", "\"\""))
+}
+
+fn quote_literal(s: &str, info: &SessionInfo) -> Result<String> {
+    if s.contains('\0') {
+        return conversion_error("strings containing NUL characters cannot be rendered as \
+                                 literals");
+    }
+
+    let s = s.replace("'", "''");
+    if !s.contains('\\') {
+        Ok(format!("'{}'", s))
+    } else if backslash_escapes(info) {
+        Ok(format!("E'{}'", s.replace("\\", "\\\\")))
+    } else {
+        Ok(format!("'{}'", s))
+    }
+}
+
+/// Converts the binary representation of a value to its text
+/// representation.
+pub fn to_text(ty: &Type, raw: &[u8]) -> Result<String> {
+    let mut rdr = raw;
+    let text = match *ty {
+        Type::Bool => if try!(rdr.read_u8()) != 0 { "t" } else { "f" }.to_owned(),
+        Type::Char => {
+            let b = try!(rdr.read_u8());
+            if b == 0 {
+                String::new()
+            } else if b < 0x80 {
+                (b as char).to_string()
+            } else {
+                format!("\\{:03o}", b)
+            }
+        }
+        Type::Int2 => try!(rdr.read_i16::<BigEndian>()).to_string(),
+        Type::Int4 => try!(rdr.read_i32::<BigEndian>()).to_string(),
+        Type::Int8 => try!(rdr.read_i64::<BigEndian>()).to_string(),
+        Type::Oid => try!(rdr.read_u32::<BigEndian>()).to_string(),
+        Type::Float4 => format_float(try!(rdr.read_f32::<BigEndian>()) as f64),
+        Type::Float8 => format_float(try!(rdr.read_f64::<BigEndian>())),
+        Type::Bytea => {
+            let mut s = "\\x".to_owned();
+            for b in raw {
+                s.push_str(&format!("{:02x}", b));
+            }
+            s
+        }
+        Type::Text | Type::Varchar | Type::Bpchar | Type::Name | Type::Json => try!(utf8(raw)),
+        Type::Jsonb => {
+            if raw.first() != Some(&1) {
+                return conversion_error("unsupported JSONB encoding version");
+            }
+            try!(utf8(&raw[1..]))
+        }
+        Type::Uuid => {
+            if raw.len() != 16 {
+                return conversion_error("invalid UUID value");
+            }
+            let hex = raw.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+            format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20],
+                    &hex[20..])
+        }
+        Type::Timestamp => format_timestamp(try!(rdr.read_i64::<BigEndian>()), ""),
+        Type::TimestampTZ => format_timestamp(try!(rdr.read_i64::<BigEndian>()), "+00"),
+        Type::Date => {
+            match try!(rdr.read_i32::<BigEndian>()) {
+                i32::MAX => "infinity".to_owned(),
+                i32::MIN => "-infinity".to_owned(),
+                days => {
+                    let (date, bc) = format_date(days as i64);
+                    format!("{}{}", date, bc)
+                }
+            }
+        }
+        Type::Time => format_time(try!(rdr.read_i64::<BigEndian>())),
+        Type::Numeric => return numeric_to_text(raw),
+        Type::Other(ref other) if other.name() == "hstore" => return hstore_to_text(raw),
+        Type::Other(ref other) if *other.kind() == Kind::Simple => try!(utf8(raw)),
+        _ => match *ty.kind() {
+            Kind::Array(ref member) => return array_to_text(member, raw),
+            _ => return conversion_error("unsupported type for conversion to text"),
+        },
+    };
+    Ok(text)
+}
+
+fn utf8(raw: &[u8]) -> Result<String> {
+    String::from_utf8(raw.to_owned()).map_err(|err| Error::Conversion(Box::new(err)))
+}
+
+fn format_float(v: f64) -> String {
+    if v.is_nan() {
+        "NaN".to_owned()
+    } else if v.is_infinite() {
+        if v > 0. { "Infinity" } else { "-Infinity" }.to_owned()
+    } else {
+        v.to_string()
+    }
+}
+
+fn floor_div(a: i64, b: i64) -> i64 {
+    if a >= 0 { a / b } else { (a - b + 1) / b }
+}
+
+// Converts a count of days since the Postgres epoch to a proleptic
+// Gregorian date.
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + EPOCH_DAYS;
+    let era = floor_div(z, 146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = floor_div(year, 400);
+    let yoe = year - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - EPOCH_DAYS
+}
+
+fn format_date(days: i64) -> (String, &'static str) {
+    let (year, month, day) = civil_from_days(days);
+    if year <= 0 {
+        (format!("{:04}-{:02}-{:02}", 1 - year, month, day), " BC")
+    } else {
+        (format!("{:04}-{:02}-{:02}", year, month, day), "")
+    }
+}
+
+fn format_time(usec: i64) -> String {
+    let secs = usec / USEC_PER_SEC;
+    format!("{:02}:{:02}:{:02}.{:06}",
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60,
+            usec % USEC_PER_SEC)
+}
+
+fn format_timestamp(usec: i64, offset: &str) -> String {
+    match usec {
+        i64::MAX => "infinity".to_owned(),
+        i64::MIN => "-infinity".to_owned(),
+        usec => {
+            let days = floor_div(usec, USEC_PER_DAY);
+            let (date, bc) = format_date(days);
+            format!("{} {}{}{}", date, format_time(usec - days * USEC_PER_DAY), offset, bc)
+        }
+    }
+}
+
+// NUMERIC values are sent as a sign, a scale, and base 10000 digits, the
+// first of which is multiplied by 10000 to the power of the weight.
+fn numeric_to_text(raw: &[u8]) -> Result<String> {
+    let mut rdr = raw;
+    let ndigits = try!(rdr.read_i16::<BigEndian>());
+    let weight = try!(rdr.read_i16::<BigEndian>()) as i32;
+    let sign = try!(rdr.read_u16::<BigEndian>());
+    let scale = try!(rdr.read_u16::<BigEndian>()) as usize;
+    let mut digits = vec![];
+    for _ in 0..ndigits {
+        digits.push(try!(rdr.read_u16::<BigEndian>()));
+    }
+    let digit = |i: i32| if i >= 0 { digits.get(i as usize).cloned().unwrap_or(0) } else { 0 };
+
+    let mut text = match sign {
+        0x0000 => String::new(),
+        0x4000 => "-".to_owned(),
+        0xC000 => return Ok("NaN".to_owned()),
+        0xD000 => return Ok("Infinity".to_owned()),
+        0xF000 => return Ok("-Infinity".to_owned()),
+        _ => return conversion_error("invalid NUMERIC sign"),
+    };
+    if weight < 0 {
+        text.push('0');
+    } else {
+        text.push_str(&digit(0).to_string());
+        for i in 1..weight + 1 {
+            text.push_str(&format!("{:04}", digit(i)));
+        }
+    }
+    if scale > 0 {
+        let mut fraction = String::new();
+        let mut i = weight + 1;
+        while fraction.len() < scale {
+            fraction.push_str(&format!("{:04}", digit(i)));
+            i += 1;
+        }
+        fraction.truncate(scale);
+        text.push('.');
+        text.push_str(&fraction);
+    }
+    Ok(text)
+}
+
+fn quote_element(s: &str) -> String {
+    format!("\"{}\"", s.replace("\\", "\\\\").replace("\"", "\\\""))
+}
+
+fn hstore_to_text(raw: &[u8]) -> Result<String> {
+    let mut rdr = raw;
+    let count = try!(rdr.read_i32::<BigEndian>());
+    let mut pairs = vec![];
+    for _ in 0..count {
+        let key = try!(read_value(&mut rdr));
+        let key = try!(utf8(try!(key.ok_or(Error::Conversion("null hstore key".into())))));
+        let val = match try!(read_value(&mut rdr)) {
+            Some(val) => quote_element(&try!(utf8(val))),
+            None => "NULL".to_owned(),
+        };
+        pairs.push(format!("{}=>{}", quote_element(&key), val));
+    }
+    Ok(pairs.join(", "))
+}
+
+// Reads a length-prefixed value, as used in arrays and hstores.
+fn read_value<'a>(rdr: &mut &'a [u8]) -> Result<Option<&'a [u8]>> {
+    let len = try!(rdr.read_i32::<BigEndian>());
+    if len < 0 {
+        return Ok(None);
+    }
+    let len = len as usize;
+    if rdr.len() < len {
+        return conversion_error("truncated value");
+    }
+    let (value, rest) = rdr.split_at(len);
+    *rdr = rest;
+    Ok(Some(value))
+}
+
+fn array_delimiter(member: &Type) -> char {
+    match *member {
+        Type::Box => ';',
+        _ => ',',
+    }
+}
+
+fn array_to_text(member: &Type, raw: &[u8]) -> Result<String> {
+    let mut rdr = raw;
+    let ndim = try!(rdr.read_i32::<BigEndian>());
+    let _has_nulls = try!(rdr.read_i32::<BigEndian>());
+    let _elem_oid = try!(rdr.read_u32::<BigEndian>());
+
+    let mut dims = vec![];
+    for _ in 0..ndim {
+        let len = try!(rdr.read_i32::<BigEndian>());
+        let lower_bound = try!(rdr.read_i32::<BigEndian>());
+        dims.push((len, lower_bound));
+    }
+
+    let mut out = String::new();
+    if dims.iter().any(|&(_, lower_bound)| lower_bound != 1) {
+        for &(len, lower_bound) in &dims {
+            out.push_str(&format!("[{}:{}]", lower_bound, lower_bound + len - 1));
+        }
+        out.push('=');
+    }
+    if dims.is_empty() {
+        out.push_str("{}");
+    } else {
+        try!(write_array_dim(member, &dims, &mut rdr, &mut out));
+    }
+    Ok(out)
+}
+
+fn write_array_dim(member: &Type, dims: &[(i32, i32)], rdr: &mut &[u8], out: &mut String)
+                   -> Result<()> {
+    out.push('{');
+    for i in 0..dims[0].0 {
+        if i != 0 {
+            out.push(array_delimiter(member));
+        }
+        if dims.len() > 1 {
+            try!(write_array_dim(member, &dims[1..], rdr, out));
+        } else {
+            match try!(read_value(rdr)) {
+                Some(value) => out.push_str(&quote_element(&try!(to_text(member, value)))),
+                None => out.push_str("NULL"),
+            }
+        }
+    }
+    out.push('}');
+    Ok(())
+}
+
+/// Converts the text representation of a value returned by the server to
+/// its binary representation.
+///
+/// Values of types whose text and binary representations are identical are
+/// passed through unchanged. Other types without a known text format are
+/// rejected, since their binary `FromSql` implementations can't decode them.
+pub fn from_text(ty: &Type, text: Vec<u8>) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    match *ty {
+        Type::Bool => try!(buf.write_u8((&*text == b"t") as u8)),
+        Type::Char => {
+            let b = if text.is_empty() {
+                0
+            } else if text.len() == 4 && text[0] == b'\\' {
+                try!(parse::<u8>(&text[1..], 8))
+            } else {
+                text[0]
+            };
+            try!(buf.write_u8(b));
+        }
+        Type::Int2 => try!(buf.write_i16::<BigEndian>(try!(parse(&text, 10)))),
+        Type::Int4 => try!(buf.write_i32::<BigEndian>(try!(parse(&text, 10)))),
+        Type::Int8 => try!(buf.write_i64::<BigEndian>(try!(parse(&text, 10)))),
+        Type::Oid => try!(buf.write_u32::<BigEndian>(try!(parse(&text, 10)))),
+        Type::Float4 => try!(buf.write_f32::<BigEndian>(try!(parse_float(&text)) as f32)),
+        Type::Float8 => try!(buf.write_f64::<BigEndian>(try!(parse_float(&text)))),
+        Type::Bytea => buf = try!(parse_bytea(&text)),
+        Type::Jsonb => {
+            buf.push(1);
+            buf.extend(text);
+        }
+        Type::Uuid => {
+            let hex = text.into_iter().filter(|&b| b != b'-').collect::<Vec<_>>();
+            if hex.len() != 32 {
+                return conversion_error("invalid UUID value");
+            }
+            for pair in hex.chunks(2) {
+                buf.push(try!(parse(pair, 16)));
+            }
+        }
+        Type::Timestamp | Type::TimestampTZ => {
+            try!(buf.write_i64::<BigEndian>(try!(parse_timestamp(&try!(utf8(&text))))));
+        }
+        Type::Date => try!(buf.write_i32::<BigEndian>(try!(parse_date(&try!(utf8(&text)))))),
+        Type::Time => try!(buf.write_i64::<BigEndian>(try!(parse_time(&try!(utf8(&text)))))),
+        Type::Text | Type::Varchar | Type::Bpchar | Type::Name | Type::Json | Type::Unknown => {
+            buf = text
+        }
+        Type::Other(ref other) if other.name() == "hstore" => {
+            buf = try!(parse_hstore(&try!(utf8(&text))));
+        }
+        Type::Other(ref other) if *other.kind() == Kind::Simple => buf = text,
+        _ => match *ty.kind() {
+            Kind::Array(ref member) => buf = try!(parse_array(member, &try!(utf8(&text)))),
+            _ => return conversion_error("unsupported type in simple query mode"),
+        },
+    }
+    Ok(buf)
+}
+
+fn parse<T>(text: &[u8], radix: u32) -> Result<T> where T: FromStrRadix {
+    match ::std::str::from_utf8(text).ok().and_then(|s| T::from_str_radix(s, radix)) {
+        Some(v) => Ok(v),
+        None => conversion_error("invalid numeric value"),
+    }
+}
+
+trait FromStrRadix: Sized {
+    fn from_str_radix(s: &str, radix: u32) -> Option<Self>;
+}
+
+macro_rules! from_str_radix {
+    ($($t:ident),+) => {
+        $(
+            impl FromStrRadix for $t {
+                fn from_str_radix(s: &str, radix: u32) -> Option<$t> {
+                    $t::from_str_radix(s, radix).ok()
+                }
+            }
+        )+
+    }
+}
+
+from_str_radix!(u8, i16, i32, i64, u32);
+
+fn parse_float(text: &[u8]) -> Result<f64> {
+    match text {
+        b"NaN" => Ok(::std::f64::NAN),
+        b"Infinity" => Ok(::std::f64::INFINITY),
+        b"-Infinity" => Ok(::std::f64::NEG_INFINITY),
+        _ => match ::std::str::from_utf8(text).ok().and_then(|s| s.parse().ok()) {
+            Some(v) => Ok(v),
+            None => conversion_error("invalid floating point value"),
+        },
+    }
+}
+
+fn parse_bytea(text: &[u8]) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    if text.starts_with(b"\\x") {
+        for pair in text[2..].chunks(2) {
+            buf.push(try!(parse(pair, 16)));
+        }
+        return Ok(buf);
+    }
+
+    // the legacy escape format
+    let mut i = 0;
+    while i < text.len() {
+        if text[i] != b'\\' {
+            buf.push(text[i]);
+            i += 1;
+        } else if text.get(i + 1) == Some(&b'\\') {
+            buf.push(b'\\');
+            i += 2;
+        } else if i + 4 <= text.len() {
+            buf.push(try!(parse(&text[i + 1..i + 4], 8)));
+            i += 4;
+        } else {
+            return conversion_error("invalid BYTEA value");
+        }
+    }
+    Ok(buf)
+}
+
+fn invalid_datetime<T>() -> Result<T> {
+    conversion_error("invalid date/time value; simple query mode requires an ISO DateStyle")
+}
+
+fn parse_date(text: &str) -> Result<i32> {
+    match text {
+        "infinity" => return Ok(i32::MAX),
+        "-infinity" => return Ok(i32::MIN),
+        _ => {}
+    }
+
+    let (text, bc) = match text.ends_with(" BC") {
+        true => (&text[..text.len() - 3], true),
+        false => (text, false),
+    };
+    match parse_ymd(text, bc) {
+        Some(days) => Ok(days as i32),
+        None => invalid_datetime(),
+    }
+}
+
+fn parse_ymd(text: &str, bc: bool) -> Option<i64> {
+    let mut parts = text.splitn(3, '-');
+    let year = match parts.next().and_then(|s| s.parse::<i64>().ok()) {
+        Some(year) if bc => 1 - year,
+        Some(year) => year,
+        None => return None,
+    };
+    let month = match parts.next().and_then(|s| s.parse().ok()) {
+        Some(month) => month,
+        None => return None,
+    };
+    let day = match parts.next().and_then(|s| s.parse().ok()) {
+        Some(day) => day,
+        None => return None,
+    };
+    Some(days_from_civil(year, month, day))
+}
+
+fn parse_time(text: &str) -> Result<i64> {
+    match parse_hms(text) {
+        Some(usec) => Ok(usec),
+        None => invalid_datetime(),
+    }
+}
+
+fn parse_hms(text: &str) -> Option<i64> {
+    let (text, frac) = match text.find('.') {
+        Some(idx) => (&text[..idx], &text[idx + 1..]),
+        None => (text, ""),
+    };
+    let mut usec = 0;
+    for (i, part) in text.split(':').enumerate() {
+        let v = match part.parse::<i64>() {
+            Ok(v) if i < 3 => v,
+            _ => return None,
+        };
+        usec = usec * 60 + v;
+    }
+    usec *= USEC_PER_SEC;
+
+    if !frac.is_empty() {
+        if frac.len() > 6 || !frac.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let digits = format!("{:0<6}", frac);
+        usec += digits.parse::<i64>().unwrap_or(0);
+    }
+    Some(usec)
+}
+
+fn parse_timestamp(text: &str) -> Result<i64> {
+    match text {
+        "infinity" => return Ok(i64::MAX),
+        "-infinity" => return Ok(i64::MIN),
+        _ => {}
+    }
+
+    let (text, bc) = match text.ends_with(" BC") {
+        true => (&text[..text.len() - 3], true),
+        false => (text, false),
+    };
+    let mut parts = text.splitn(2, ' ');
+    let date = parts.next().unwrap_or("");
+    let time = parts.next().unwrap_or("");
+
+    let (time, offset) = match time.find(|c| c == '+' || c == '-') {
+        Some(idx) => (&time[..idx], Some(&time[idx..])),
+        None => (time, None),
+    };
+
+    let days = match parse_ymd(date, bc) {
+        Some(days) => days,
+        None => return invalid_datetime(),
+    };
+    let usec = match parse_hms(time) {
+        Some(usec) => usec,
+        None => return invalid_datetime(),
+    };
+    let offset = match offset {
+        Some(offset) => {
+            let sign = if offset.starts_with('-') { -1 } else { 1 };
+            match parse_hms(&offset[1..]) {
+                // an offset of "+05" is five hours, not five seconds
+                Some(usec) if !offset.contains(':') => sign * usec * 3600,
+                Some(usec) if offset.matches(':').count() == 1 => sign * usec * 60,
+                Some(usec) => sign * usec,
+                None => return invalid_datetime(),
+            }
+        }
+        None => 0,
+    };
+
+    Ok(days * USEC_PER_DAY + usec - offset)
+}
+
+// Reads a double-quoted token with backslash escapes from the start of
+// `text`, returning it and the remaining text.
+fn read_quoted(text: &str) -> Option<(String, &str)> {
+    let mut out = String::new();
+    let mut chars = text.char_indices().skip(1);
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, c)) => out.push(c),
+                None => return None,
+            },
+            '"' => return Some((out, &text[idx + 1..])),
+            c => out.push(c),
+        }
+    }
+    None
+}
+
+fn parse_hstore(text: &str) -> Result<Vec<u8>> {
+    let mut pairs = vec![];
+    let mut rest = text.trim_left();
+    while !rest.is_empty() {
+        let (key, after_key) = match read_quoted(rest) {
+            Some(r) => r,
+            None => return conversion_error("invalid HSTORE value"),
+        };
+        rest = after_key.trim_left();
+        if !rest.starts_with("=>") {
+            return conversion_error("invalid HSTORE value");
+        }
+        rest = rest[2..].trim_left();
+
+        let value = if rest.starts_with("NULL") {
+            rest = &rest[4..];
+            None
+        } else {
+            match read_quoted(rest) {
+                Some((value, after_value)) => {
+                    rest = after_value;
+                    Some(value)
+                }
+                None => return conversion_error("invalid HSTORE value"),
+            }
+        };
+        pairs.push((key, value));
+
+        rest = rest.trim_left();
+        if rest.starts_with(',') {
+            rest = rest[1..].trim_left();
+        }
+    }
+
+    let mut buf = vec![];
+    try!(buf.write_i32::<BigEndian>(pairs.len() as i32));
+    for (key, value) in pairs {
+        try!(write_value(&mut buf, Some(key.as_bytes())));
+        try!(write_value(&mut buf, value.as_ref().map(|v| v.as_bytes())));
+    }
+    Ok(buf)
+}
+
+fn write_value(buf: &mut Vec<u8>, value: Option<&[u8]>) -> Result<()> {
+    match value {
+        Some(value) => {
+            try!(buf.write_i32::<BigEndian>(value.len() as i32));
+            buf.extend(value.iter().cloned());
+        }
+        None => try!(buf.write_i32::<BigEndian>(-1)),
+    }
+    Ok(())
+}
+
+enum ArrayNode {
+    Array(Vec<ArrayNode>),
+    Element(Option<String>),
+}
+
+fn parse_array(member: &Type, text: &str) -> Result<Vec<u8>> {
+    let mut lower_bounds = vec![];
+    let mut rest = text;
+    if rest.starts_with('[') {
+        let idx = match rest.find('=') {
+            Some(idx) => idx,
+            None => return conversion_error("invalid array value"),
+        };
+        for dim in rest[..idx].split(']').filter(|s| !s.is_empty()) {
+            match dim.trim_left_matches('[').split(':').next().and_then(|s| s.parse().ok()) {
+                Some(lower_bound) => lower_bounds.push(lower_bound),
+                None => return conversion_error("invalid array value"),
+            }
+        }
+        rest = &rest[idx + 1..];
+    }
+
+    let delimiter = array_delimiter(member);
+    let root = match parse_array_node(rest, delimiter) {
+        Some((node, _)) => node,
+        None => return conversion_error("invalid array value"),
+    };
+
+    let mut dims = vec![];
+    let mut node = &root;
+    while let ArrayNode::Array(ref children) = *node {
+        dims.push(children.len() as i32);
+        match children.first() {
+            Some(child) => node = child,
+            None => break,
+        }
+    }
+    if dims == [0] {
+        dims.clear();
+    }
+
+    let mut elements = vec![];
+    flatten(root, &mut elements);
+
+    let mut buf = vec![];
+    try!(buf.write_i32::<BigEndian>(dims.len() as i32));
+    try!(buf.write_i32::<BigEndian>(elements.iter().any(|e| e.is_none()) as i32));
+    try!(buf.write_u32::<BigEndian>(member.oid()));
+    for (i, &len) in dims.iter().enumerate() {
+        try!(buf.write_i32::<BigEndian>(len));
+        try!(buf.write_i32::<BigEndian>(*lower_bounds.get(i).unwrap_or(&1)));
+    }
+    for element in elements {
+        match element {
+            Some(element) => {
+                let value = try!(from_text(member, element.into_bytes()));
+                try!(write_value(&mut buf, Some(&value)));
+            }
+            None => try!(write_value(&mut buf, None)),
+        }
+    }
+
+    Ok(buf)
+}
+
+fn parse_array_node(text: &str, delimiter: char) -> Option<(ArrayNode, &str)> {
+    let text = text.trim_left();
+    if text.starts_with('{') {
+        let mut children = vec![];
+        let mut rest = text[1..].trim_left();
+        if rest.starts_with('}') {
+            return Some((ArrayNode::Array(children), &rest[1..]));
+        }
+        loop {
+            let (child, after) = match parse_array_node(rest, delimiter) {
+                Some(r) => r,
+                None => return None,
+            };
+            children.push(child);
+            let after = after.trim_left();
+            if after.starts_with(delimiter) {
+                rest = &after[delimiter.len_utf8()..];
+            } else if after.starts_with('}') {
+                return Some((ArrayNode::Array(children), &after[1..]));
+            } else {
+                return None;
+            }
+        }
+    } else if text.starts_with('"') {
+        read_quoted(text).map(|(s, rest)| (ArrayNode::Element(Some(s)), rest))
+    } else {
+        let end = match text.find(|c| c == delimiter || c == '}') {
+            Some(end) => end,
+            None => return None,
+        };
+        let element = text[..end].trim_right();
+        let element = if element.eq_ignore_ascii_case("NULL") {
+            None
+        } else {
+            Some(element.to_owned())
+        };
+        Some((ArrayNode::Element(element), &text[end..]))
+    }
+}
+
+fn flatten(node: ArrayNode, out: &mut Vec<Option<String>>) {
+    match node {
+        ArrayNode::Array(children) => {
+            for child in children {
+                flatten(child, out);
+            }
+        }
+        ArrayNode::Element(element) => out.push(element),
+    }
+}
+
+/// Builds the columns described by a `RowDescription` received in response
+/// to a simple query, converting any values in the text format to the binary
+/// format.
+pub fn decode_text_rows(conn: &mut InnerConnection,
+                        descriptions: Vec<RowDescriptionEntry>,
+                        rows: Vec<Vec<Option<Vec<u8>>>>)
+                        -> Result<(Vec<Column>, Vec<Vec<Option<Vec<u8>>>>)> {
+    let mut columns = vec![];
+    let mut formats = vec![];
+    for desc in descriptions {
+        let type_ = try!(conn.get_type(desc.type_oid));
+        formats.push(desc.format);
+        columns.push(Column::new(desc, type_));
+    }
+
+    let mut data = Vec::with_capacity(rows.len());
+    for row in rows {
+        let mut converted = Vec::with_capacity(row.len());
+        for ((value, column), &format) in row.into_iter().zip(&columns).zip(&formats) {
+            converted.push(match value {
+                Some(value) if format == 0 => Some(try!(from_text(column.type_(), value))),
+                value => value,
+            });
+        }
+        data.push(converted);
+    }
+    Ok((columns, data))
+}
@@ -0,0 +1,82 @@
+//! A mutex which detects attempts by the thread holding it to lock it again.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Mutex, MutexGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+thread_local!(static THREAD_TOKEN: u8 = 0);
+
+// The address of a thread local is unique among live threads, and never 0.
+fn thread_token() -> usize {
+    THREAD_TOKEN.with(|t| t as *const u8 as usize)
+}
+
+pub struct Lock<T> {
+    mutex: Mutex<T>,
+    owner: AtomicUsize,
+}
+
+impl<T> Lock<T> {
+    pub fn new(t: T) -> Lock<T> {
+        Lock {
+            mutex: Mutex::new(t),
+            owner: AtomicUsize::new(0),
+        }
+    }
+
+    /// Acquires the lock, blocking if another thread holds it.
+    ///
+    /// Returns `None` if the current thread already holds the lock, since
+    /// blocking would deadlock. A lock poisoned by a panic is still acquired,
+    /// and the returned guard reports that it was poisoned.
+    pub fn lock(&self) -> Option<LockGuard<T>> {
+        let token = thread_token();
+        if self.owner.load(Ordering::SeqCst) == token {
+            return None;
+        }
+
+        let (guard, poisoned) = match self.mutex.lock() {
+            Ok(guard) => (guard, false),
+            Err(err) => (err.into_inner(), true),
+        };
+        self.owner.store(token, Ordering::SeqCst);
+        Some(LockGuard {
+            guard: guard,
+            owner: &self.owner,
+            poisoned: poisoned,
+        })
+    }
+}
+
+pub struct LockGuard<'a, T: 'a> {
+    guard: MutexGuard<'a, T>,
+    owner: &'a AtomicUsize,
+    poisoned: bool,
+}
+
+impl<'a, T> Drop for LockGuard<'a, T> {
+    fn drop(&mut self) {
+        self.owner.store(0, Ordering::SeqCst);
+    }
+}
+
+impl<'a, T> LockGuard<'a, T> {
+    /// Determines if a thread panicked while holding the lock.
+    pub fn poisoned(&self) -> bool {
+        self.poisoned
+    }
+}
+
+impl<'a, T> Deref for LockGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<'a, T> DerefMut for LockGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
@@ -25,3 +25,60 @@ macro_rules! bad_response {
         return Err(::error::Error::IoError(::bad_response()));
     })
 }
+
+macro_rules! try_ready {
+    ($e:expr) => (
+        match try!($e) {
+            ::nonblocking::Async::Ready(t) => t,
+            ::nonblocking::Async::NotReady => return Ok(::nonblocking::Async::NotReady),
+        }
+    )
+}
+
+/// Implements `FromRow` for a struct, reading each of the listed fields from
+/// the column of the same name.
+///
+/// Every field of the struct must be listed, and the type of each must
+/// implement `FromSql`. Errors converting a value identify its column, and a
+/// missing column is reported as an `Error::Conversion` naming the field.
+///
+/// ## Example
+///
+/// ```rust,no_run
+/// # #[macro_use] extern crate postgres;
+/// # use postgres::{Connection, SslMode};
+/// struct Person {
+///     id: i32,
+///     name: String,
+///     data: Option<Vec<u8>>,
+/// }
+///
+/// from_row!(Person { id, name, data });
+///
+/// # fn main() {
+/// # let conn = Connection::connect("", &SslMode::None).unwrap();
+/// let rows = conn.query("SELECT id, name, data FROM person", &[]).unwrap();
+/// for person in rows.into_typed::<Person>().unwrap() {
+///     println!("Found person {}", person.name);
+/// }
+/// # }
+/// ```
+#[macro_export]
+macro_rules! from_row {
+    ($name:ident { $($field:ident),* $(,)* }) => {
+        impl $crate::rows::FromRow for $name {
+            fn from_row(row: &$crate::rows::Row) -> $crate::Result<$name> {
+                Ok($name {
+                    $(
+                        $field: match $crate::rows::get_field(row,
+                                                              stringify!($name),
+                                                              stringify!($field)) {
+                            Ok(value) => value,
+                            Err(err) => return Err(err),
+                        },
+                    )*
+                })
+            }
+        }
+    }
+}
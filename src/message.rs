@@ -71,7 +71,7 @@ pub enum BackendMessage {
     ParseComplete,
     PortalSuspended,
     ReadyForQuery {
-        _state: u8
+        state: u8
     },
     RowDescription {
         descriptions: Vec<RowDescriptionEntry>
@@ -120,6 +120,7 @@ pub enum FrontendMessage<'a> {
         portal: &'a str,
         max_rows: i32
     },
+    Flush,
     Parse {
         name: &'a str,
         query: &'a str,
@@ -246,6 +247,7 @@ impl<W: Write> WriteMessage for W {
                 try!(buf.write_u8(0));
             }
             SslRequest { code } => try!(buf.write_u32::<BigEndian>(code)),
+            Flush => ident = Some(b'H'),
             Sync => ident = Some(b'S'),
             Terminate => ident = Some(b'X'),
         }
@@ -349,7 +351,7 @@ impl<R: BufRead> ReadMessage for R {
             },
             b't' => try!(read_parameter_description(&mut rdr)),
             b'T' => try!(read_row_description(&mut rdr)),
-            b'Z' => ReadyForQuery { _state: try!(rdr.read_u8()) },
+            b'Z' => ReadyForQuery { state: try!(rdr.read_u8()) },
             t => return Err(io::Error::new(io::ErrorKind::Other,
                                            format!("unexpected message tag `{}`", t))),
         };
@@ -0,0 +1,195 @@
+//! Statements with named parameters.
+
+use debug_builders::DebugStruct;
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, BuildHasher};
+
+use rows::Rows;
+use stmt::Statement;
+use types::ToSql;
+use {NamedStatementNew, Result, misuse};
+
+/// A set of parameter values looked up by name.
+///
+/// Implementations are provided for maps from names to values and for
+/// slices of name/value pairs. The `named_params!` macro will generate an
+/// implementation for a struct whose fields are the parameters.
+pub trait NamedParams {
+    /// Returns the value of the named parameter, if present.
+    fn get(&self, name: &str) -> Option<&ToSql>;
+
+    /// Returns the names of all parameters present.
+    fn names(&self) -> Vec<&str>;
+}
+
+impl<'a, K, S> NamedParams for HashMap<K, &'a ToSql, S>
+        where K: Borrow<str> + Hash + Eq, S: BuildHasher {
+    fn get(&self, name: &str) -> Option<&ToSql> {
+        HashMap::get(self, name).map(|&v| v)
+    }
+
+    fn names(&self) -> Vec<&str> {
+        self.keys().map(|k| k.borrow()).collect()
+    }
+}
+
+impl<'a> NamedParams for [(&'a str, &'a ToSql)] {
+    fn get(&self, name: &str) -> Option<&ToSql> {
+        self.iter().find(|&&(n, _)| n == name).map(|&(_, v)| v)
+    }
+
+    fn names(&self) -> Vec<&str> {
+        self.iter().map(|&(n, _)| n).collect()
+    }
+}
+
+/// Generates an implementation of `NamedParams` for a struct.
+///
+/// Each listed field is a parameter with the same name, and must implement
+/// `ToSql`.
+///
+/// ## Example
+///
+/// ```rust,no_run
+/// # #[macro_use] extern crate postgres;
+/// struct Person {
+///     name: String,
+///     age: i32,
+/// }
+///
+/// named_params!(Person { name, age });
+/// # fn main() {}
+/// ```
+#[macro_export]
+macro_rules! named_params {
+    ($t:ty { $($field:ident),* }) => (
+        impl $crate::named::NamedParams for $t {
+            fn get(&self, name: &str) -> ::std::option::Option<&$crate::types::ToSql> {
+                match name {
+                    $(stringify!($field) => ::std::option::Option::Some(&self.$field),)*
+                    _ => ::std::option::Option::None,
+                }
+            }
+
+            fn names(&self) -> ::std::vec::Vec<&str> {
+                vec![$(stringify!($field)),*]
+            }
+        }
+    );
+    ($t:ty { $($field:ident),+ , }) => (
+        named_params!($t { $($field),+ });
+    );
+}
+
+/// A prepared statement whose parameters are referred to by name.
+///
+/// Parameters are written as `:name` or `@name`. A sigil directly preceded
+/// by an identifier character is not treated as a parameter, so casts like
+/// `x::INT` and array slices like `arr[1:n]` work as expected, but a slice
+/// with a bare parameter as its upper bound like `arr[:n]` is treated as a
+/// parameter. The `@` operator must be followed by whitespace to avoid being
+/// treated as a parameter. Sigils inside of string literals, quoted
+/// identifiers, dollar-quoted strings and comments are ignored.
+///
+/// A name may be used any number of times in the query. Named parameters
+/// cannot be mixed with positional `$n` parameters.
+///
+/// ## Example
+///
+/// ```rust,no_run
+/// # use postgres::{Connection, SslMode};
+/// # use postgres::types::ToSql;
+/// # let conn = Connection::connect("", &SslMode::None).unwrap();
+/// let stmt = conn.prepare_named("SELECT name FROM person WHERE id = :id").unwrap();
+/// let params: &[(&str, &ToSql)] = &[("id", &1i32)];
+/// for row in &stmt.query(params).unwrap() {
+///     let name: String = row.get(0);
+///     println!("{}", name);
+/// }
+/// ```
+pub struct NamedStatement<'conn> {
+    stmt: Statement<'conn>,
+    names: Vec<String>,
+}
+
+impl<'conn> fmt::Debug for NamedStatement<'conn> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "NamedStatement")
+            .field("statement", &self.stmt)
+            .field("names", &self.names)
+            .finish()
+    }
+}
+
+impl<'conn> NamedStatementNew<'conn> for NamedStatement<'conn> {
+    fn new(stmt: Statement<'conn>, names: Vec<String>) -> NamedStatement<'conn> {
+        NamedStatement {
+            stmt: stmt,
+            names: names,
+        }
+    }
+}
+
+impl<'conn> NamedStatement<'conn> {
+    /// Returns the names of the statement's parameters, in the order of
+    /// their first appearance in the query.
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Returns the underlying statement, whose parameters are in the order
+    /// returned by `names`.
+    pub fn statement(&self) -> &Statement<'conn> {
+        &self.stmt
+    }
+
+    /// Like `Statement::execute`, except that parameters are looked up by
+    /// name.
+    ///
+    /// Returns `Error::Misuse` without contacting the server if a parameter
+    /// of the statement is missing from `params` or if `params` contains a
+    /// parameter the statement does not use.
+    pub fn execute<P: ?Sized + NamedParams>(&self, params: &P) -> Result<u64> {
+        let params = try!(self.bind(params));
+        self.stmt.execute(&params)
+    }
+
+    /// Like `Statement::query`, except that parameters are looked up by
+    /// name.
+    ///
+    /// Returns `Error::Misuse` without contacting the server if a parameter
+    /// of the statement is missing from `params` or if `params` contains a
+    /// parameter the statement does not use.
+    pub fn query<P: ?Sized + NamedParams>(&self, params: &P) -> Result<Rows<'conn>> {
+        let params = try!(self.bind(params));
+        self.stmt.query(&params)
+    }
+
+    fn bind<'a, P: ?Sized + NamedParams>(&self, params: &'a P) -> Result<Vec<&'a ToSql>> {
+        let mut values = Vec::with_capacity(self.names.len());
+        let mut missing = vec![];
+        for name in &self.names {
+            match params.get(name) {
+                Some(value) => values.push(value),
+                None => missing.push(&**name),
+            }
+        }
+        if !missing.is_empty() {
+            return Err(misuse(format!("missing named parameters: {}",
+                                             missing.join(", "))));
+        }
+
+        let mut unused = params.names()
+                               .into_iter()
+                               .filter(|n| !self.names.iter().any(|m| m == n))
+                               .collect::<Vec<_>>();
+        if !unused.is_empty() {
+            unused.sort();
+            return Err(misuse(format!("unused named parameters: {}", unused.join(", "))));
+        }
+
+        Ok(values)
+    }
+}
@@ -0,0 +1,1220 @@
+//! A non-blocking interface to a Postgres database.
+//!
+//! The types in this module mirror `Connection`, `Statement` and `Transaction`
+//! but never block the calling thread once connected. Each request returns an
+//! operation which is driven to completion by calling its `poll` method,
+//! typically whenever an event loop reports that the connection's socket is
+//! readable or writable. A `poll` which cannot make progress without more
+//! data from the server returns `Async::NotReady`.
+//!
+//! A connection processes one operation at a time: an operation must be
+//! polled until it completes before the next one is started.
+//!
+//! SSL and Unix sockets are not supported. Name resolution and the TCP
+//! handshake made by `Connection::connect` block.
+//!
+//! ## Example
+//!
+//! ```rust,no_run
+//! use postgres::nonblocking::{Async, Connection, Operation};
+//!
+//! # fn wait_for_socket(_: &Connection) {}
+//! let mut connect = Connection::connect("postgres://postgres@localhost").unwrap();
+//! let mut conn = loop {
+//!     match connect.poll().unwrap() {
+//!         Async::Ready(conn) => break conn,
+//!         Async::NotReady => {} // wait for the socket to become readable
+//!     }
+//! };
+//!
+//! let mut prepare = conn.prepare("SELECT $1::INT").unwrap();
+//! let stmt = loop {
+//!     match prepare.poll(&mut conn).unwrap() {
+//!         Async::Ready(stmt) => break stmt,
+//!         Async::NotReady => wait_for_socket(&conn),
+//!     }
+//! };
+//!
+//! let mut query = stmt.query(&mut conn, &[&1i32]).unwrap();
+//! let rows = loop {
+//!     match query.poll(&mut conn).unwrap() {
+//!         Async::Ready(rows) => break rows,
+//!         Async::NotReady => wait_for_socket(&conn),
+//!     }
+//! };
+//! let value: i32 = rows[0].get(0);
+//! assert_eq!(1, value);
+//! ```
+
+use byteorder::{BigEndian, ReadBytesExt};
+use debug_builders::DebugStruct;
+use std::any;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io;
+use std::io::prelude::*;
+use std::mem;
+use std::result;
+use std::sync::Arc;
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+#[cfg(windows)]
+use std::os::windows::io::{AsRawSocket, RawSocket};
+
+use {Result, IntoConnectParams, ConnectParams, UserInfo, HandleNotice, LoggingNoticeHandler};
+use misuse;
+use {Notification, CancelData, DbErrorNew, OtherNew, TypeNew, ColumnNew, SessionInfoNew};
+use {CommandTagNew, ValueErrorNew};
+use error::{Error, ConnectError, DbError, SqlState, ValueError, ValueLocation};
+use message::{self, FrontendMessage, BackendMessage, RowDescriptionEntry};
+use message::{ReadMessage, WriteMessage};
+use message::BackendMessage::*;
+use message::FrontendMessage::*;
+use priv_io::{self, Stream};
+use rows::RowIndex;
+use stmt::{Column, CommandTag};
+use types::{FromSql, ToSql, IsNull, Kind, Oid, Other, SessionInfo, Type};
+
+/// The state of an operation.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Async<T> {
+    /// The operation has completed with a value.
+    Ready(T),
+    /// The operation cannot make progress until the socket is readable or
+    /// writable.
+    NotReady,
+}
+
+impl<T> Async<T> {
+    /// Determines if the value is `Ready`.
+    pub fn is_ready(&self) -> bool {
+        match *self {
+            Async::Ready(_) => true,
+            Async::NotReady => false,
+        }
+    }
+
+    /// Maps the value of a `Ready` state.
+    pub fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Async<U> {
+        match self {
+            Async::Ready(t) => Async::Ready(f(t)),
+            Async::NotReady => Async::NotReady,
+        }
+    }
+}
+
+/// An in-progress request on a `Connection`.
+pub trait Operation {
+    /// The value produced when the operation completes.
+    type Item;
+
+    /// Makes as much progress as possible without blocking.
+    ///
+    /// Once an operation has returned a value or an error, it must not be
+    /// polled again.
+    fn poll(&mut self, conn: &mut Connection) -> Result<Async<Self::Item>>;
+}
+
+impl<'a, O: Operation + ?Sized> Operation for &'a mut O {
+    type Item = O::Item;
+
+    fn poll(&mut self, conn: &mut Connection) -> Result<Async<O::Item>> {
+        (**self).poll(conn)
+    }
+}
+
+fn busy() -> io::Error {
+    io::Error::new(io::ErrorKind::Other,
+                   "another operation is in progress on the connection")
+}
+
+// Everything the server sent in response to a request, up to the next
+// `ReadyForQuery`.
+struct Response {
+    param_types: Vec<Oid>,
+    columns: Vec<RowDescriptionEntry>,
+    rows: Vec<Vec<Option<Vec<u8>>>>,
+    tag: Option<String>,
+    error: Option<DbError>,
+}
+
+impl Response {
+    fn new() -> Response {
+        Response {
+            param_types: vec![],
+            columns: vec![],
+            rows: vec![],
+            tag: None,
+            error: None,
+        }
+    }
+
+    fn check(&mut self) -> Result<()> {
+        match self.error.take() {
+            Some(err) => Err(Error::DbError(err)),
+            None => Ok(()),
+        }
+    }
+
+    fn update_count(&mut self) -> u64 {
+        self.tag.take().and_then(|tag| CommandTag::new(tag).rows()).unwrap_or(0)
+    }
+}
+
+impl fmt::Debug for Response {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "Response")
+            .field("rows", &self.rows.len())
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+enum Event {
+    Done,
+    CopyIn,
+    CopyOut,
+    CopyData(Vec<u8>),
+}
+
+/// A non-blocking connection to a Postgres database.
+pub struct Connection {
+    stream: Stream,
+    in_buf: Vec<u8>,
+    in_pos: usize,
+    out_buf: Vec<u8>,
+    out_pos: usize,
+    notice_handler: Box<HandleNotice>,
+    notifications: VecDeque<Notification>,
+    cancel_data: CancelData,
+    unknown_types: HashMap<Oid, Type>,
+    parameters: Arc<HashMap<String, String>>,
+    next_stmt_id: u32,
+    trans_depth: u32,
+    busy: bool,
+    desynchronized: bool,
+}
+
+impl fmt::Debug for Connection {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "Connection")
+            .field("cancel_data", &self.cancel_data)
+            .field("notifications", &self.notifications.len())
+            .field("transaction_depth", &self.trans_depth)
+            .field("busy", &self.busy)
+            .field("desynchronized", &self.desynchronized)
+            .finish()
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if !self.desynchronized && !self.busy {
+            let _ = self.out_buf.write_message(&Terminate);
+            let _ = self.flush();
+        }
+    }
+}
+
+impl Connection {
+    /// Begins connecting to a Postgres server.
+    ///
+    /// See `postgres::Connection::connect` for details of the `params`
+    /// argument. The returned `Connect` must be polled until it yields the
+    /// connection.
+    pub fn connect<T>(params: T) -> result::Result<Connect, ConnectError>
+            where T: IntoConnectParams {
+        let params = try!(params.into_connect_params());
+        let stream = try!(priv_io::open_nonblocking(&params));
+
+        let ConnectParams { user, database, options, .. } = params;
+        let user = try!(user.ok_or(ConnectError::MissingUser));
+        let options = ::startup_parameters(&user, database, options);
+
+        let mut conn = Connection {
+            stream: stream,
+            in_buf: vec![],
+            in_pos: 0,
+            out_buf: vec![],
+            out_pos: 0,
+            notice_handler: Box::new(LoggingNoticeHandler),
+            notifications: VecDeque::new(),
+            cancel_data: CancelData { process_id: 0, secret_key: 0 },
+            unknown_types: HashMap::new(),
+            parameters: Arc::new(HashMap::new()),
+            next_stmt_id: 0,
+            trans_depth: 0,
+            busy: true,
+            desynchronized: false,
+        };
+        try!(conn.out_buf.write_message(&StartupMessage {
+            version: message::PROTOCOL_VERSION,
+            parameters: &options,
+        }));
+
+        Ok(Connect {
+            conn: Some(conn),
+            user: user,
+            authenticated: false,
+        })
+    }
+
+    /// Sets the notice handler for the connection, returning the old handler.
+    pub fn set_notice_handler(&mut self, handler: Box<HandleNotice>) -> Box<HandleNotice> {
+        mem::replace(&mut self.notice_handler, handler)
+    }
+
+    /// Returns the information used to cancel queries made on this
+    /// connection.
+    ///
+    /// Cancellation requests are sent over a separate, blocking connection
+    /// with `postgres::cancel_query`.
+    pub fn cancel_data(&self) -> CancelData {
+        self.cancel_data
+    }
+
+    /// Returns the value of the specified Postgres backend parameter, such as
+    /// `timezone` or `server_version`.
+    pub fn parameter(&self, param: &str) -> Option<String> {
+        self.parameters.get(param).cloned()
+    }
+
+    /// Determines if the connection is no longer usable due to an earlier IO
+    /// error or protocol violation.
+    pub fn is_desynchronized(&self) -> bool {
+        self.desynchronized
+    }
+
+    /// Determines if an operation is in progress on the connection.
+    pub fn is_busy(&self) -> bool {
+        self.busy
+    }
+
+    /// Determines if the connection is not currently in a transaction.
+    pub fn is_active(&self) -> bool {
+        self.trans_depth == 0
+    }
+
+    /// Determines if the connection has buffered data which has not yet been
+    /// written to the socket.
+    ///
+    /// An event loop should wait for the socket to become writable as well as
+    /// readable while this returns `true`.
+    pub fn wants_write(&self) -> bool {
+        self.out_pos < self.out_buf.len()
+    }
+
+    /// Begins preparing a statement.
+    pub fn prepare(&mut self, query: &str) -> Result<Prepare> {
+        let name = format!("s{}", self.next_stmt_id);
+        self.next_stmt_id += 1;
+        debug!("preparing query with name `{}`: {}", name, query);
+        try!(self.start(&[
+            Parse {
+                name: &name,
+                query: query,
+                param_types: &[],
+            },
+            Describe {
+                variant: b'S',
+                name: &name,
+            },
+            Sync]));
+        Ok(Prepare {
+            name: name,
+            response: Response::new(),
+            resolver: None,
+        })
+    }
+
+    /// Begins executing a series of statements separated by `;`, which may
+    /// not take parameters.
+    ///
+    /// Like `postgres::Connection::batch_execute`.
+    pub fn batch_execute(&mut self, query: &str) -> Result<BatchExecute> {
+        debug!("executing query: {}", query);
+        try!(self.start(&[FrontendMessage::Query { query: query }]));
+        Ok(BatchExecute { response: Response::new() })
+    }
+
+    /// Begins a new transaction.
+    ///
+    /// Returns `Error::Misuse` if a transaction is already active.
+    pub fn transaction(&mut self) -> Result<Begin> {
+        if self.trans_depth != 0 {
+            return Err(misuse("`transaction` must be called on the active transaction"));
+        }
+        Begin::new(self, "BEGIN", 1)
+    }
+
+    /// Returns the oldest pending notification.
+    ///
+    /// Notifications are only read from the socket while no operation is in
+    /// progress, though any that arrive during an operation are queued.
+    pub fn poll_notification(&mut self) -> Result<Async<Notification>> {
+        if let Some(notification) = self.notifications.pop_front() {
+            return Ok(Async::Ready(notification));
+        }
+        if self.busy {
+            return Ok(Async::NotReady);
+        }
+        check_desync!(self);
+
+        match try!(self.poll_message()) {
+            Async::Ready(_) => bad_response!(self),
+            Async::NotReady => {}
+        }
+        Ok(match self.notifications.pop_front() {
+            Some(notification) => Async::Ready(notification),
+            None => Async::NotReady,
+        })
+    }
+
+    fn start(&mut self, messages: &[FrontendMessage]) -> Result<()> {
+        check_desync!(self);
+        if self.busy {
+            return Err(Error::IoError(busy()));
+        }
+        for message in messages {
+            try!(self.out_buf.write_message(message));
+        }
+        self.busy = true;
+        Ok(())
+    }
+
+    fn bind(&mut self, stmt: &Statement, params: &[&ToSql]) -> Result<()> {
+        if stmt.param_types.len() != params.len() {
+            return Err(misuse(format!("expected {} parameters but got {}",
+                                      stmt.param_types.len(),
+                                      params.len())));
+        }
+        debug!("executing statement {} with parameters: {:?}", stmt.name, params);
+        let mut values = vec![];
+        for (i, (param, ty)) in params.iter().zip(stmt.param_types.iter()).enumerate() {
+            let mut buf = vec![];
+            let is_null = try!(param.to_sql_checked(ty, &mut buf, &SessionInfo::new(&self.parameters))
+                                    .map_err(|err| {
+                                        ValueError::wrap(ValueLocation::Parameter(i),
+                                                         ty.clone(),
+                                                         param.rust_type_name(),
+                                                         err)
+                                    }));
+            match is_null {
+                IsNull::Yes => values.push(None),
+                IsNull::No => values.push(Some(buf)),
+            }
+        }
+
+        self.start(&[
+            Bind {
+                portal: "",
+                statement: &stmt.name,
+                formats: &[1],
+                values: &values,
+                result_formats: &[1],
+            },
+            FrontendMessage::Execute {
+                portal: "",
+                max_rows: 0,
+            },
+            Sync])
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        while self.out_pos < self.out_buf.len() {
+            match self.stream.write(&self.out_buf[self.out_pos..]) {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                   "the server closed the connection")),
+                Ok(len) => self.out_pos += len,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.out_buf.clear();
+        self.out_pos = 0;
+        Ok(())
+    }
+
+    // Parses a message if a complete one has been buffered.
+    fn parse_message(&mut self) -> io::Result<Option<BackendMessage>> {
+        let buf = &self.in_buf[self.in_pos..];
+        if buf.len() < 5 {
+            return Ok(None);
+        }
+        let len = 1 + try!((&mut &buf[1..5]).read_u32::<BigEndian>()) as usize;
+        if buf.len() < len {
+            return Ok(None);
+        }
+        let message = try!((&mut &buf[..len]).read_message());
+        self.in_pos += len;
+        Ok(Some(message))
+    }
+
+    fn poll_message(&mut self) -> io::Result<Async<BackendMessage>> {
+        let message = self.poll_message_inner();
+        if message.is_err() {
+            self.desynchronized = true;
+        }
+        message
+    }
+
+    fn poll_message_inner(&mut self) -> io::Result<Async<BackendMessage>> {
+        try!(self.flush());
+        loop {
+            match try!(self.parse_message()) {
+                Some(NoticeResponse { fields }) => {
+                    if let Ok(err) = DbError::new_raw(fields) {
+                        self.notice_handler.handle_notice(err);
+                    }
+                }
+                Some(ParameterStatus { parameter, value }) => {
+                    Arc::make_mut(&mut self.parameters).insert(parameter, value);
+                }
+                Some(NotificationResponse { pid, channel, payload }) => {
+                    self.notifications.push_back(Notification {
+                        pid: pid,
+                        channel: channel,
+                        payload: payload,
+                    });
+                }
+                Some(message) => return Ok(Async::Ready(message)),
+                None => {
+                    if self.in_pos > 0 {
+                        self.in_buf.drain(..self.in_pos);
+                        self.in_pos = 0;
+                    }
+                    let mut buf = [0; 8 * 1024];
+                    match self.stream.read(&mut buf) {
+                        Ok(0) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                                           "the server closed the connection")),
+                        Ok(len) => self.in_buf.extend_from_slice(&buf[..len]),
+                        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                            return Ok(Async::NotReady);
+                        }
+                        Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+            }
+        }
+    }
+
+    fn poll_response(&mut self, response: &mut Response) -> Result<Async<Event>> {
+        loop {
+            match try_ready!(self.poll_message()) {
+                ParseComplete | BindComplete | CloseComplete | NoData | EmptyQueryResponse
+                | PortalSuspended | BCopyDone => {}
+                ParameterDescription { types } => response.param_types = types,
+                RowDescription { descriptions } => response.columns = descriptions,
+                DataRow { row } => response.rows.push(row),
+                CommandComplete { tag } => response.tag = Some(tag),
+                ErrorResponse { fields } => {
+                    let err = match DbError::new_raw(fields) {
+                        Ok(err) => err,
+                        Err(()) => bad_response!(self),
+                    };
+                    if response.error.is_none() {
+                        response.error = Some(err);
+                    }
+                }
+                CopyInResponse { .. } => return Ok(Async::Ready(Event::CopyIn)),
+                CopyOutResponse { .. } => return Ok(Async::Ready(Event::CopyOut)),
+                BCopyData { data } => return Ok(Async::Ready(Event::CopyData(data))),
+                ReadyForQuery { .. } => {
+                    self.busy = false;
+                    return Ok(Async::Ready(Event::Done));
+                }
+                _ => bad_response!(self),
+            }
+        }
+    }
+
+    // Like `poll_response`, but refuses any COPY started by the request. The
+    // server ignores a `Sync` sent during a COPY, so requests using the
+    // extended protocol need another after the `CopyFail`.
+    fn poll_done(&mut self, response: &mut Response, extended: bool) -> Result<Async<()>> {
+        loop {
+            match try_ready!(self.poll_response(response)) {
+                Event::Done => return Ok(Async::Ready(())),
+                Event::CopyIn => {
+                    try!(self.out_buf.write_message(&CopyFail {
+                        message: "COPY queries cannot be directly executed",
+                    }));
+                    if extended {
+                        try!(self.out_buf.write_message(&Sync));
+                    }
+                }
+                Event::CopyOut | Event::CopyData(_) => {}
+            }
+        }
+    }
+
+    fn known_type(&self, oid: Oid) -> Option<Type> {
+        match Type::new(oid) {
+            Some(ty) => Some(ty),
+            None => self.unknown_types.get(&oid).cloned(),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl AsRawFd for Connection {
+    fn as_raw_fd(&self) -> RawFd {
+        self.stream.as_raw_fd()
+    }
+}
+
+#[cfg(windows)]
+impl AsRawSocket for Connection {
+    fn as_raw_socket(&self) -> RawSocket {
+        self.stream.as_raw_socket()
+    }
+}
+
+/// An in-progress connection attempt.
+pub struct Connect {
+    conn: Option<Connection>,
+    user: UserInfo,
+    authenticated: bool,
+}
+
+impl fmt::Debug for Connect {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "Connect")
+            .field("authenticated", &self.authenticated)
+            .finish()
+    }
+}
+
+impl Connect {
+    /// Makes as much progress as possible without blocking, returning the
+    /// connection once the server is ready for queries.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if called after the connection has been returned.
+    pub fn poll(&mut self) -> result::Result<Async<Connection>, ConnectError> {
+        loop {
+            let message = {
+                let conn = self.conn.as_mut().expect("`poll` called on a completed `Connect`");
+                match try!(conn.poll_message()) {
+                    Async::Ready(message) => message,
+                    Async::NotReady => return Ok(Async::NotReady),
+                }
+            };
+
+            if !self.authenticated {
+                try!(self.handle_auth(message));
+                continue;
+            }
+
+            match message {
+                BackendKeyData { process_id, secret_key } => {
+                    let conn = self.conn.as_mut().unwrap();
+                    conn.cancel_data.process_id = process_id;
+                    conn.cancel_data.secret_key = secret_key;
+                }
+                ReadyForQuery { .. } => {
+                    let mut conn = self.conn.take().unwrap();
+                    conn.busy = false;
+                    return Ok(Async::Ready(conn));
+                }
+                ErrorResponse { fields } => return DbError::new_connect(fields),
+                _ => return Err(ConnectError::IoError(::bad_response())),
+            }
+        }
+    }
+
+    fn handle_auth(&mut self, message: BackendMessage) -> result::Result<(), ConnectError> {
+        let password = match message {
+            AuthenticationOk => {
+                self.authenticated = true;
+                return Ok(());
+            }
+            AuthenticationCleartextPassword => {
+                try!(self.user.password.clone().ok_or(ConnectError::MissingPassword))
+            }
+            AuthenticationMD5Password { salt } => {
+                let pass = try!(self.user.password.as_ref().ok_or(ConnectError::MissingPassword));
+                ::md5_password(&self.user.user, pass, &salt)
+            }
+            AuthenticationKerberosV5
+            | AuthenticationSCMCredential
+            | AuthenticationGSS
+            | AuthenticationSSPI => return Err(ConnectError::UnsupportedAuthentication),
+            ErrorResponse { fields } => return DbError::new_connect(fields),
+            _ => return Err(ConnectError::IoError(::bad_response())),
+        };
+
+        let conn = self.conn.as_mut().unwrap();
+        try!(conn.out_buf.write_message(&PasswordMessage { password: &password }));
+        Ok(())
+    }
+}
+
+/// A prepared statement on a non-blocking connection.
+///
+/// The statement remains prepared until it is closed or the connection ends.
+#[derive(Debug)]
+pub struct Statement {
+    name: String,
+    param_types: Vec<Type>,
+    columns: Arc<Vec<Column>>,
+}
+
+impl Statement {
+    /// Returns a slice containing the expected parameter types.
+    pub fn param_types(&self) -> &[Type] {
+        &self.param_types
+    }
+
+    /// Returns a slice describing the columns of the result of the query.
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Begins executing the statement, eventually returning the number of
+    /// rows modified.
+    ///
+    /// Returns `Error::Misuse` if the number of parameters provided does not
+    /// match the number expected.
+    pub fn execute(&self, conn: &mut Connection, params: &[&ToSql]) -> Result<Execute> {
+        try!(conn.bind(self, params));
+        Ok(Execute { response: Response::new() })
+    }
+
+    /// Begins executing the statement, eventually returning the resulting
+    /// rows.
+    ///
+    /// Returns `Error::Misuse` if the number of parameters provided does not
+    /// match the number expected.
+    pub fn query(&self, conn: &mut Connection, params: &[&ToSql]) -> Result<Query> {
+        try!(conn.bind(self, params));
+        Ok(Query {
+            columns: self.columns.clone(),
+            response: Response::new(),
+        })
+    }
+
+    /// Begins executing a `COPY FROM STDIN` statement.
+    ///
+    /// Data is supplied through the returned `CopyIn`.
+    ///
+    /// Returns `Error::Misuse` if the number of parameters provided does not
+    /// match the number expected.
+    pub fn copy_in(&self, conn: &mut Connection, params: &[&ToSql]) -> Result<CopyIn> {
+        try!(conn.bind(self, params));
+        Ok(CopyIn {
+            response: Response::new(),
+            pending: vec![],
+            started: false,
+            finished: false,
+        })
+    }
+
+    /// Begins executing a `COPY TO STDOUT` statement.
+    ///
+    /// Returns `Error::Misuse` if the number of parameters provided does not
+    /// match the number expected.
+    pub fn copy_out(&self, conn: &mut Connection, params: &[&ToSql]) -> Result<CopyOut> {
+        try!(conn.bind(self, params));
+        Ok(CopyOut {
+            response: Response::new(),
+            started: false,
+            rows: 0,
+        })
+    }
+
+    /// Begins closing the statement on the server.
+    pub fn close(self, conn: &mut Connection) -> Result<Close> {
+        try!(conn.start(&[
+            FrontendMessage::Close {
+                variant: b'S',
+                name: &self.name,
+            },
+            Sync]));
+        Ok(Close { response: Response::new() })
+    }
+}
+
+/// A row of data returned by a non-blocking query.
+pub struct Row {
+    columns: Arc<Vec<Column>>,
+    parameters: Arc<HashMap<String, String>>,
+    data: Vec<Option<Vec<u8>>>,
+}
+
+impl fmt::Debug for Row {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "Row")
+            .field("columns", &self.columns)
+            .finish()
+    }
+}
+
+impl Row {
+    /// Returns the number of values in the row.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns information about the columns in the row.
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Like `postgres::rows::Row::get_opt`.
+    pub fn get_opt<I, T>(&self, idx: I) -> Result<T> where I: RowIndex, T: FromSql {
+        let idx = try!(idx.idx(&self.columns).ok_or(Error::InvalidColumn));
+        let column = &self.columns[idx];
+        let ty = column.type_();
+        let result = if !<T as FromSql>::accepts(ty) {
+            Err(Error::WrongType(ty.clone()))
+        } else {
+            FromSql::from_sql_nullable(ty, self.data[idx].as_ref().map(|e| &**e).as_mut(),
+                                       &SessionInfo::new(&self.parameters))
+        };
+        result.map_err(|err| {
+            let location = ValueLocation::Column {
+                index: idx,
+                name: column.name().to_owned(),
+            };
+            ValueError::wrap(location, ty.clone(), any::type_name::<T>(), err)
+        })
+    }
+
+    /// Like `postgres::rows::Row::get`.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if the index does not reference a column or the return type is
+    /// not compatible with the Postgres type.
+    pub fn get<I, T>(&self, idx: I) -> T where I: RowIndex + fmt::Debug + Clone, T: FromSql {
+        match self.get_opt(idx.clone()) {
+            Ok(ok) => ok,
+            Err(err) => panic!("error retrieving column {:?}: {:?}", idx, err),
+        }
+    }
+}
+
+// Looks up the names and kinds of types the connection hasn't seen before.
+struct ResolveTypes {
+    stack: Vec<Oid>,
+    response: Option<Response>,
+    fallback: bool,
+}
+
+impl fmt::Debug for ResolveTypes {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "ResolveTypes")
+            .field("pending", &self.stack)
+            .finish()
+    }
+}
+
+impl ResolveTypes {
+    fn poll(&mut self, conn: &mut Connection) -> Result<Async<()>> {
+        loop {
+            let oid = match self.stack.last() {
+                Some(&oid) => oid,
+                None => return Ok(Async::Ready(())),
+            };
+            if conn.known_type(oid).is_some() {
+                self.stack.pop();
+                continue;
+            }
+
+            if self.response.is_none() {
+                // Range types weren't added until Postgres 9.2
+                let query = if self.fallback {
+                    format!("SELECT typname, typelem, NULL::OID \
+                             FROM pg_catalog.pg_type \
+                             WHERE oid = {}", oid)
+                } else {
+                    format!("SELECT t.typname, t.typelem, r.rngsubtype \
+                             FROM pg_catalog.pg_type t \
+                             LEFT OUTER JOIN pg_catalog.pg_range r \
+                                 ON r.rngtypid = t.oid \
+                             WHERE t.oid = {}", oid)
+                };
+                try!(conn.start(&[FrontendMessage::Query { query: &query }]));
+                self.response = Some(Response::new());
+            }
+            try_ready!(conn.poll_done(self.response.as_mut().unwrap(), false));
+            let mut response = self.response.take().unwrap();
+
+            match response.error.take() {
+                Some(ref err) if err.code() == &SqlState::UndefinedTable && !self.fallback => {
+                    self.fallback = true;
+                    continue;
+                }
+                Some(err) => return Err(Error::DbError(err)),
+                None => {}
+            }
+
+            let (name, elem_oid, rngsubtype) = match response.rows.pop() {
+                Some(row) => try!(parse_type_info(row)),
+                None => return Err(Error::IoError(::bad_response())),
+            };
+
+            let kind = if elem_oid != 0 {
+                match conn.known_type(elem_oid) {
+                    Some(ty) => Kind::Array(ty),
+                    None => {
+                        self.stack.push(elem_oid);
+                        continue;
+                    }
+                }
+            } else {
+                match rngsubtype {
+                    Some(oid) => match conn.known_type(oid) {
+                        Some(ty) => Kind::Range(ty),
+                        None => {
+                            self.stack.push(oid);
+                            continue;
+                        }
+                    },
+                    None => Kind::Simple,
+                }
+            };
+
+            let ty = Type::Other(Box::new(Other::new(name, oid, kind)));
+            conn.unknown_types.insert(oid, ty);
+            self.stack.pop();
+        }
+    }
+}
+
+fn parse_type_info(row: Vec<Option<Vec<u8>>>) -> Result<(String, Oid, Option<Oid>)> {
+    let mut row = row.into_iter().map(|v| v.map(|v| String::from_utf8_lossy(&v).into_owned()));
+    let name = match row.next() {
+        Some(Some(name)) => name,
+        _ => return Err(Error::IoError(::bad_response())),
+    };
+    let elem_oid = match row.next() {
+        Some(Some(oid)) => try!(oid.parse().map_err(|_| Error::IoError(::bad_response()))),
+        _ => return Err(Error::IoError(::bad_response())),
+    };
+    let rngsubtype = match row.next() {
+        Some(Some(oid)) => Some(try!(oid.parse().map_err(|_| Error::IoError(::bad_response())))),
+        _ => None,
+    };
+    Ok((name, elem_oid, rngsubtype))
+}
+
+/// An in-progress `Connection::prepare`.
+#[derive(Debug)]
+pub struct Prepare {
+    name: String,
+    response: Response,
+    resolver: Option<ResolveTypes>,
+}
+
+impl Operation for Prepare {
+    type Item = Statement;
+
+    fn poll(&mut self, conn: &mut Connection) -> Result<Async<Statement>> {
+        if self.resolver.is_none() {
+            try_ready!(conn.poll_done(&mut self.response, true));
+            try!(self.response.check());
+            let mut stack: Vec<Oid> = self.response.param_types.iter().cloned().collect();
+            stack.extend(self.response.columns.iter().map(|c| c.type_oid));
+            self.resolver = Some(ResolveTypes {
+                stack: stack,
+                response: None,
+                fallback: false,
+            });
+        }
+        try_ready!(self.resolver.as_mut().unwrap().poll(conn));
+
+        let param_types = self.response.param_types.iter()
+            .map(|&oid| conn.known_type(oid).unwrap())
+            .collect();
+        let columns = self.response.columns.drain(..)
+            .map(|c| {
+                let type_ = conn.known_type(c.type_oid).unwrap();
+                Column::new(c, type_)
+            })
+            .collect();
+        Ok(Async::Ready(Statement {
+            name: mem::replace(&mut self.name, String::new()),
+            param_types: param_types,
+            columns: Arc::new(columns),
+        }))
+    }
+}
+
+/// An in-progress `Statement::execute`.
+#[derive(Debug)]
+pub struct Execute {
+    response: Response,
+}
+
+impl Operation for Execute {
+    type Item = u64;
+
+    fn poll(&mut self, conn: &mut Connection) -> Result<Async<u64>> {
+        try_ready!(conn.poll_done(&mut self.response, true));
+        try!(self.response.check());
+        Ok(Async::Ready(self.response.update_count()))
+    }
+}
+
+/// An in-progress `Statement::query`.
+#[derive(Debug)]
+pub struct Query {
+    columns: Arc<Vec<Column>>,
+    response: Response,
+}
+
+impl Operation for Query {
+    type Item = Vec<Row>;
+
+    fn poll(&mut self, conn: &mut Connection) -> Result<Async<Vec<Row>>> {
+        try_ready!(conn.poll_done(&mut self.response, true));
+        try!(self.response.check());
+        let columns = &self.columns;
+        let rows = self.response.rows.drain(..).map(|data| {
+            Row {
+                columns: columns.clone(),
+                parameters: conn.parameters.clone(),
+                data: data,
+            }
+        }).collect();
+        Ok(Async::Ready(rows))
+    }
+}
+
+/// An in-progress `Connection::batch_execute`.
+#[derive(Debug)]
+pub struct BatchExecute {
+    response: Response,
+}
+
+impl Operation for BatchExecute {
+    type Item = ();
+
+    fn poll(&mut self, conn: &mut Connection) -> Result<Async<()>> {
+        try_ready!(conn.poll_done(&mut self.response, false));
+        try!(self.response.check());
+        Ok(Async::Ready(()))
+    }
+}
+
+/// An in-progress `Statement::close`.
+#[derive(Debug)]
+pub struct Close {
+    response: Response,
+}
+
+impl Operation for Close {
+    type Item = ();
+
+    fn poll(&mut self, conn: &mut Connection) -> Result<Async<()>> {
+        try_ready!(conn.poll_done(&mut self.response, true));
+        try!(self.response.check());
+        Ok(Async::Ready(()))
+    }
+}
+
+/// An in-progress `COPY FROM STDIN` statement.
+///
+/// Data passed to `write` is buffered until the server is ready for it. The
+/// operation completes with the number of rows copied once `finish` has been
+/// called and the server has processed the data.
+#[derive(Debug)]
+pub struct CopyIn {
+    response: Response,
+    pending: Vec<u8>,
+    started: bool,
+    finished: bool,
+}
+
+impl CopyIn {
+    /// Queues a chunk of data to be sent to the server.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if called after `finish` or `fail`.
+    pub fn write(&mut self, data: &[u8]) {
+        assert!(!self.finished, "`write` called on a finished `CopyIn`");
+        let _ = self.pending.write_message(&CopyData { data: data });
+    }
+
+    /// Signals that all data has been written.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if called after `finish` or `fail`.
+    pub fn finish(&mut self) {
+        assert!(!self.finished, "`finish` called on a finished `CopyIn`");
+        let _ = self.pending.write_message(&CopyDone);
+        let _ = self.pending.write_message(&Sync);
+        self.finished = true;
+    }
+
+    /// Aborts the copy, causing the operation to fail with an error
+    /// containing `message`.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if called after `finish` or `fail`.
+    pub fn fail(&mut self, message: &str) {
+        assert!(!self.finished, "`fail` called on a finished `CopyIn`");
+        let _ = self.pending.write_message(&CopyFail { message: message });
+        let _ = self.pending.write_message(&Sync);
+        self.finished = true;
+    }
+}
+
+impl Operation for CopyIn {
+    type Item = u64;
+
+    fn poll(&mut self, conn: &mut Connection) -> Result<Async<u64>> {
+        loop {
+            if self.started && !self.pending.is_empty() {
+                conn.out_buf.extend_from_slice(&self.pending);
+                self.pending.clear();
+            }
+
+            match try_ready!(conn.poll_response(&mut self.response)) {
+                Event::CopyIn => self.started = true,
+                Event::CopyOut | Event::CopyData(_) => {}
+                Event::Done => {
+                    try!(self.response.check());
+                    if !self.started {
+                        return Err(Error::IoError(io::Error::new(
+                                    io::ErrorKind::InvalidInput,
+                                    "called `copy_in` on a non-`COPY FROM STDIN` statement")));
+                    }
+                    return Ok(Async::Ready(self.response.update_count()));
+                }
+            }
+        }
+    }
+}
+
+/// An in-progress `COPY TO STDOUT` statement.
+///
+/// Each call to `poll` returns the next chunk of data, or `None` once all of
+/// the data has been returned.
+#[derive(Debug)]
+pub struct CopyOut {
+    response: Response,
+    started: bool,
+    rows: u64,
+}
+
+impl CopyOut {
+    /// Returns the number of rows copied, once all of the data has been
+    /// returned.
+    pub fn rows(&self) -> u64 {
+        self.rows
+    }
+}
+
+impl Operation for CopyOut {
+    type Item = Option<Vec<u8>>;
+
+    fn poll(&mut self, conn: &mut Connection) -> Result<Async<Option<Vec<u8>>>> {
+        loop {
+            match try_ready!(conn.poll_response(&mut self.response)) {
+                Event::CopyOut => self.started = true,
+                Event::CopyData(data) => return Ok(Async::Ready(Some(data))),
+                Event::CopyIn => {
+                    try!(conn.out_buf.write_message(&CopyFail {
+                        message: "called `copy_out` on a `COPY FROM STDIN` statement",
+                    }));
+                    try!(conn.out_buf.write_message(&Sync));
+                }
+                Event::Done => {
+                    try!(self.response.check());
+                    if !self.started {
+                        return Err(Error::IoError(io::Error::new(
+                                    io::ErrorKind::InvalidInput,
+                                    "called `copy_out` on a non-`COPY TO STDOUT` statement")));
+                    }
+                    self.rows = self.response.update_count();
+                    return Ok(Async::Ready(None));
+                }
+            }
+        }
+    }
+}
+
+/// A transaction on a non-blocking connection.
+///
+/// Unlike `postgres::Transaction`, a `Transaction` cannot finish itself when
+/// dropped; it must be explicitly committed or rolled back. Statements are
+/// executed in the transaction through the `Connection` as usual.
+#[derive(Debug)]
+#[must_use = "transactions must be committed or rolled back"]
+pub struct Transaction {
+    depth: u32,
+}
+
+impl Transaction {
+    /// Determines if the transaction is currently the innermost active
+    /// transaction on `conn`.
+    pub fn is_active(&self, conn: &Connection) -> bool {
+        conn.trans_depth == self.depth
+    }
+
+    /// Begins a nested transaction.
+    ///
+    /// Returns `Error::Misuse` if there is an active nested transaction.
+    pub fn transaction(&self, conn: &mut Connection) -> Result<Begin> {
+        if conn.trans_depth != self.depth {
+            return Err(misuse("`transaction` may only be called on the active transaction"));
+        }
+        Begin::new(conn, "SAVEPOINT sp", self.depth + 1)
+    }
+
+    /// Begins committing the transaction.
+    pub fn commit(self, conn: &mut Connection) -> Result<BatchExecute> {
+        let query = if self.depth == 1 { "COMMIT" } else { "RELEASE sp" };
+        self.finish(conn, query)
+    }
+
+    /// Begins rolling back the transaction.
+    pub fn rollback(self, conn: &mut Connection) -> Result<BatchExecute> {
+        let query = if self.depth == 1 { "ROLLBACK" } else { "ROLLBACK TO sp" };
+        self.finish(conn, query)
+    }
+
+    fn finish(self, conn: &mut Connection, query: &str) -> Result<BatchExecute> {
+        debug_assert!(self.depth == conn.trans_depth);
+        let op = try!(conn.batch_execute(query));
+        conn.trans_depth -= 1;
+        Ok(op)
+    }
+}
+
+/// An in-progress `Connection::transaction` or `Transaction::transaction`.
+#[derive(Debug)]
+pub struct Begin {
+    depth: u32,
+    response: Response,
+}
+
+impl Begin {
+    fn new(conn: &mut Connection, query: &str, depth: u32) -> Result<Begin> {
+        try!(conn.start(&[FrontendMessage::Query { query: query }]));
+        Ok(Begin {
+            depth: depth,
+            response: Response::new(),
+        })
+    }
+}
+
+impl Operation for Begin {
+    type Item = Transaction;
+
+    fn poll(&mut self, conn: &mut Connection) -> Result<Async<Transaction>> {
+        try_ready!(conn.poll_done(&mut self.response, false));
+        try!(self.response.check());
+        conn.trans_depth = self.depth;
+        Ok(Async::Ready(Transaction { depth: self.depth }))
+    }
+}
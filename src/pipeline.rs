@@ -0,0 +1,253 @@
+//! Pipelined statement execution.
+
+use debug_builders::DebugStruct;
+use std::fmt;
+use std::io::Write;
+use std::sync::Arc;
+
+use error::{Error, DbError};
+use message::BackendMessage::*;
+use message::RowDescriptionEntry;
+use rows::Rows;
+use stmt::{Statement, CommandTag};
+use types::ToSql;
+use literal;
+use {bad_response, misuse, CommandTagNew, Connection, DbErrorNew, InnerConnection, PipelineNew,
+     Result, RowsNew};
+use StatementInternals;
+
+// The number of executions written before their responses are read. If
+// nothing were read until every execution had been written, a long pipeline
+// could deadlock once the server blocked writing responses we weren't reading.
+const MAX_IN_FLIGHT: usize = 128;
+
+/// The result of one statement executed by a `Pipeline`.
+#[derive(Debug)]
+pub enum PipelineResult<'conn> {
+    /// The number of rows modified by a statement queued with
+    /// `Pipeline::execute`.
+    Execute(u64),
+    /// The rows returned by a statement queued with `Pipeline::query`.
+    Query(Rows<'conn>),
+}
+
+struct Entry<'a> {
+    stmt: &'a Statement<'a>,
+    params: &'a [&'a ToSql],
+    query: bool,
+}
+
+/// A sequence of statement executions which are sent to the server together.
+///
+/// Executing a statement normally costs a round trip to the server. A
+/// `Pipeline` writes its queued executions in batches of up to 128 and reads
+/// each batch's results before writing the next, so a pipeline costs a
+/// single round trip per batch.
+///
+/// Each execution is its own unit of work, exactly as if the statements had
+/// been executed one after another: an error in one execution does not stop
+/// later executions from running, and its error is reported in that
+/// execution's slot of the results. Outside of a transaction, each execution
+/// commits on its own. Inside of one, an error aborts the transaction as
+/// usual, so later executions will fail until it is rolled back.
+///
+/// `COPY` statements cannot be pipelined. An execution of one fails with an
+/// `Error::Misuse` without being sent.
+///
+/// ## Example
+///
+/// ```rust,no_run
+/// # use postgres::{Connection, SslMode};
+/// # use postgres::pipeline::PipelineResult;
+/// # let conn = Connection::connect("", &SslMode::None).unwrap();
+/// let insert = conn.prepare("INSERT INTO foo (bar) VALUES ($1)").unwrap();
+/// let select = conn.prepare("SELECT COUNT(*) FROM foo").unwrap();
+///
+/// let mut pipeline = conn.pipeline();
+/// pipeline.execute(&insert, &[&1i32])
+///         .execute(&insert, &[&2i32])
+///         .query(&select, &[]);
+/// for result in pipeline.run().unwrap() {
+///     match result {
+///         Ok(PipelineResult::Execute(count)) => println!("{} row(s) inserted", count),
+///         Ok(PipelineResult::Query(rows)) => println!("{} row(s) returned", rows.len()),
+///         Err(err) => println!("error: {}", err),
+///     }
+/// }
+/// ```
+pub struct Pipeline<'a> {
+    conn: &'a Connection,
+    entries: Vec<Entry<'a>>,
+}
+
+impl<'a> fmt::Debug for Pipeline<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "Pipeline")
+            .field("connection", self.conn)
+            .field("len", &self.entries.len())
+            .finish()
+    }
+}
+
+impl<'a> PipelineNew<'a> for Pipeline<'a> {
+    fn new(conn: &'a Connection) -> Pipeline<'a> {
+        Pipeline {
+            conn: conn,
+            entries: vec![],
+        }
+    }
+}
+
+impl<'a> Pipeline<'a> {
+    /// Queues an execution of a statement which will return the number of
+    /// rows modified.
+    pub fn execute(&mut self,
+                   stmt: &'a Statement<'a>,
+                   params: &'a [&'a ToSql])
+                   -> &mut Pipeline<'a> {
+        self.entries.push(Entry {
+            stmt: stmt,
+            params: params,
+            query: false,
+        });
+        self
+    }
+
+    /// Queues an execution of a statement which will return the resulting
+    /// rows.
+    pub fn query(&mut self,
+                 stmt: &'a Statement<'a>,
+                 params: &'a [&'a ToSql])
+                 -> &mut Pipeline<'a> {
+        self.entries.push(Entry {
+            stmt: stmt,
+            params: params,
+            query: true,
+        });
+        self
+    }
+
+    /// Returns the number of queued executions.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Determines if no executions have been queued.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Sends all queued executions to the server and returns their results,
+    /// in the order they were queued.
+    ///
+    /// An execution whose parameters cannot be converted is not sent, and
+    /// its conversion error is returned in its slot. The outer `Result` is
+    /// only an error if communication with the server failed.
+    ///
+    /// An execution whose number of parameters does not match the number
+    /// expected fails with an `Error::Misuse` without being sent, as does an
+    /// execution of a statement prepared on a different connection.
+    pub fn run(self) -> Result<Vec<Result<PipelineResult<'a>>>> {
+        let mut conn = try!(self.conn.lock());
+        check_desync!(conn);
+
+        // Every response must be read before any unknown types in simple
+        // query mode results are looked up.
+        let mut responses = Vec::with_capacity(self.entries.len());
+        for chunk in self.entries.chunks(MAX_IN_FLIGHT) {
+            let mut sent = Vec::with_capacity(chunk.len());
+            for entry in chunk {
+                if entry.stmt.conn() as *const Connection != self.conn as *const Connection {
+                    sent.push(Err(misuse("the statement passed to a `Pipeline` must belong to \
+                                          the `Pipeline`'s connection")));
+                    continue;
+                }
+                match entry.stmt.write_execution(&mut conn, entry.params) {
+                    Ok(()) => sent.push(Ok(())),
+                    Err(err) => {
+                        if conn.is_desynchronized() {
+                            return Err(err);
+                        }
+                        sent.push(Err(err));
+                    }
+                }
+            }
+            try_desync!(conn, conn.stream.flush());
+
+            for sent in sent {
+                responses.push(match sent {
+                    Ok(()) => try!(read_response(&mut conn)),
+                    Err(err) => Err(err),
+                });
+            }
+        }
+
+        let mut results = Vec::with_capacity(self.entries.len());
+        for (entry, response) in self.entries.iter().zip(responses) {
+            let (descriptions, rows, tag) = match response {
+                Ok(response) => response,
+                Err(err) => {
+                    results.push(Err(err));
+                    continue;
+                }
+            };
+            if !entry.query {
+                let count = tag.as_ref().and_then(CommandTag::rows).unwrap_or(0);
+                results.push(Ok(PipelineResult::Execute(count)));
+                continue;
+            }
+
+            let (columns, rows) = match descriptions {
+                Some(descriptions) => {
+                    match literal::decode_text_rows(&mut conn, descriptions, rows) {
+                        Ok((columns, rows)) => (Arc::new(columns), rows),
+                        Err(err) => {
+                            results.push(Err(err));
+                            continue;
+                        }
+                    }
+                }
+                None => (entry.stmt.columns_arc(), rows),
+            };
+            let rows = Rows::new(conn.parameters.clone(), columns, rows, tag);
+            results.push(Ok(PipelineResult::Query(rows)));
+        }
+
+        Ok(results)
+    }
+}
+
+type Response = (Option<Vec<RowDescriptionEntry>>, Vec<Vec<Option<Vec<u8>>>>, Option<CommandTag>);
+
+// Reads the response to one execution. The outer `Result` is only an error if
+// the connection was desynchronized.
+fn read_response(conn: &mut InnerConnection) -> Result<Result<Response>> {
+    let mut descriptions = None;
+    let mut rows = vec![];
+    let mut tag = None;
+    let mut error = None;
+    loop {
+        match try!(conn.read_message()) {
+            ReadyForQuery { .. } => break,
+            BindComplete => {}
+            EmptyQueryResponse => tag = Some(CommandTag::new(String::new())),
+            // Only simple query mode statements describe their rows
+            RowDescription { descriptions: d } => {
+                descriptions = Some(d);
+                rows.clear();
+            }
+            DataRow { row } => rows.push(row),
+            CommandComplete { tag: t } => tag = Some(CommandTag::new(t)),
+            ErrorResponse { fields } => error = Some(fields),
+            _ => {
+                conn.desynchronized = true;
+                return Err(Error::IoError(bad_response()));
+            }
+        }
+    }
+
+    Ok(match error {
+        Some(fields) => DbError::new(fields),
+        None => Ok((descriptions, rows, tag)),
+    })
+}
@@ -118,6 +118,23 @@ fn open_socket(params: &ConnectParams) -> Result<InternalStream, ConnectError> {
     }
 }
 
+/// Opens a socket to the server and switches it to non-blocking mode.
+///
+/// Name resolution and the TCP handshake itself still block.
+pub fn open_nonblocking(params: &ConnectParams) -> Result<Stream, ConnectError> {
+    let socket = try!(open_socket(params));
+    match socket {
+        InternalStream::Tcp(ref s) => try!(s.set_nonblocking(true)),
+        #[cfg(feature = "unix_socket")]
+        InternalStream::Unix(_) => {
+            return Err(ConnectError::IoError(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "non-blocking connections over Unix sockets are not supported")));
+        }
+    }
+    Ok(Stream(socket))
+}
+
 pub fn initialize_stream(params: &ConnectParams, ssl: &SslMode)
                             -> Result<Box<StreamWrapper>, ConnectError> {
     let mut socket = Stream(try!(open_socket(params)));
@@ -1,38 +1,60 @@
 //! Query result rows.
 
+use std::any;
 use std::ascii::AsciiExt;
 use std::fmt;
+use std::io;
 use std::collections::VecDeque;
 use debug_builders::DebugStruct;
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::marker::PhantomData;
 use std::slice;
+use std::sync::Arc;
 use std::vec;
 
-use {Statement,
+use {ConnRef,
+     ConnGuard,
      Column,
+     CommandTag,
      Result,
+     SslMode,
      Transaction,
+     bad_response,
+     cancel_query,
      read_rows,
      DbErrorNew,
      SessionInfoNew,
      RowsNew,
      LazyRowsNew,
-     StatementInternals};
+     RowStreamNew,
+     SimpleQueryResultNew,
+     ValueErrorNew};
 use types::{FromSql, SessionInfo};
-use error::Error;
+use error::{Error, DbError, ValueError, ValueLocation};
+use message::BackendMessage::*;
 use message::FrontendMessage::*;
 
 /// The resulting rows of a query.
 pub struct Rows<'stmt> {
-    stmt: &'stmt Statement<'stmt>,
+    info: Arc<HashMap<String, String>>,
+    columns: Arc<Vec<Column>>,
     data: Vec<Vec<Option<Vec<u8>>>>,
+    tag: Option<CommandTag>,
+    _p: PhantomData<&'stmt ()>,
 }
 
 impl<'a> RowsNew<'a> for Rows<'a> {
-    fn new(stmt: &'a Statement<'a>, data: Vec<Vec<Option<Vec<u8>>>>) -> Rows<'a> {
+    fn new(info: Arc<HashMap<String, String>>,
+           columns: Arc<Vec<Column>>,
+           data: Vec<Vec<Option<Vec<u8>>>>,
+           tag: Option<CommandTag>) -> Rows<'a> {
         Rows {
-            stmt: stmt,
+            info: info,
+            columns: columns,
             data: data,
+            tag: tag,
+            _p: PhantomData,
         }
     }
 }
@@ -42,14 +64,23 @@ impl<'a> fmt::Debug for Rows<'a> {
         DebugStruct::new(fmt, "Rows")
             .field("columns", &self.columns())
             .field("rows", &self.data.len())
+            .field("command_tag", &self.tag)
             .finish()
     }
 }
 
 impl<'stmt> Rows<'stmt> {
     /// Returns a slice describing the columns of the `Rows`.
-    pub fn columns(&self) -> &'stmt [Column] {
-        self.stmt.columns()
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Returns the tag sent by the server when the query completed.
+    ///
+    /// Returns `None` if the query has not completed, which is the case for
+    /// the rows of a `Portal` that was suspended.
+    pub fn command_tag(&self) -> Option<&CommandTag> {
+        self.tag.as_ref()
     }
 
     /// Returns the number of rows present.
@@ -64,7 +95,8 @@ impl<'stmt> Rows<'stmt> {
     /// Panics if `idx` is out of bounds.
     pub fn get<'a>(&'a self, idx: usize) -> Row<'a> {
         Row {
-            stmt: self.stmt,
+            info: self.info.clone(),
+            columns: self.columns.clone(),
             data: Cow::Borrowed(&self.data[idx]),
         }
     }
@@ -72,10 +104,27 @@ impl<'stmt> Rows<'stmt> {
     /// Returns an iterator over the `Row`s.
     pub fn iter<'a>(&'a self) -> Iter<'a> {
         Iter {
-            stmt: self.stmt,
+            info: &self.info,
+            columns: self.columns.clone(),
             iter: self.data.iter()
         }
     }
+
+    /// Converts each `Row` into a value of a type implementing `FromRow`.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, SslMode};
+    /// # let conn = Connection::connect("", &SslMode::None).unwrap();
+    /// let rows = conn.query("SELECT id, name FROM person", &[]).unwrap();
+    /// for (id, name) in rows.into_typed::<(i32, String)>().unwrap() {
+    ///     println!("{}: {}", id, name);
+    /// }
+    /// ```
+    pub fn into_typed<T: FromRow>(self) -> Result<Vec<T>> {
+        self.iter().map(|row| T::from_row(&row)).collect()
+    }
 }
 
 impl<'a> IntoIterator for &'a Rows<'a> {
@@ -93,15 +142,18 @@ impl<'stmt> IntoIterator for Rows<'stmt> {
 
     fn into_iter(self) -> IntoIter<'stmt> {
         IntoIter {
-            stmt: self.stmt,
-            iter: self.data.into_iter()
+            info: self.info,
+            columns: self.columns,
+            iter: self.data.into_iter(),
+            _p: PhantomData,
         }
     }
 }
 
 /// An iterator over `Row`s.
 pub struct Iter<'a> {
-    stmt: &'a Statement<'a>,
+    info: &'a Arc<HashMap<String, String>>,
+    columns: Arc<Vec<Column>>,
     iter: slice::Iter<'a, Vec<Option<Vec<u8>>>>,
 }
 
@@ -111,7 +163,8 @@ impl<'a> Iterator for Iter<'a> {
     fn next(&mut self) -> Option<Row<'a>> {
         self.iter.next().map(|row| {
             Row {
-                stmt: self.stmt,
+                info: self.info.clone(),
+                columns: self.columns.clone(),
                 data: Cow::Borrowed(row),
             }
         })
@@ -126,7 +179,8 @@ impl<'a> DoubleEndedIterator for Iter<'a> {
     fn next_back(&mut self) -> Option<Row<'a>> {
         self.iter.next_back().map(|row| {
             Row {
-                stmt: self.stmt,
+                info: self.info.clone(),
+                columns: self.columns.clone(),
                 data: Cow::Borrowed(row),
             }
         })
@@ -137,8 +191,10 @@ impl<'a> ExactSizeIterator for Iter<'a> {}
 
 /// An owning iterator over `Row`s.
 pub struct IntoIter<'stmt> {
-    stmt: &'stmt Statement<'stmt>,
+    info: Arc<HashMap<String, String>>,
+    columns: Arc<Vec<Column>>,
     iter: vec::IntoIter<Vec<Option<Vec<u8>>>>,
+    _p: PhantomData<&'stmt ()>,
 }
 
 impl<'stmt> Iterator for IntoIter<'stmt> {
@@ -147,7 +203,8 @@ impl<'stmt> Iterator for IntoIter<'stmt> {
     fn next(&mut self) -> Option<Row<'stmt>> {
         self.iter.next().map(|row| {
             Row {
-                stmt: self.stmt,
+                info: self.info.clone(),
+                columns: self.columns.clone(),
                 data: Cow::Owned(row),
             }
         })
@@ -162,7 +219,8 @@ impl<'stmt> DoubleEndedIterator for IntoIter<'stmt> {
     fn next_back(&mut self) -> Option<Row<'stmt>> {
         self.iter.next_back().map(|row| {
             Row {
-                stmt: self.stmt,
+                info: self.info.clone(),
+                columns: self.columns.clone(),
                 data: Cow::Owned(row),
             }
         })
@@ -173,14 +231,15 @@ impl<'stmt> ExactSizeIterator for IntoIter<'stmt> {}
 
 /// A single result row of a query.
 pub struct Row<'a> {
-    stmt: &'a Statement<'a>,
+    info: Arc<HashMap<String, String>>,
+    columns: Arc<Vec<Column>>,
     data: Cow<'a, [Option<Vec<u8>>]>
 }
 
 impl<'a> fmt::Debug for Row<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         DebugStruct::new(fmt, "Row")
-            .field("statement", self.stmt)
+            .field("columns", &self.columns())
             .finish()
     }
 }
@@ -192,8 +251,8 @@ impl<'a> Row<'a> {
     }
 
     /// Returns a slice describing the columns of the `Row`.
-    pub fn columns(&self) -> &'a [Column] {
-        self.stmt.columns()
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
     }
 
     /// Retrieves the contents of a field of the row.
@@ -202,16 +261,26 @@ impl<'a> Row<'a> {
     /// access by index is more efficient. Rows are 0-indexed.
     ///
     /// Returns an `Error` value if the index does not reference a column or
-    /// the return type is not compatible with the Postgres type.
+    /// the return type is not compatible with the Postgres type. Errors
+    /// converting the value are wrapped in an `Error::Value` identifying the
+    /// column and the Postgres and Rust types involved.
     pub fn get_opt<I, T>(&self, idx: I) -> Result<T> where I: RowIndex, T: FromSql {
-        let idx = try!(idx.idx(self.stmt).ok_or(Error::InvalidColumn));
-        let ty = self.stmt.columns()[idx].type_();
-        if !<T as FromSql>::accepts(ty) {
-            return Err(Error::WrongType(ty.clone()));
-        }
-        let conn = self.stmt.conn().conn.borrow();
-        FromSql::from_sql_nullable(ty, self.data[idx].as_ref().map(|e| &**e).as_mut(),
-                                   &SessionInfo::new(&*conn))
+        let idx = try!(idx.idx(&self.columns).ok_or(Error::InvalidColumn));
+        let column = &self.columns[idx];
+        let ty = column.type_();
+        let result = if !<T as FromSql>::accepts(ty) {
+            Err(Error::WrongType(ty.clone()))
+        } else {
+            FromSql::from_sql_nullable(ty, self.data[idx].as_ref().map(|e| &**e).as_mut(),
+                                       &SessionInfo::new(&self.info))
+        };
+        result.map_err(|err| {
+            let location = ValueLocation::Column {
+                index: idx,
+                name: column.name().to_owned(),
+            };
+            ValueError::wrap(location, ty.clone(), any::type_name::<T>(), err)
+        })
     }
 
     /// Retrieves the contents of a field of the row.
@@ -222,7 +291,8 @@ impl<'a> Row<'a> {
     /// ## Panics
     ///
     /// Panics if the index does not reference a column or the return type is
-    /// not compatible with the Postgres type.
+    /// not compatible with the Postgres type. Use `get_opt` to handle these
+    /// errors instead.
     ///
     /// ## Example
     ///
@@ -248,7 +318,7 @@ impl<'a> Row<'a> {
     ///
     /// Panics if the index does not reference a column.
     pub fn get_bytes<I>(&self, idx: I) -> Option<&[u8]> where I: RowIndex + fmt::Debug {
-        match idx.idx(self.stmt) {
+        match idx.idx(&self.columns) {
             Some(idx) => self.data[idx].as_ref().map(|e| &**e),
             None => panic!("invalid index {:?}", idx),
         }
@@ -259,13 +329,13 @@ impl<'a> Row<'a> {
 pub trait RowIndex {
     /// Returns the index of the appropriate column, or `None` if no such
     /// column exists.
-    fn idx(&self, stmt: &Statement) -> Option<usize>;
+    fn idx(&self, columns: &[Column]) -> Option<usize>;
 }
 
 impl RowIndex for usize {
     #[inline]
-    fn idx(&self, stmt: &Statement) -> Option<usize> {
-        if *self >= stmt.columns().len() {
+    fn idx(&self, columns: &[Column]) -> Option<usize> {
+        if *self >= columns.len() {
             None
         } else {
             Some(*self)
@@ -275,21 +345,23 @@ impl RowIndex for usize {
 
 impl<'a> RowIndex for &'a str {
     #[inline]
-    fn idx(&self, stmt: &Statement) -> Option<usize> {
-        if let Some(idx) = stmt.columns().iter().position(|d| d.name() == *self) {
+    fn idx(&self, columns: &[Column]) -> Option<usize> {
+        if let Some(idx) = columns.iter().position(|d| d.name() == *self) {
             return Some(idx);
         };
 
         // FIXME ASCII-only case insensitivity isn't really the right thing to
         // do. Postgres itself uses a dubious wrapper around tolower and JDBC
         // uses the US locale.
-        stmt.columns().iter().position(|d| d.name().eq_ignore_ascii_case(*self))
+        columns.iter().position(|d| d.name().eq_ignore_ascii_case(*self))
     }
 }
 
 /// A lazily-loaded iterator over the resulting rows of a query.
 pub struct LazyRows<'trans, 'stmt> {
-    stmt: &'stmt Statement<'stmt>,
+    conn: ConnRef<'stmt>,
+    info: Arc<HashMap<String, String>>,
+    columns: Arc<Vec<Column>>,
     data: VecDeque<Vec<Option<Vec<u8>>>>,
     name: String,
     row_limit: i32,
@@ -299,7 +371,9 @@ pub struct LazyRows<'trans, 'stmt> {
 }
 
 impl<'trans, 'stmt> LazyRowsNew<'trans, 'stmt> for LazyRows<'trans, 'stmt> {
-    fn new(stmt: &'stmt Statement<'stmt>,
+    fn new(conn: ConnRef<'stmt>,
+           info: Arc<HashMap<String, String>>,
+           columns: Arc<Vec<Column>>,
            data: VecDeque<Vec<Option<Vec<u8>>>>,
            name: String,
            row_limit: i32,
@@ -307,7 +381,9 @@ impl<'trans, 'stmt> LazyRowsNew<'trans, 'stmt> for LazyRows<'trans, 'stmt> {
            finished: bool,
            trans: &'trans Transaction<'trans>) -> LazyRows<'trans, 'stmt> {
         LazyRows {
-            stmt: stmt,
+            conn: conn,
+            info: info,
+            columns: columns,
             data: data,
             name: name,
             row_limit: row_limit,
@@ -321,7 +397,7 @@ impl<'trans, 'stmt> LazyRowsNew<'trans, 'stmt> for LazyRows<'trans, 'stmt> {
 impl<'a, 'b> Drop for LazyRows<'a, 'b> {
     fn drop(&mut self) {
         if !self.finished {
-            let _ = self.finish_inner();
+            let _ = self.finish_inner(true);
         }
     }
 }
@@ -338,14 +414,19 @@ impl<'a, 'b> fmt::Debug for LazyRows<'a, 'b> {
 }
 
 impl<'trans, 'stmt> LazyRows<'trans, 'stmt> {
-    fn finish_inner(&mut self) -> Result<()> {
-        let mut conn = self.stmt.conn().conn.borrow_mut();
+    fn finish_inner(&mut self, defer: bool) -> Result<()> {
+        self.finished = true;
+        if defer {
+            self.conn.defer_close(b'P', self.name.clone());
+            return Ok(());
+        }
+        let mut conn = try!(self.conn.lock());
         check_desync!(conn);
         conn.close_statement(&self.name, b'P')
     }
 
     fn execute(&mut self) -> Result<()> {
-        let mut conn = self.stmt.conn().conn.borrow_mut();
+        let mut conn = try!(self.conn.lock());
 
         try!(conn.write_messages(&[
             Execute {
@@ -353,20 +434,32 @@ impl<'trans, 'stmt> LazyRows<'trans, 'stmt> {
                 max_rows: self.row_limit
             },
             Sync]));
-        read_rows(&mut conn, &mut self.data).map(|more_rows| self.more_rows = more_rows)
+        self.more_rows = try!(read_rows(&mut conn, &mut self.data)).is_none();
+        self.info = conn.parameters.clone();
+        Ok(())
     }
 
     /// Returns a slice describing the columns of the `LazyRows`.
-    pub fn columns(&self) -> &'stmt [Column] {
-        self.stmt.columns()
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Returns an iterator which converts each `Row` into a value of a type
+    /// implementing `FromRow`.
+    pub fn into_typed<T: FromRow>(self) -> TypedLazyRows<'trans, 'stmt, T> {
+        TypedLazyRows {
+            rows: self,
+            _p: PhantomData,
+        }
     }
 
     /// Consumes the `LazyRows`, cleaning up associated state.
     ///
-    /// Functionally identical to the `Drop` implementation on `LazyRows`
-    /// except that it returns any error to the caller.
+    /// Unlike the `Drop` implementation on `LazyRows`, which defers closing
+    /// the portal to the next batch of messages sent on the connection, this
+    /// closes it immediately and returns any error to the caller.
     pub fn finish(mut self) -> Result<()> {
-        self.finish_inner()
+        self.finish_inner(false)
     }
 }
 
@@ -382,7 +475,8 @@ impl<'trans, 'stmt> Iterator for LazyRows<'trans, 'stmt> {
 
         self.data.pop_front().map(|r| {
             Ok(Row {
-                stmt: self.stmt,
+                info: self.info.clone(),
+                columns: self.columns.clone(),
                 data: Cow::Owned(r),
             })
         })
@@ -398,3 +492,305 @@ impl<'trans, 'stmt> Iterator for LazyRows<'trans, 'stmt> {
         (lower, upper)
     }
 }
+
+/// A lazily-loaded iterator over the resulting rows of a query, converted
+/// into values of a type implementing `FromRow`.
+///
+/// Created by `LazyRows::into_typed`.
+pub struct TypedLazyRows<'trans, 'stmt, T> {
+    rows: LazyRows<'trans, 'stmt>,
+    _p: PhantomData<fn() -> T>,
+}
+
+impl<'trans, 'stmt, T> fmt::Debug for TypedLazyRows<'trans, 'stmt, T> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "TypedLazyRows")
+            .field("rows", &self.rows)
+            .finish()
+    }
+}
+
+impl<'trans, 'stmt, T> TypedLazyRows<'trans, 'stmt, T> {
+    /// Returns a slice describing the columns of the rows.
+    pub fn columns(&self) -> &[Column] {
+        self.rows.columns()
+    }
+
+    /// Like `LazyRows::finish`.
+    pub fn finish(self) -> Result<()> {
+        self.rows.finish()
+    }
+}
+
+impl<'trans, 'stmt, T: FromRow> Iterator for TypedLazyRows<'trans, 'stmt, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        self.rows.next().map(|row| row.and_then(|row| T::from_row(&row)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.rows.size_hint()
+    }
+}
+
+/// A type which can be created from a `Row`.
+///
+/// This is implemented for tuples of up to 12 `FromSql` values, which are
+/// read from the columns of the row in order. The `from_row!` macro
+/// implements it for structs, reading each field from the column of the
+/// same name.
+pub trait FromRow: Sized {
+    /// Creates a value from a row.
+    fn from_row(row: &Row) -> Result<Self>;
+}
+
+// Used by `from_row!` to report the field which could not be read.
+#[doc(hidden)]
+pub fn get_field<T: FromSql>(row: &Row, type_name: &str, field: &str) -> Result<T> {
+    match row.get_opt(field) {
+        Err(Error::InvalidColumn) => {
+            Err(Error::Conversion(format!("the row has no column for field `{}::{}`",
+                                          type_name,
+                                          field)
+                                      .into()))
+        }
+        result => result,
+    }
+}
+
+/// Specifies what a `RowStream` does with the rest of its query's results if
+/// it is dropped before they have all been read.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DropBehavior {
+    /// Read and discard the remaining rows.
+    ///
+    /// This is the default.
+    Drain,
+    /// Ask the server to cancel the query, then discard any rows it sent
+    /// before the cancellation took effect.
+    ///
+    /// The cancellation request is sent over a new connection to the server
+    /// without SSL. If it cannot be sent, the remaining rows are drained. A
+    /// request which arrives after the query has finished may cancel a later
+    /// query on the connection.
+    Cancel,
+}
+
+/// An iterator over the resulting rows of a query which reads each row from
+/// the server as it is requested.
+///
+/// Unlike `LazyRows`, a `RowStream` can be used outside of a transaction.
+/// The `Connection` is locked for the lifetime of the `RowStream`, so it
+/// cannot be used for anything else until the stream is finished or
+/// dropped. Any attempt to do so from the thread holding the `RowStream`
+/// will return an `Error::Misuse`, while other threads will block.
+pub struct RowStream<'a> {
+    conn: ConnGuard<'a>,
+    columns: Arc<Vec<Column>>,
+    drop_behavior: DropBehavior,
+    finished: bool,
+}
+
+impl<'a> RowStreamNew<'a> for RowStream<'a> {
+    fn new(conn: ConnGuard<'a>, columns: Arc<Vec<Column>>) -> RowStream<'a> {
+        RowStream {
+            conn: conn,
+            columns: columns,
+            drop_behavior: DropBehavior::Drain,
+            finished: false,
+        }
+    }
+}
+
+impl<'a> Drop for RowStream<'a> {
+    fn drop(&mut self) {
+        let _ = self.finish_inner();
+    }
+}
+
+impl<'a> fmt::Debug for RowStream<'a> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "RowStream")
+            .field("columns", &self.columns())
+            .field("drop_behavior", &self.drop_behavior)
+            .field("finished", &self.finished)
+            .finish()
+    }
+}
+
+impl<'a> RowStream<'a> {
+    /// Returns a slice describing the columns of the `RowStream`.
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Returns the `DropBehavior` of the `RowStream`.
+    pub fn drop_behavior(&self) -> DropBehavior {
+        self.drop_behavior
+    }
+
+    /// Sets what the `RowStream` will do with any unread rows when it is
+    /// dropped or finished.
+    pub fn set_drop_behavior(&mut self, drop_behavior: DropBehavior) {
+        self.drop_behavior = drop_behavior;
+    }
+
+    /// Consumes the `RowStream`, discarding or cancelling any remaining rows
+    /// as specified by its `DropBehavior`.
+    ///
+    /// Functionally equivalent to `RowStream`'s `Drop` implementation,
+    /// except that it returns any error to the caller. An error caused by
+    /// cancelling the query is not returned.
+    pub fn finish(mut self) -> Result<()> {
+        self.finish_inner()
+    }
+
+    fn finish_inner(&mut self) -> Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        check_desync!(self.conn);
+
+        if self.drop_behavior == DropBehavior::Cancel {
+            let _ = cancel_query(self.conn.cancel_params.clone(),
+                                 &SslMode::None,
+                                 self.conn.cancel_data);
+        }
+
+        loop {
+            match try!(self.conn.read_message()) {
+                ReadyForQuery { .. } => return Ok(()),
+                ErrorResponse { fields } => {
+                    // An error is the expected outcome of a cancellation
+                    if self.drop_behavior == DropBehavior::Drain {
+                        try!(self.conn.wait_for_ready());
+                        return DbError::new(fields);
+                    }
+                }
+                CopyInResponse { .. } => {
+                    try!(self.conn.write_messages(&[
+                        CopyFail {
+                            message: "COPY queries cannot be directly executed",
+                        },
+                        Sync]));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn read_row(&mut self) -> Result<Option<Vec<Option<Vec<u8>>>>> {
+        loop {
+            match try!(self.conn.read_message()) {
+                DataRow { row } => return Ok(Some(row)),
+                EmptyQueryResponse | CommandComplete { .. } => {
+                    self.finished = true;
+                    try!(self.conn.wait_for_ready());
+                    return Ok(None);
+                }
+                ErrorResponse { fields } => {
+                    self.finished = true;
+                    try!(self.conn.wait_for_ready());
+                    return DbError::new(fields);
+                }
+                CopyInResponse { .. } => {
+                    try!(self.conn.write_messages(&[
+                        CopyFail {
+                            message: "COPY queries cannot be directly executed",
+                        },
+                        Sync]));
+                }
+                CopyOutResponse { .. } => {
+                    self.finished = true;
+                    loop {
+                        match try!(self.conn.read_message()) {
+                            ReadyForQuery { .. } => break,
+                            _ => {}
+                        }
+                    }
+                    return Err(Error::IoError(io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                "COPY queries cannot be directly executed")));
+                }
+                _ => {
+                    self.finished = true;
+                    self.conn.desynchronized = true;
+                    return Err(Error::IoError(bad_response()));
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for RowStream<'a> {
+    type Item = Result<Row<'a>>;
+
+    fn next(&mut self) -> Option<Result<Row<'a>>> {
+        if self.finished {
+            return None;
+        }
+
+        match self.read_row() {
+            Ok(Some(row)) => {
+                Some(Ok(Row {
+                    info: self.conn.parameters.clone(),
+                    columns: self.columns.clone(),
+                    data: Cow::Owned(row),
+                }))
+            }
+            Ok(None) => None,
+            Err(err) => {
+                self.finished = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// The result of one statement executed by `Connection::simple_query`.
+#[derive(Debug)]
+pub struct SimpleQueryResult {
+    columns: Vec<Column>,
+    rows: Vec<Vec<Option<String>>>,
+    tag: CommandTag,
+}
+
+impl SimpleQueryResultNew for SimpleQueryResult {
+    fn new(columns: Vec<Column>, rows: Vec<Vec<Option<String>>>, tag: CommandTag)
+           -> SimpleQueryResult {
+        SimpleQueryResult {
+            columns: columns,
+            rows: rows,
+            tag: tag,
+        }
+    }
+}
+
+impl SimpleQueryResult {
+    /// Returns a slice describing the columns of the statement's rows.
+    ///
+    /// The slice is empty if the statement does not return rows.
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Returns the rows returned by the statement.
+    ///
+    /// Values are in Postgres's text format, with `None` representing `NULL`.
+    pub fn rows(&self) -> &[Vec<Option<String>>] {
+        &self.rows
+    }
+
+    /// Returns the command tag of the statement, such as `INSERT 0 1` or
+    /// `SELECT 2`.
+    pub fn tag(&self) -> &str {
+        self.tag.as_str()
+    }
+
+    /// Returns the parsed command tag of the statement.
+    pub fn command_tag(&self) -> &CommandTag {
+        &self.tag
+    }
+}
@@ -0,0 +1,263 @@
+//! Lexical scanning of SQL query strings.
+//!
+//! This is not a parser - it only knows enough about Postgres's lexical
+//! structure to find parameter placeholders while skipping over string
+//! literals, quoted identifiers, dollar-quoted strings, comments and casts.
+
+/// A piece of a query string.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Fragment<'a> {
+    /// Literal SQL text to be passed through unchanged.
+    Sql(&'a str),
+    /// A positional `$n` placeholder, holding `n`.
+    Positional(usize),
+    /// A `:name` or `@name` placeholder, holding `name`.
+    Named(&'a str),
+}
+
+/// Splits a query into literal SQL and placeholders.
+///
+/// `backslash_escapes` should be true if the server treats backslashes in
+/// ordinary string literals as escape characters, i.e. if
+/// `standard_conforming_strings` is off.
+///
+/// Named placeholders are only recognized if `named` is true. A sigil is
+/// only treated as the start of a placeholder if it is followed by an
+/// identifier and not preceded by part of one, so `::` casts and array
+/// slices like `a[1:n]` are left alone.
+pub fn fragments(query: &str, backslash_escapes: bool, named: bool) -> Vec<Fragment> {
+    let bytes = query.as_bytes();
+    let mut fragments = vec![];
+    let mut start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\'' => {
+                let escapes = backslash_escapes || is_escape_string_prefix(bytes, i);
+                i = skip_quoted(bytes, i, b'\'', escapes);
+            }
+            b'"' => i = skip_quoted(bytes, i, b'"', false),
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => i = skip_block_comment(bytes, i),
+            b':' if bytes.get(i + 1) == Some(&b':') => i += 2,
+            b':' | b'@' if named
+                           && (i == 0 || !is_ident_byte(bytes[i - 1]))
+                           && bytes.get(i + 1).map_or(false, |&b| is_ident_start_byte(b)) => {
+                let mut end = i + 2;
+                while end < bytes.len() && is_ident_byte(bytes[end]) {
+                    end += 1;
+                }
+                if start < i {
+                    fragments.push(Fragment::Sql(&query[start..i]));
+                }
+                fragments.push(Fragment::Named(&query[i + 1..end]));
+                start = end;
+                i = end;
+            }
+            b'$' if i == 0 || !is_ident_byte(bytes[i - 1]) => {
+                let digits_end = skip_digits(bytes, i + 1);
+                if digits_end > i + 1 {
+                    match query[i + 1..digits_end].parse() {
+                        Ok(idx) if idx > 0 => {
+                            if start < i {
+                                fragments.push(Fragment::Sql(&query[start..i]));
+                            }
+                            fragments.push(Fragment::Positional(idx));
+                            start = digits_end;
+                        }
+                        _ => {}
+                    }
+                    i = digits_end;
+                } else {
+                    i = skip_dollar_quoted(bytes, i);
+                }
+            }
+            _ => i += 1,
+        }
+    }
+
+    if start < bytes.len() {
+        fragments.push(Fragment::Sql(&query[start..]));
+    }
+
+    fragments
+}
+
+/// Rewrites a query's named placeholders to positional ones, returning the
+/// new query and the placeholder names in positional order.
+///
+/// Returns `None` if the query also contains positional placeholders.
+pub fn rewrite_named(query: &str, backslash_escapes: bool) -> Option<(String, Vec<String>)> {
+    let mut names: Vec<String> = vec![];
+    let mut rewritten = String::with_capacity(query.len());
+    for fragment in fragments(query, backslash_escapes, true) {
+        match fragment {
+            Fragment::Sql(sql) => rewritten.push_str(sql),
+            Fragment::Named(name) => {
+                let idx = match names.iter().position(|n| n == name) {
+                    Some(idx) => idx,
+                    None => {
+                        names.push(name.to_owned());
+                        names.len() - 1
+                    }
+                };
+                rewritten.push_str(&format!("${}", idx + 1));
+            }
+            Fragment::Positional(_) => return None,
+        }
+    }
+    Some((rewritten, names))
+}
+
+/// Determines if any of the statements in a query is a `COPY`.
+///
+/// `backslash_escapes` has the same meaning as in `fragments`.
+pub fn contains_copy(query: &str, backslash_escapes: bool) -> bool {
+    let bytes = query.as_bytes();
+    let mut statement_start = true;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                continue;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = skip_block_comment(bytes, i);
+                continue;
+            }
+            b';' => {
+                statement_start = true;
+                i += 1;
+                continue;
+            }
+            b if b.is_ascii_whitespace() => {
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+
+        let first_token = statement_start;
+        statement_start = false;
+        match bytes[i] {
+            b'\'' => {
+                let escapes = backslash_escapes || is_escape_string_prefix(bytes, i);
+                i = skip_quoted(bytes, i, b'\'', escapes);
+            }
+            b'"' => i = skip_quoted(bytes, i, b'"', false),
+            b'$' => i = skip_dollar_quoted(bytes, i),
+            b if is_ident_start_byte(b) => {
+                let mut end = i + 1;
+                while end < bytes.len() && is_ident_byte(bytes[end]) {
+                    end += 1;
+                }
+                if first_token && query[i..end].eq_ignore_ascii_case("copy") {
+                    return true;
+                }
+                i = end;
+            }
+            _ => i += 1,
+        }
+    }
+
+    false
+}
+
+/// Determines if `b` can appear after the first byte of an identifier.
+fn is_ident_byte(b: u8) -> bool {
+    b == b'_' || b == b'$' || b.is_ascii_alphanumeric() || b >= 0x80
+}
+
+fn is_ident_start_byte(b: u8) -> bool {
+    b == b'_' || b.is_ascii_alphabetic() || b >= 0x80
+}
+
+// `E'...'` strings always process backslash escapes.
+fn is_escape_string_prefix(bytes: &[u8], quote: usize) -> bool {
+    quote > 0
+        && (bytes[quote - 1] == b'E' || bytes[quote - 1] == b'e')
+        && (quote == 1 || !is_ident_byte(bytes[quote - 2]))
+}
+
+fn skip_digits(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() && bytes[i].is_ascii_digit() {
+        i += 1;
+    }
+    i
+}
+
+/// Returns the index just past the closing quote of the literal or
+/// identifier beginning at `start`.
+fn skip_quoted(bytes: &[u8], start: usize, quote: u8, backslash_escapes: bool) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        if backslash_escapes && bytes[i] == b'\\' {
+            i += 2;
+        } else if bytes[i] == quote {
+            if bytes.get(i + 1) == Some(&quote) {
+                i += 2;
+            } else {
+                return i + 1;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    bytes.len()
+}
+
+/// Returns the index just past the end of the (possibly nested) block
+/// comment beginning at `start`.
+fn skip_block_comment(bytes: &[u8], start: usize) -> usize {
+    let mut depth = 0;
+    let mut i = start;
+    while i < bytes.len() {
+        if bytes[i] == b'/' && bytes.get(i + 1) == Some(&b'*') {
+            depth += 1;
+            i += 2;
+        } else if bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/') {
+            depth -= 1;
+            i += 2;
+            if depth == 0 {
+                return i;
+            }
+        } else {
+            i += 1;
+        }
+    }
+    bytes.len()
+}
+
+/// Returns the index just past the end of the dollar-quoted string beginning
+/// at `start`, or `start + 1` if the `$` does not begin one.
+fn skip_dollar_quoted(bytes: &[u8], start: usize) -> usize {
+    let mut tag_end = start + 1;
+    if tag_end < bytes.len() && is_ident_start_byte(bytes[tag_end]) {
+        tag_end += 1;
+        while tag_end < bytes.len() && is_ident_byte(bytes[tag_end]) && bytes[tag_end] != b'$' {
+            tag_end += 1;
+        }
+    }
+    if bytes.get(tag_end) != Some(&b'$') {
+        return start + 1;
+    }
+
+    let tag = &bytes[start..tag_end + 1];
+    let mut i = tag_end + 1;
+    while i + tag.len() <= bytes.len() {
+        if &bytes[i..i + tag.len()] == tag {
+            return i + tag.len();
+        }
+        i += 1;
+    }
+    bytes.len()
+}
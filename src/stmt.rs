@@ -1,35 +1,51 @@
 //! Prepared statements
 
 use debug_builders::DebugStruct;
-use std::cell::{Cell, RefMut};
+use std::cell::{Cell, RefCell};
 use std::collections::VecDeque;
 use std::fmt;
-use std::io::{self, Cursor, BufRead, Read};
+use std::io::{self, BufRead, Read, Write};
+use std::result;
+use std::sync::Arc;
 
-use error::{Error, DbError};
-use types::{ReadWithInfo, SessionInfo, Type, ToSql, IsNull};
+use cache::CachedStatement;
+use error::{Error, DbError, BatchError, SqlState, ValueError, ValueLocation};
+use types::{ReadWithInfo, SessionInfo, Type, ToSql, IsNull, Oid, Kind};
 use message::FrontendMessage::*;
 use message::BackendMessage::*;
-use message::WriteMessage;
-use util;
-use rows::{Rows, LazyRows};
-use {read_rows, bad_response, Connection, Transaction, StatementInternals, Result, RowsNew};
-use {InnerConnection, SessionInfoNew, LazyRowsNew, DbErrorNew, ColumnNew};
+use message::RowDescriptionEntry;
+use literal;
+use rows::{Rows, LazyRows, RowStream};
+use sql::{self, Fragment};
+use {read_rows, bad_response, ConnRef, Transaction, TransactionScope, StatementInternals, Result,
+     RowsNew};
+use {Connection, ConnGuard, InnerConnection, SessionInfoNew, LazyRowsNew, DbErrorNew, ColumnNew};
+use ColumnOriginNew;
+use {BatchErrorNew, CommandTagNew, RowStreamNew, ValueErrorNew, misuse};
+use {prepare_unnamed, query_unnamed};
+
+// The number of executions `execute_many` sends before reading their results.
+const EXECUTE_MANY_BATCH_SIZE: usize = 128;
 
 /// A prepared statement.
 pub struct Statement<'conn> {
-    conn: &'conn Connection,
+    conn: ConnRef<'conn>,
     name: String,
+    query: String,
     param_types: Vec<Type>,
-    columns: Vec<Column>,
+    columns: Arc<Vec<Column>>,
     next_portal_id: Cell<u32>,
+    simple: bool,
+    // The cache entry currently backing a statement from `prepare_cached`.
+    // It is replaced if the statement has to be re-prepared.
+    cached: RefCell<Option<CachedStatement>>,
     finished: bool,
 }
 
 impl<'a> fmt::Debug for Statement<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         DebugStruct::new(fmt, "Statement")
-            .field("name", &self.name)
+            .field("name", &self.stmt_name())
             .field("parameter_types", &self.param_types)
             .field("columns", &self.columns)
             .finish()
@@ -38,13 +54,14 @@ impl<'a> fmt::Debug for Statement<'a> {
 
 impl<'conn> Drop for Statement<'conn> {
     fn drop(&mut self) {
-        let _ = self.finish_inner();
+        let _ = self.finish_inner(true);
     }
 }
 
 impl<'conn> StatementInternals<'conn> for Statement<'conn> {
-    fn new(conn: &'conn Connection,
+    fn new(conn: ConnRef<'conn>,
            name: String,
+           query: String,
            param_types: Vec<Type>,
            columns: Vec<Column>,
            next_portal_id: Cell<u32>,
@@ -52,80 +69,387 @@ impl<'conn> StatementInternals<'conn> for Statement<'conn> {
         Statement {
             conn: conn,
             name: name,
+            query: query,
             param_types: param_types,
-            columns: columns,
+            columns: Arc::new(columns),
             next_portal_id: next_portal_id,
+            simple: false,
+            cached: RefCell::new(None),
             finished: finished,
         }
     }
 
-    fn conn(&self) -> &'conn Connection {
-        self.conn
+    fn new_cached(conn: ConnRef<'conn>, query: String, stmt: CachedStatement)
+                  -> Statement<'conn> {
+        Statement {
+            conn: conn,
+            name: stmt.name.clone(),
+            query: query,
+            param_types: stmt.param_types.clone(),
+            columns: stmt.columns.clone(),
+            next_portal_id: Cell::new(0),
+            simple: false,
+            cached: RefCell::new(Some(stmt)),
+            finished: true,
+        }
+    }
+
+    fn new_simple(conn: ConnRef<'conn>, query: String, param_types: Vec<Type>)
+                  -> Statement<'conn> {
+        Statement {
+            conn: conn,
+            name: String::new(),
+            query: query,
+            param_types: param_types,
+            columns: Arc::new(vec![]),
+            next_portal_id: Cell::new(0),
+            simple: true,
+            cached: RefCell::new(None),
+            finished: true,
+        }
+    }
+
+    fn columns_arc(&self) -> Arc<Vec<Column>> {
+        match *self.cached.borrow() {
+            Some(ref stmt) => stmt.columns.clone(),
+            None => self.columns.clone(),
+        }
+    }
+
+    fn conn(&self) -> &Connection {
+        &self.conn
+    }
+
+    fn write_execution(&self, conn: &mut InnerConnection, params: &[&ToSql]) -> Result<()> {
+        // The responses to the executions written after a COPY would be read
+        // as part of it, so it's rejected before anything is sent.
+        let backslash_escapes = literal::backslash_escapes(&SessionInfo::new(&conn.parameters));
+        if sql::contains_copy(&self.query, backslash_escapes) {
+            return Err(misuse("COPY statements cannot be pipelined"));
+        }
+        Statement::write_execution(self, conn, "", 0, params)
+    }
+
+    fn query_locked(&self, conn: &mut InnerConnection, params: &[&ToSql])
+                    -> Result<Rows<'conn>> {
+        check_desync!(conn);
+        if self.simple {
+            return self.simple_query(conn, params);
+        }
+        self.inner_query(conn, "", 0, params).map(|(buf, tag)| {
+            Rows::new(conn.parameters.clone(),
+                      self.columns_arc(),
+                      buf.into_iter().collect(),
+                      tag)
+        })
+    }
+
+    fn execute_locked(&self, conn: &mut InnerConnection, params: &[&ToSql]) -> Result<CommandTag> {
+        check_desync!(conn);
+        if self.simple {
+            return self.simple_execute(conn, params);
+        }
+        try!(self.inner_execute(conn, "", 0, params));
+
+        let tag;
+        loop {
+            match try!(conn.read_message()) {
+                DataRow { .. } => {}
+                ErrorResponse { fields } => {
+                    try!(conn.wait_for_ready());
+                    return DbError::new(fields);
+                }
+                CommandComplete { tag: t } => {
+                    tag = CommandTag::new(t);
+                    break;
+                }
+                EmptyQueryResponse => {
+                    tag = CommandTag::new(String::new());
+                    break;
+                }
+                CopyInResponse { .. } => {
+                    try!(conn.write_messages(&[
+                        CopyFail {
+                            message: "COPY queries cannot be directly executed",
+                        },
+                        Sync]));
+                }
+                CopyOutResponse { .. } => {
+                    loop {
+                        match try!(conn.read_message()) {
+                            BCopyDone => break,
+                            ErrorResponse { fields } => {
+                                try!(conn.wait_for_ready());
+                                return DbError::new(fields);
+                            }
+                            _ => {}
+                        }
+                    }
+                    tag = CommandTag::new(String::new());
+                    break;
+                }
+                _ => {
+                    conn.desynchronized = true;
+                    return Err(Error::IoError(bad_response()));
+                }
+            }
+        }
+        try!(conn.wait_for_ready());
+
+        Ok(tag)
     }
 }
 
 impl<'conn> Statement<'conn> {
-    fn finish_inner(&mut self) -> Result<()> {
+    fn finish_inner(&mut self, defer: bool) -> Result<()> {
         if !self.finished {
             self.finished = true;
-            let mut conn = self.conn.conn.borrow_mut();
+            if defer {
+                self.conn.defer_close(b'S', self.name.clone());
+                return Ok(());
+            }
+            let mut conn = try!(self.conn.lock());
             check_desync!(conn);
             conn.close_statement(&self.name, b'S')
+        } else if self.cached.get_mut().take().is_some() {
+            // This may have been the last user of a statement evicted from
+            // the cache.
+            let mut conn = try!(self.conn.lock());
+            check_desync!(conn);
+            conn.close_evicted_statements();
+            Ok(())
         } else {
             Ok(())
         }
     }
 
-    fn inner_execute(&self, portal_name: &str, row_limit: i32, params: &[&ToSql]) -> Result<()> {
-        let mut conn = self.conn.conn.borrow_mut();
-        assert!(self.param_types().len() == params.len(),
-                "expected {} parameters but got {}",
-                self.param_types.len(),
-                params.len());
-        debug!("executing statement {} with parameters: {:?}", self.name, params);
+    // The name of the server-side statement, which changes if a cached
+    // statement is re-prepared.
+    fn stmt_name(&self) -> String {
+        match *self.cached.borrow() {
+            Some(ref stmt) => stmt.name.clone(),
+            None => self.name.clone(),
+        }
+    }
+
+    // Writes the messages executing the statement without flushing them or
+    // waiting for a response.
+    fn write_execution(&self,
+                       conn: &mut InnerConnection,
+                       portal_name: &str,
+                       row_limit: i32,
+                       params: &[&ToSql])
+                       -> Result<()> {
+        if self.simple {
+            let query = try!(self.render_simple(conn, params));
+            debug!("executing query: {}", query);
+            try!(conn.write_message(&Query { query: &query }));
+            return Ok(());
+        }
+        let name = self.stmt_name();
+        debug!("executing statement {} with parameters: {:?}", name, params);
+        let values = try!(self.encode_params(conn, params));
+
+        for message in &[
+                Bind {
+                    portal: portal_name,
+                    statement: &name,
+                    formats: &[1],
+                    values: &values,
+                    result_formats: &[1]
+                },
+                Execute {
+                    portal: portal_name,
+                    max_rows: row_limit
+                },
+                Sync] {
+            try!(conn.write_message(message));
+        }
+        Ok(())
+    }
+
+    fn encode_params(&self, conn: &InnerConnection, params: &[&ToSql])
+                     -> Result<Vec<Option<Vec<u8>>>> {
+        if self.param_types.len() != params.len() {
+            return Err(param_count_error(self.param_types.len(), params.len()));
+        }
         let mut values = vec![];
-        for (param, ty) in params.iter().zip(self.param_types.iter()) {
+        for (i, (param, ty)) in params.iter().zip(self.param_types.iter()).enumerate() {
             let mut buf = vec![];
-            match try!(param.to_sql_checked(ty, &mut buf, &SessionInfo::new(&*conn))) {
+            let is_null = try!(param.to_sql_checked(ty, &mut buf, &SessionInfo::new(&conn.parameters))
+                                    .map_err(|err| param_error(i, ty.clone(), *param, err)));
+            match is_null {
                 IsNull::Yes => values.push(None),
                 IsNull::No => values.push(Some(buf)),
             }
+        }
+        Ok(values)
+    }
+
+    fn inner_execute(&self,
+                     conn: &mut InnerConnection,
+                     portal_name: &str,
+                     row_limit: i32,
+                     params: &[&ToSql])
+                     -> Result<()> {
+        try!(self.write_execution(conn, portal_name, row_limit, params));
+        try_desync!(conn, conn.stream.flush());
+        if self.simple {
+            return Ok(());
+        }
+
+        let res = read_bind_result(conn);
+        let stale = match res {
+            Err(Error::DbError(ref err)) => is_stale_plan(err),
+            _ => false,
         };
+        if !stale || self.cached.borrow().is_none() {
+            return res;
+        }
 
-        try!(conn.write_messages(&[
-            Bind {
-                portal: portal_name,
-                statement: &self.name,
-                formats: &[1],
-                values: &values,
-                result_formats: &[1]
-            },
-            Execute {
-                portal: portal_name,
-                max_rows: row_limit
-            },
-            Sync]));
+        // The retry would fail as well inside of a failed transaction, so the
+        // stale entry is just dropped from the cache.
+        let name = self.stmt_name();
+        if conn.transaction_status != b'I' {
+            conn.statement_cache.invalidate(&name);
+            return res;
+        }
 
-        match try!(conn.read_message()) {
-            BindComplete => Ok(()),
-            ErrorResponse { fields } => {
-                try!(conn.wait_for_ready());
-                DbError::new(fields)
+        debug!("re-preparing invalidated statement {}", name);
+        let stmt = try!(conn.reprepare_cached(&name, &self.query, &self.param_types));
+        *self.cached.borrow_mut() = Some(stmt);
+
+        try!(self.write_execution(conn, portal_name, row_limit, params));
+        try_desync!(conn, conn.stream.flush());
+        read_bind_result(conn)
+    }
+
+    fn inner_query(&self,
+                   conn: &mut InnerConnection,
+                   portal_name: &str,
+                   row_limit: i32,
+                   params: &[&ToSql])
+                   -> Result<(VecDeque<Vec<Option<Vec<u8>>>>, Option<CommandTag>)> {
+        try!(self.inner_execute(conn, portal_name, row_limit, params));
+
+        let mut buf = VecDeque::new();
+        let tag = try!(read_rows(conn, &mut buf));
+        Ok((buf, tag))
+    }
+
+    // Interpolates the parameters into the query as literals for execution
+    // via the simple query protocol.
+    fn render_simple(&self, conn: &InnerConnection, params: &[&ToSql]) -> Result<String> {
+        let info = SessionInfo::new(&conn.parameters);
+        try!(literal::check_encoding(&info));
+        let fragments = sql::fragments(&self.query, literal::backslash_escapes(&info), false);
+
+        let expected = fragments.iter()
+                                .filter_map(|f| match *f {
+                                    Fragment::Positional(idx) => Some(idx),
+                                    Fragment::Sql(_) | Fragment::Named(_) => None,
+                                })
+                                .max()
+                                .unwrap_or(0);
+        if expected != params.len() {
+            return Err(param_count_error(expected, params.len()));
+        }
+        debug!("executing simple statement with parameters: {:?}", params);
+
+        let mut literals: Vec<Option<String>> = vec![None; params.len()];
+        let mut query = String::with_capacity(self.query.len());
+        for fragment in fragments {
+            match fragment {
+                Fragment::Sql(sql) | Fragment::Named(sql) => query.push_str(sql),
+                Fragment::Positional(idx) => {
+                    if literals[idx - 1].is_none() {
+                        let param = params[idx - 1];
+                        let ty = self.param_types.get(idx - 1);
+                        let literal = try!(literal::render_param(param, ty, &info).map_err(|err| {
+                            let ty = ty.cloned().unwrap_or(Type::Unknown);
+                            param_error(idx - 1, ty, param, err)
+                        }));
+                        literals[idx - 1] = Some(literal);
+                    }
+                    query.push_str(literals[idx - 1].as_ref().unwrap());
+                }
             }
-            _ => {
-                conn.desynchronized = true;
-                Err(Error::IoError(bad_response()))
+        }
+        Ok(query)
+    }
+
+    fn simple_execute(&self, conn: &mut InnerConnection, params: &[&ToSql])
+                      -> Result<CommandTag> {
+        try!(self.inner_execute(conn, "", 0, params));
+
+        let mut tag = CommandTag::new(String::new());
+        let mut error = None;
+        loop {
+            match try!(conn.read_message()) {
+                ReadyForQuery { .. } => break,
+                RowDescription { .. } | DataRow { .. } => {}
+                CommandComplete { tag: t } => tag = CommandTag::new(t),
+                EmptyQueryResponse => tag = CommandTag::new(String::new()),
+                ErrorResponse { fields } => error = Some(fields),
+                CopyInResponse { .. } => {
+                    try!(conn.write_messages(&[
+                        CopyFail {
+                            message: "COPY queries cannot be directly executed",
+                        }]));
+                }
+                CopyOutResponse { .. } | BCopyData { .. } | BCopyDone => {}
+                _ => {
+                    conn.desynchronized = true;
+                    return Err(Error::IoError(bad_response()));
+                }
             }
         }
+
+        match error {
+            Some(fields) => DbError::new(fields),
+            None => Ok(tag),
+        }
     }
 
-    fn inner_query<'a>(&'a self, portal_name: &str, row_limit: i32, params: &[&ToSql])
-                       -> Result<(VecDeque<Vec<Option<Vec<u8>>>>, bool)> {
-        try!(self.inner_execute(portal_name, row_limit, params));
+    fn simple_query(&self, conn: &mut InnerConnection, params: &[&ToSql])
+                    -> Result<Rows<'conn>> {
+        try!(self.inner_execute(conn, "", 0, params));
 
-        let mut buf = VecDeque::new();
-        let more_rows = try!(read_rows(&mut self.conn.conn.borrow_mut(), &mut buf));
-        Ok((buf, more_rows))
+        let mut descriptions = vec![];
+        let mut rows = vec![];
+        let mut tag = None;
+        let mut error = None;
+        loop {
+            match try!(conn.read_message()) {
+                ReadyForQuery { .. } => break,
+                // Only the results of the last statement are returned
+                RowDescription { descriptions: d } => {
+                    descriptions = d;
+                    rows.clear();
+                }
+                DataRow { row } => rows.push(row),
+                CommandComplete { tag: t } => tag = Some(CommandTag::new(t)),
+                EmptyQueryResponse => tag = Some(CommandTag::new(String::new())),
+                ErrorResponse { fields } => error = Some(fields),
+                CopyInResponse { .. } => {
+                    try!(conn.write_messages(&[
+                        CopyFail {
+                            message: "COPY queries cannot be directly executed",
+                        }]));
+                }
+                CopyOutResponse { .. } | BCopyData { .. } | BCopyDone => {}
+                _ => {
+                    conn.desynchronized = true;
+                    return Err(Error::IoError(bad_response()));
+                }
+            }
+        }
+        if let Some(fields) = error {
+            return DbError::new(fields);
+        }
+
+        let (columns, data) = try!(literal::decode_text_rows(conn, descriptions, rows));
+        Ok(Rows::new(conn.parameters.clone(), Arc::new(columns), data, tag))
     }
 
     /// Returns a slice containing the expected parameter types.
@@ -134,18 +458,56 @@ impl<'conn> Statement<'conn> {
     }
 
     /// Returns a slice describing the columns of the result of the query.
+    ///
+    /// These describe the statement as it was originally prepared. If a
+    /// cached statement is later prepared again, its new columns are returned
+    /// by `current_columns` instead.
     pub fn columns(&self) -> &[Column] {
         &self.columns
     }
 
+    /// Returns the columns of the result of the query as the statement is
+    /// currently prepared.
+    ///
+    /// These differ from `columns` only if the statement came from
+    /// `prepare_cached` and was prepared again because its result type
+    /// changed.
+    pub fn current_columns(&self) -> Vec<Column> {
+        (*self.columns_arc()).clone()
+    }
+
+    /// Looks up the table column each of the statement's result columns come
+    /// from.
+    ///
+    /// The returned vector contains an entry for each column, which is `None`
+    /// if the column's values are not taken directly from a table column.
+    /// The lookup queries the system catalogs, and its results are cached
+    /// for the lifetime of the connection.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, SslMode};
+    /// # let conn = Connection::connect("", &SslMode::None).unwrap();
+    /// let stmt = conn.prepare("SELECT id, name FROM users").unwrap();
+    /// for (column, origin) in stmt.columns().iter().zip(stmt.column_origins().unwrap()) {
+    ///     if let Some(origin) = origin {
+    ///         println!("{} comes from {}.{}", column.name(), origin.table(), origin.column());
+    ///     }
+    /// }
+    /// ```
+    pub fn column_origins(&self) -> Result<Vec<Option<ColumnOrigin>>> {
+        let mut conn = try!(self.conn.lock());
+        let columns = self.columns_arc();
+        conn.get_column_origins(&columns)
+    }
+
     /// Executes the prepared statement, returning the number of rows modified.
     ///
     /// If the statement does not modify any rows (e.g. SELECT), 0 is returned.
     ///
-    /// ## Panics
-    ///
-    /// Panics if the number of parameters provided does not match the number
-    /// expected.
+    /// Returns `Error::Misuse` if the number of parameters provided does not
+    /// match the number expected.
     ///
     /// ## Example
     ///
@@ -161,64 +523,220 @@ impl<'conn> Statement<'conn> {
     /// }
     /// ```
     pub fn execute(&self, params: &[&ToSql]) -> Result<u64> {
-        check_desync!(self.conn);
-        try!(self.inner_execute("", 0, params));
+        self.execute_tagged(params).map(|tag| tag.rows().unwrap_or(0))
+    }
+
+    /// Like `execute`, except that it returns the command tag sent by the
+    /// server.
+    ///
+    /// The tag distinguishes between kinds of commands and between commands
+    /// which affected no rows and those which do not report a row count.
+    ///
+    /// Returns `Error::Misuse` if the number of parameters provided does not
+    /// match the number expected.
+    pub fn execute_tagged(&self, params: &[&ToSql]) -> Result<CommandTag> {
+        let mut conn = try!(self.conn.lock());
+        self.execute_locked(&mut conn, params)
+    }
+
+    /// Executes the prepared statement once for each set of parameters,
+    /// returning the number of rows modified by each execution.
+    ///
+    /// The executions are sent to the server in batches without waiting for
+    /// the result of each one, so a round trip is paid once per batch rather
+    /// than once per execution.
+    ///
+    /// The executions all run in a single implicit transaction, so if one
+    /// fails none of them take effect. If a transaction is already active, it
+    /// is aborted as usual. Parameter sets are converted a batch at a time as
+    /// they are sent, so a parameter set which can't be converted also rolls
+    /// back the batches before it, and aborts an active transaction if any
+    /// were sent. The returned `BatchError` contains the index of the
+    /// parameter set which failed.
+    ///
+    /// `COPY` statements cannot be executed this way.
+    ///
+    /// A parameter set of the wrong length is reported as an `Error::Misuse`
+    /// for its index.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, SslMode};
+    /// # use postgres::types::ToSql;
+    /// # let conn = Connection::connect("", &SslMode::None).unwrap();
+    /// let stmt = conn.prepare("INSERT INTO foo (bar) VALUES ($1)").unwrap();
+    /// let values = (0..10_000).collect::<Vec<i32>>();
+    /// let params = values.iter().map(|v| vec![v as &ToSql]);
+    /// match stmt.execute_many(params) {
+    ///     Ok(counts) => println!("{} row(s) inserted", counts.len()),
+    ///     Err(err) => println!("row {} failed: {}", err.index(), err.error()),
+    /// }
+    /// ```
+    pub fn execute_many<'a, I, P>(&self, params: I) -> result::Result<Vec<u64>, BatchError>
+            where I: IntoIterator<Item = P>, P: AsRef<[&'a ToSql]> {
+        let mut conn = match self.conn.lock() {
+            Ok(conn) => conn,
+            Err(err) => return Err(BatchError::new(0, err)),
+        };
+        if conn.is_desynchronized() {
+            return Err(BatchError::new(0, Error::IoError(::desynchronized())));
+        }
+
+        let result = if self.simple {
+            self.simple_execute_many(&mut conn, params)
+        } else {
+            self.extended_execute_many(&mut conn, params)
+        };
+        result.map_err(|(index, err)| BatchError::new(index, err))
+    }
 
-        let mut conn = self.conn.conn.borrow_mut();
-        let num;
+    fn extended_execute_many<'a, I, P>(&self, conn: &mut InnerConnection, params: I)
+                                       -> result::Result<Vec<u64>, (usize, Error)>
+            where I: IntoIterator<Item = P>, P: AsRef<[&'a ToSql]> {
+        let name = self.stmt_name();
+        debug!("executing statement {} with many parameter sets", name);
+
+        let mut params = params.into_iter();
+        let mut counts = vec![];
+        // Each batch is converted just before it is sent so that memory use is
+        // bounded by the batch size. The server's responses to a batch must fit
+        // in the socket buffers, since they aren't read until the whole batch
+        // has been written.
         loop {
-            match try!(conn.read_message()) {
-                DataRow { .. } => {}
-                ErrorResponse { fields } => {
-                    try!(conn.wait_for_ready());
-                    return DbError::new(fields);
+            let base = counts.len();
+            let mut batch = Vec::with_capacity(EXECUTE_MANY_BATCH_SIZE);
+            for (i, params) in params.by_ref().take(EXECUTE_MANY_BATCH_SIZE).enumerate() {
+                match self.encode_params(conn, params.as_ref()) {
+                    Ok(values) => batch.push(values),
+                    Err(err) => {
+                        if base > 0 {
+                            if let Err(err) = abort_execute_many(conn) {
+                                return Err((base, err));
+                            }
+                        }
+                        return Err((base + i, err));
+                    }
                 }
-                CommandComplete { tag } => {
-                    num = util::parse_update_count(tag);
-                    break;
+            }
+            if batch.is_empty() {
+                break;
+            }
+
+            for values in &batch {
+                for message in &[
+                        Bind {
+                            portal: "",
+                            statement: &name,
+                            formats: &[1],
+                            values: values,
+                            result_formats: &[1]
+                        },
+                        Execute {
+                            portal: "",
+                            max_rows: 0
+                        }] {
+                    if let Err(err) = conn.write_message(message) {
+                        return Err((base, Error::IoError(err)));
+                    }
                 }
-                EmptyQueryResponse => {
-                    num = 0;
-                    break;
+            }
+            if let Err(err) = conn.write_messages(&[Flush]) {
+                return Err((base, Error::IoError(err)));
+            }
+
+            for i in 0..batch.len() {
+                match read_execute_many_result(conn) {
+                    Ok(Ok(count)) => counts.push(count),
+                    Ok(Err(fields)) => {
+                        // The server skips everything up to the next Sync
+                        let err = conn.write_messages(&[Sync])
+                                      .map_err(Error::IoError)
+                                      .and_then(|_| conn.wait_for_ready())
+                                      .and_then(|_| DbError::new::<()>(fields));
+                        return Err((base + i, err.unwrap_err()));
+                    }
+                    Err(err) => return Err((base + i, err)),
                 }
-                CopyInResponse { .. } => {
-                    try!(conn.write_messages(&[
-                        CopyFail {
-                            message: "COPY queries cannot be directly executed",
-                        },
-                        Sync]));
+            }
+        }
+
+        if counts.is_empty() {
+            return Ok(counts);
+        }
+        let result = conn.write_messages(&[Sync])
+                         .map_err(Error::IoError)
+                         .and_then(|_| conn.wait_for_ready());
+        match result {
+            Ok(()) => Ok(counts),
+            Err(err) => Err((counts.len(), err)),
+        }
+    }
+
+    // Runs every execution in one query so that they share an implicit
+    // transaction, as they do in the extended protocol.
+    fn simple_execute_many<'a, I, P>(&self, conn: &mut InnerConnection, params: I)
+                                     -> result::Result<Vec<u64>, (usize, Error)>
+            where I: IntoIterator<Item = P>, P: AsRef<[&'a ToSql]> {
+        let mut query = String::new();
+        let mut len = 0;
+        for (i, params) in params.into_iter().enumerate() {
+            match self.render_simple(conn, params.as_ref()) {
+                Ok(rendered) => {
+                    query.push_str(&rendered);
+                    query.push_str(";\n");
                 }
-                CopyOutResponse { .. } => {
-                    loop {
-                        match try!(conn.read_message()) {
-                            BCopyDone => break,
-                            ErrorResponse { fields } => {
-                                try!(conn.wait_for_ready());
-                                return DbError::new(fields);
-                            }
-                            _ => {}
-                        }
+                Err(err) => return Err((i, err)),
+            }
+            len += 1;
+        }
+        if len == 0 {
+            return Ok(vec![]);
+        }
+        debug!("executing query: {}", query);
+        if let Err(err) = conn.write_messages(&[Query { query: &query }]) {
+            return Err((0, Error::IoError(err)));
+        }
+
+        let mut counts = Vec::with_capacity(len);
+        let mut error = None;
+        loop {
+            let message = match conn.read_message() {
+                Ok(message) => message,
+                Err(err) => return Err((counts.len(), Error::IoError(err))),
+            };
+            match message {
+                ReadyForQuery { .. } => break,
+                RowDescription { .. } | DataRow { .. } => {}
+                CommandComplete { tag } => counts.push(CommandTag::new(tag).rows().unwrap_or(0)),
+                EmptyQueryResponse => counts.push(0),
+                ErrorResponse { fields } => error = Some((counts.len(), fields)),
+                CopyInResponse { .. } => {
+                    if let Err(err) = conn.write_messages(&[
+                            CopyFail {
+                                message: "COPY statements cannot be executed with `execute_many`",
+                            }]) {
+                        return Err((counts.len(), Error::IoError(err)));
                     }
-                    num = 0;
-                    break;
                 }
+                CopyOutResponse { .. } | BCopyData { .. } | BCopyDone => {}
                 _ => {
                     conn.desynchronized = true;
-                    return Err(Error::IoError(bad_response()));
+                    return Err((counts.len(), Error::IoError(bad_response())));
                 }
             }
         }
-        try!(conn.wait_for_ready());
 
-        Ok(num)
+        match error {
+            Some((index, fields)) => Err((index, DbError::new::<()>(fields).unwrap_err())),
+            None => Ok(counts),
+        }
     }
 
     /// Executes the prepared statement, returning the resulting rows.
     ///
-    /// ## Panics
-    ///
-    /// Panics if the number of parameters provided does not match the number
-    /// expected.
+    /// Returns `Error::Misuse` if the number of parameters provided does not
+    /// match the number expected.
     ///
     /// ## Example
     ///
@@ -236,13 +754,169 @@ impl<'conn> Statement<'conn> {
     ///     println!("foo: {}", foo);
     /// }
     /// ```
-    pub fn query<'a>(&'a self, params: &[&ToSql]) -> Result<Rows<'a>> {
-        check_desync!(self.conn);
-        self.inner_query("", 0, params).map(|(buf, _)| {
-            Rows::new(self, buf.into_iter().collect())
+    pub fn query(&self, params: &[&ToSql]) -> Result<Rows<'conn>> {
+        let mut conn = try!(self.conn.lock());
+        self.query_locked(&mut conn, params)
+    }
+
+    /// Binds parameters to the prepared statement, creating a `Portal` which
+    /// can be executed to fetch the resulting rows in batches.
+    ///
+    /// Portals only exist for the duration of the transaction in which they
+    /// were created, so this can only be called inside of a transaction.
+    ///
+    /// Portals are not supported in simple query mode, and will return an
+    /// error.
+    ///
+    /// Returns `Error::Misuse` if the number of parameters provided does not
+    /// match the number expected.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, SslMode};
+    /// # let conn = Connection::connect("", &SslMode::None).unwrap();
+    /// let trans = conn.transaction().unwrap();
+    /// let stmt = trans.prepare("SELECT id FROM big_table").unwrap();
+    /// let portal = stmt.bind(&[]).unwrap();
+    /// loop {
+    ///     let (rows, suspended) = portal.execute(100).unwrap();
+    ///     for row in &rows {
+    ///         let id: i32 = row.get(0);
+    ///         println!("{}", id);
+    ///     }
+    ///     if !suspended {
+    ///         break;
+    ///     }
+    /// }
+    /// ```
+    pub fn bind<'a>(&'a self, params: &[&ToSql]) -> Result<Portal<'a>> {
+        let mut conn = try!(self.conn.lock());
+        check_desync!(conn);
+        if self.simple {
+            return Err(misuse("portals are not supported in simple query mode"));
+        }
+        if conn.trans_depth == 0 {
+            return Err(misuse("portals can only be created inside of a transaction"));
+        }
+
+        let values = try!(self.encode_params(&conn, params));
+        let id = self.next_portal_id.get();
+        self.next_portal_id.set(id + 1);
+        let portal_name = format!("{}p{}", self.name, id);
+        let name = self.stmt_name();
+        debug!("binding statement {} to portal {} with parameters: {:?}",
+               name,
+               portal_name,
+               params);
+
+        try!(conn.write_messages(&[
+            Bind {
+                portal: &portal_name,
+                statement: &name,
+                formats: &[1],
+                values: &values,
+                result_formats: &[1]
+            },
+            Sync]));
+        let resp = match try!(conn.read_message()) {
+            BindComplete => Ok(()),
+            ErrorResponse { fields } => DbError::new(fields),
+            _ => bad_response!(conn),
+        };
+        try!(conn.wait_for_ready());
+        try!(resp);
+
+        Ok(Portal {
+            conn: ConnRef::Borrowed(&self.conn),
+            name: portal_name,
+            columns: self.columns_arc(),
+            finished: false,
         })
     }
 
+    /// Declares a SQL cursor over the results of the prepared statement.
+    ///
+    /// Unlike a `Portal`, a cursor declared with `CursorOptions::hold` set
+    /// can outlive the transaction in which it was declared. A cursor
+    /// declared without it can only be declared inside of a transaction.
+    ///
+    /// Returns `Error::Misuse` if the number of parameters provided does not
+    /// match the number expected.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, SslMode};
+    /// # use postgres::stmt::CursorOptions;
+    /// # let conn = Connection::connect("", &SslMode::None).unwrap();
+    /// let stmt = conn.prepare("SELECT id FROM big_table ORDER BY id").unwrap();
+    /// let options = CursorOptions { scroll: true, hold: true };
+    /// let cursor = stmt.declare_cursor(&[], options).unwrap();
+    /// let page = cursor.fetch(50).unwrap();
+    /// cursor.move_absolute(100).unwrap();
+    /// let later_page = cursor.fetch(50).unwrap();
+    /// # let _ = (page, later_page);
+    /// ```
+    pub fn declare_cursor(&self, params: &[&ToSql], options: CursorOptions)
+                          -> Result<Cursor<'conn>> {
+        let mut conn = try!(self.conn.lock());
+        check_desync!(conn);
+        let name = format!("c{}", conn.next_stmt_id);
+        conn.next_stmt_id += 1;
+
+        let query = format!("DECLARE {} {}CURSOR {}FOR {}",
+                            name,
+                            if options.scroll { "SCROLL " } else { "NO SCROLL " },
+                            if options.hold { "WITH HOLD " } else { "" },
+                            self.query);
+        let stmt = try!(prepare_unnamed(&mut conn, self.conn.clone(), &query, &self.param_types));
+        try!(stmt.execute_locked(&mut conn, params));
+
+        Ok(Cursor {
+            conn: self.conn.clone(),
+            name: name,
+            scope: if options.hold { None } else { Some(conn.transaction_scope()) },
+            finished: false,
+        })
+    }
+
+    /// Executes the prepared statement, returning an iterator which reads
+    /// the resulting rows from the server as they are requested.
+    ///
+    /// Unlike `lazy_query`, no transaction is required and rows are not
+    /// fetched in batches. The `Connection` cannot be used for anything else
+    /// until the returned `RowStream` is finished or dropped. See
+    /// `DropBehavior` for what happens to unread rows.
+    ///
+    /// Streaming is not supported in simple query mode, and will return an
+    /// error.
+    ///
+    /// Returns `Error::Misuse` if the number of parameters provided does not
+    /// match the number expected.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,no_run
+    /// # use postgres::{Connection, SslMode};
+    /// # let conn = Connection::connect("", &SslMode::None).unwrap();
+    /// let stmt = conn.prepare("SELECT id FROM big_table").unwrap();
+    /// for row in stmt.stream(&[]).unwrap() {
+    ///     let id: i32 = row.unwrap().get(0);
+    ///     println!("{}", id);
+    /// }
+    /// ```
+    pub fn stream<'a>(&'a self, params: &[&ToSql]) -> Result<RowStream<'a>> {
+        let mut conn = try!(self.conn.lock());
+        check_desync!(conn);
+        if self.simple {
+            return Err(misuse("`stream` is not supported in simple query mode"));
+        }
+
+        try!(self.inner_execute(&mut conn, "", 0, params));
+        Ok(RowStream::new(conn, self.columns_arc()))
+    }
+
     /// Executes the prepared statement, returning a lazily loaded iterator
     /// over the resulting rows.
     ///
@@ -255,33 +929,45 @@ impl<'conn> Statement<'conn> {
     /// object representing the active transaction must be passed to
     /// `lazy_query`.
     ///
-    /// ## Panics
-    ///
-    /// Panics if the provided `Transaction` is not associated with the same
-    /// `Connection` as this `Statement`, if the `Transaction` is not
-    /// active, or if the number of parameters provided does not match the
-    /// number of parameters expected.
+    /// Returns `Error::Misuse` if the provided `Transaction` is not
+    /// associated with the same `Connection` as this `Statement`, if the
+    /// `Transaction` is not active, if the number of parameters provided does
+    /// not match the number of parameters expected, or if the statement was
+    /// prepared in simple query mode.
     pub fn lazy_query<'trans, 'stmt>(&'stmt self,
                                      trans: &'trans Transaction,
                                      params: &[&ToSql],
                                      row_limit: i32)
                                      -> Result<LazyRows<'trans, 'stmt>> {
-        assert!(self.conn as *const _ == trans.conn as *const _,
-                "the `Transaction` passed to `lazy_query` must be associated with the same \
-                 `Connection` as the `Statement`");
-        let conn = self.conn.conn.borrow();
+        if &*self.conn as *const Connection != trans.connection() as *const Connection {
+            return Err(misuse("the `Transaction` passed to `lazy_query` must be associated with \
+                               the same `Connection` as the `Statement`"));
+        }
+        let mut conn = try!(self.conn.lock());
         check_desync!(conn);
-        assert!(conn.trans_depth == trans.depth,
-                "`lazy_query` must be passed the active transaction");
-        drop(conn);
+        if conn.trans_depth != trans.depth {
+            return Err(misuse("`lazy_query` must be passed the active transaction"));
+        }
+
+        if self.simple {
+            return Err(misuse("`lazy_query` is not supported in simple query mode"));
+        }
 
         let id = self.next_portal_id.get();
         self.next_portal_id.set(id + 1);
         let portal_name = format!("{}p{}", self.name, id);
 
-        self.inner_query(&portal_name, row_limit, params).map(move |(data, more_rows)| {
-            LazyRows::new(self, data, portal_name, row_limit, more_rows, false, trans)
-        })
+        let (data, tag) = try!(self.inner_query(&mut conn, &portal_name, row_limit, params));
+        let more_rows = tag.is_none();
+        Ok(LazyRows::new(ConnRef::Borrowed(&self.conn),
+                         conn.parameters.clone(),
+                         self.columns_arc(),
+                         data,
+                         portal_name,
+                         row_limit,
+                         more_rows,
+                         false,
+                         trans))
     }
 
     /// Executes a `COPY FROM STDIN` statement, returning the number of rows
@@ -306,8 +992,15 @@ impl<'conn> Statement<'conn> {
     /// stmt.copy_in(&[], &mut "1\tjohn\n2\tjane\n".as_bytes()).unwrap();
     /// ```
     pub fn copy_in<R: ReadWithInfo>(&self, params: &[&ToSql], r: &mut R) -> Result<u64> {
-        try!(self.inner_execute("", 0, params));
-        let mut conn = self.conn.conn.borrow_mut();
+        self.copy_in_tagged(params, r).map(|tag| tag.rows().unwrap_or(0))
+    }
+
+    /// Like `copy_in`, except that it returns the command tag sent by the
+    /// server.
+    pub fn copy_in_tagged<R: ReadWithInfo>(&self, params: &[&ToSql], r: &mut R)
+                                           -> Result<CommandTag> {
+        let mut conn = try!(self.conn.lock());
+        try!(self.inner_execute(&mut conn, "", 0, params));
 
         match try!(conn.read_message()) {
             CopyInResponse { .. } => {}
@@ -327,21 +1020,27 @@ impl<'conn> Statement<'conn> {
 
         let mut buf = [0; 16 * 1024];
         loop {
-            match fill_copy_buf(&mut buf, r, &SessionInfo::new(&conn)) {
+            match fill_copy_buf(&mut buf, r, &SessionInfo::new(&conn.parameters)) {
                 Ok(0) => break,
-                Ok(len) => {
-                    try_desync!(conn, conn.stream.write_message(
-                        &CopyData {
-                            data: &buf[..len],
-                        }));
-                }
-                Err(err) => {
-                    try!(conn.write_messages(&[
-                        CopyFail {
-                            message: "",
-                        },
-                        CopyDone,
-                        Sync]));
+                Ok(len) => {
+                    try!(conn.write_message(&CopyData {
+                        data: &buf[..len],
+                    }));
+                }
+                Err(err) => {
+                    if self.simple {
+                        try!(conn.write_messages(&[
+                            CopyFail {
+                                message: "",
+                            }]));
+                    } else {
+                        try!(conn.write_messages(&[
+                            CopyFail {
+                                message: "",
+                            },
+                            CopyDone,
+                            Sync]));
+                    }
                     match try!(conn.read_message()) {
                         ErrorResponse { .. } => { /* expected from the CopyFail */ }
                         _ => {
@@ -355,10 +1054,14 @@ impl<'conn> Statement<'conn> {
             }
         }
 
-        try!(conn.write_messages(&[CopyDone, Sync]));
+        if self.simple {
+            try!(conn.write_messages(&[CopyDone]));
+        } else {
+            try!(conn.write_messages(&[CopyDone, Sync]));
+        }
 
-        let num = match try!(conn.read_message()) {
-            CommandComplete { tag } => util::parse_update_count(tag),
+        let tag = match try!(conn.read_message()) {
+            CommandComplete { tag } => CommandTag::new(tag),
             ErrorResponse { fields } => {
                 try!(conn.wait_for_ready());
                 return DbError::new(fields);
@@ -370,7 +1073,7 @@ impl<'conn> Statement<'conn> {
         };
 
         try!(conn.wait_for_ready());
-        Ok(num)
+        Ok(tag)
     }
 
     /// Executes a `COPY TO STDOUT` statement, returning a `Read`er of the
@@ -384,8 +1087,9 @@ impl<'conn> Statement<'conn> {
     ///
     /// # Warning
     ///
-    /// The underlying connection may not be used while the returned `Read`er
-    /// exists. Any attempt to do so will panic.
+    /// The underlying connection may not be used by this thread while the
+    /// returned `Read`er exists. Any attempt to do so will return an error,
+    /// while other threads will block until the `Read`er is dropped.
     ///
     /// # Examples
     ///
@@ -404,18 +1108,25 @@ impl<'conn> Statement<'conn> {
     /// assert_eq!(buf, b"1\tjohn\n2\tjane\n");
     /// ```
     pub fn copy_out<'a>(&'a self, params: &[&ToSql]) -> Result<CopyOutReader<'a>> {
-        try!(self.inner_execute("", 0, params));
-        let mut conn = self.conn.conn.borrow_mut();
+        let mut conn = try!(self.conn.lock());
+        try!(self.inner_execute(&mut conn, "", 0, params));
 
         let (format, column_formats) = match try!(conn.read_message()) {
             CopyOutResponse { format, column_formats } => (format, column_formats),
             CopyInResponse { .. } => {
-                try!(conn.write_messages(&[
-                    CopyFail {
-                        message: "",
-                    },
-                    CopyDone,
-                    Sync]));
+                if self.simple {
+                    try!(conn.write_messages(&[
+                        CopyFail {
+                            message: "",
+                        }]));
+                } else {
+                    try!(conn.write_messages(&[
+                        CopyFail {
+                            message: "",
+                        },
+                        CopyDone,
+                        Sync]));
+                }
                 match try!(conn.read_message()) {
                     ErrorResponse { .. } => { /* expected from the CopyFail */ }
                     _ => {
@@ -446,20 +1157,23 @@ impl<'conn> Statement<'conn> {
             conn: conn,
             format: Format::from_u16(format as u16),
             column_formats: column_formats.iter().map(|&f| Format::from_u16(f)).collect(),
-            buf: Cursor::new(vec![]),
+            buf: io::Cursor::new(vec![]),
             finished: false,
         })
     }
 
     /// Consumes the statement, clearing it from the Postgres session.
     ///
-    /// If this statement was created via the `prepare_cached` method, `finish`
-    /// does nothing.
+    /// If this statement was created via the `prepare_cached` method, it
+    /// remains in the cache and is only closed once it has been evicted and
+    /// no other `Statement`s created from it remain.
     ///
-    /// Functionally identical to the `Drop` implementation of the
-    /// `Statement` except that it returns any error to the caller.
+    /// The `Drop` implementation of `Statement` sends the close along with
+    /// the next batch of messages on the connection and reports any error to
+    /// the connection's notice handler. This method closes the statement
+    /// immediately and returns any error to the caller.
     pub fn finish(mut self) -> Result<()> {
-        self.finish_inner()
+        self.finish_inner(false)
     }
 }
 
@@ -477,18 +1191,344 @@ fn fill_copy_buf<R: ReadWithInfo>(buf: &mut [u8], r: &mut R, info: &SessionInfo)
     Ok(nread)
 }
 
+// Rolls back the batches of an `execute_many` which have already been
+// executed. A Sync on its own would commit them, so a portal which doesn't
+// exist is executed to force an error, which rolls back the implicit
+// transaction covering the earlier batches.
+fn abort_execute_many(conn: &mut InnerConnection) -> Result<()> {
+    try!(conn.write_messages(&[
+        Execute {
+            portal: "execute_many abort",
+            max_rows: 0,
+        },
+        Sync]));
+    loop {
+        match try!(conn.read_message()) {
+            ErrorResponse { .. } => {}
+            ReadyForQuery { .. } => return Ok(()),
+            _ => bad_response!(conn),
+        }
+    }
+}
+
+// Reads the result of one execution in a batch, returning the server's
+// error separately from failures to communicate with it.
+fn read_execute_many_result(conn: &mut InnerConnection)
+                            -> Result<result::Result<u64, Vec<(u8, String)>>> {
+    loop {
+        match try!(conn.read_message()) {
+            BindComplete | DataRow { .. } => {}
+            CommandComplete { tag } => return Ok(Ok(CommandTag::new(tag).rows().unwrap_or(0))),
+            EmptyQueryResponse => return Ok(Ok(0)),
+            ErrorResponse { fields } => return Ok(Err(fields)),
+            CopyInResponse { .. } | CopyOutResponse { .. } => {
+                // The rest of the batch has already been sent and would be
+                // read as COPY data.
+                conn.desynchronized = true;
+                return Err(Error::IoError(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "COPY statements cannot be executed with `execute_many`")));
+            }
+            _ => {
+                conn.desynchronized = true;
+                return Err(Error::IoError(bad_response()));
+            }
+        }
+    }
+}
+
+/// A prepared statement with bound parameters.
+///
+/// A `Portal` is created by `Statement::bind`, and is closed when it is
+/// dropped.
+pub struct Portal<'stmt> {
+    conn: ConnRef<'stmt>,
+    name: String,
+    columns: Arc<Vec<Column>>,
+    finished: bool,
+}
+
+impl<'stmt> fmt::Debug for Portal<'stmt> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "Portal")
+            .field("name", &self.name)
+            .field("columns", &self.columns)
+            .finish()
+    }
+}
+
+impl<'stmt> Drop for Portal<'stmt> {
+    fn drop(&mut self) {
+        let _ = self.finish_inner(true);
+    }
+}
+
+impl<'stmt> Portal<'stmt> {
+    fn finish_inner(&mut self, defer: bool) -> Result<()> {
+        if !self.finished {
+            self.finished = true;
+            if defer {
+                self.conn.defer_close(b'P', self.name.clone());
+                return Ok(());
+            }
+            let mut conn = try!(self.conn.lock());
+            check_desync!(conn);
+            conn.close_statement(&self.name, b'P')
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the name of the portal.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns a slice describing the columns of the rows returned by the
+    /// portal, as described when its statement was prepared.
+    pub fn columns(&self) -> &[Column] {
+        &self.columns
+    }
+
+    /// Executes the portal, returning up to `max_rows` of its remaining rows.
+    ///
+    /// The returned flag is true if the portal was suspended with rows
+    /// remaining, in which case they can be fetched by executing it again.
+    /// If `max_rows` is less than or equal to 0, all remaining rows are
+    /// returned.
+    pub fn execute(&self, max_rows: i32) -> Result<(Rows<'stmt>, bool)> {
+        let mut conn = try!(self.conn.lock());
+        check_desync!(conn);
+        try!(conn.write_messages(&[
+            Execute {
+                portal: &self.name,
+                max_rows: max_rows
+            },
+            Sync]));
+
+        let mut buf = VecDeque::new();
+        let tag = try!(read_rows(&mut conn, &mut buf));
+        let suspended = tag.is_none();
+        Ok((Rows::new(conn.parameters.clone(),
+                      self.columns.clone(),
+                      buf.into_iter().collect(),
+                      tag),
+            suspended))
+    }
+
+    /// Asks the server to describe the rows returned by the portal.
+    pub fn describe(&self) -> Result<Vec<Column>> {
+        let mut conn = try!(self.conn.lock());
+        check_desync!(conn);
+        try!(conn.write_messages(&[
+            Describe {
+                variant: b'P',
+                name: &self.name,
+            },
+            Sync]));
+
+        let resp = match try!(conn.read_message()) {
+            RowDescription { descriptions } => Ok(descriptions),
+            NoData => Ok(vec![]),
+            ErrorResponse { fields } => DbError::new(fields),
+            _ => bad_response!(conn),
+        };
+        try!(conn.wait_for_ready());
+
+        let mut columns = vec![];
+        for desc in try!(resp) {
+            let type_ = try!(conn.get_type(desc.type_oid));
+            columns.push(Column::new(desc, type_));
+        }
+        Ok(columns)
+    }
+
+    /// Closes the portal.
+    ///
+    /// Unlike the `Drop` implementation of `Portal`, which defers the close
+    /// to the next batch of messages sent on the connection, this closes the
+    /// portal immediately and returns any error to the caller.
+    pub fn close(mut self) -> Result<()> {
+        self.finish_inner(false)
+    }
+}
+
+/// Options for a SQL cursor declared by `Statement::declare_cursor`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct CursorOptions {
+    /// If set, the cursor is declared `SCROLL`, so it can be fetched
+    /// backwards and moved to absolute positions.
+    pub scroll: bool,
+    /// If set, the cursor is declared `WITH HOLD`, so it can be used after
+    /// the transaction in which it was declared commits.
+    pub hold: bool,
+}
+
+/// A SQL cursor.
+///
+/// A `Cursor` is created by `Statement::declare_cursor`, and is closed when
+/// it is dropped. A cursor declared without `CursorOptions::hold` is closed
+/// by the server when the transaction it was declared in ends, after which
+/// dropping it does nothing.
+pub struct Cursor<'conn> {
+    conn: ConnRef<'conn>,
+    name: String,
+    // The transaction a cursor not declared `WITH HOLD` is closed with.
+    scope: Option<TransactionScope>,
+    finished: bool,
+}
+
+impl<'conn> fmt::Debug for Cursor<'conn> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "Cursor")
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl<'conn> Drop for Cursor<'conn> {
+    fn drop(&mut self) {
+        let _ = self.finish_inner();
+    }
+}
+
+impl<'conn> Cursor<'conn> {
+    fn finish_inner(&mut self) -> Result<()> {
+        if !self.finished {
+            self.finished = true;
+            let mut conn = try!(self.conn.lock());
+            check_desync!(conn);
+            // Closing a cursor the server has already closed would fail, and
+            // abort whatever transaction is now open.
+            if let Some(ref scope) = self.scope {
+                if !conn.in_scope(scope) {
+                    return Ok(());
+                }
+            }
+            conn.quick_query(&format!("CLOSE {}", self.name)).map(|_| ())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the name of the cursor.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Fetches up to the next `count` rows of the cursor.
+    ///
+    /// An empty `Rows` is returned once the cursor is exhausted.
+    pub fn fetch(&self, count: i64) -> Result<Rows<'conn>> {
+        query_unnamed(self.conn.clone(),
+                      &format!("FETCH FORWARD {} FROM {}", count, self.name),
+                      &[])
+    }
+
+    /// Fetches up to the previous `count` rows of the cursor, in reverse
+    /// order.
+    ///
+    /// The cursor must have been declared with `CursorOptions::scroll` set.
+    pub fn fetch_backward(&self, count: i64) -> Result<Rows<'conn>> {
+        query_unnamed(self.conn.clone(),
+                      &format!("FETCH BACKWARD {} FROM {}", count, self.name),
+                      &[])
+    }
+
+    /// Moves the cursor to the specified row without fetching any rows.
+    ///
+    /// Rows are 1-indexed, and negative positions count back from the end of
+    /// the results. Position 0 is before the first row. Returns 1 if the
+    /// cursor is positioned on a row and 0 otherwise.
+    ///
+    /// The cursor must have been declared with `CursorOptions::scroll` set
+    /// unless the position is after its current position.
+    pub fn move_absolute(&self, position: i64) -> Result<u64> {
+        self.move_inner(&format!("MOVE ABSOLUTE {} IN {}", position, self.name))
+    }
+
+    /// Moves the cursor by `offset` rows without fetching any rows,
+    /// returning the number of rows moved over.
+    ///
+    /// The cursor must have been declared with `CursorOptions::scroll` set
+    /// to move backwards.
+    pub fn move_relative(&self, offset: i64) -> Result<u64> {
+        self.move_inner(&format!("MOVE FORWARD {} IN {}", offset, self.name))
+    }
+
+    fn move_inner(&self, query: &str) -> Result<u64> {
+        let mut conn = try!(self.conn.lock());
+        let stmt = try!(prepare_unnamed(&mut conn, self.conn.clone(), query, &[]));
+        let tag = try!(stmt.execute_locked(&mut conn, &[]));
+        Ok(tag.rows().unwrap_or(0))
+    }
+
+    /// Closes the cursor.
+    ///
+    /// Functionally identical to the `Drop` implementation of the `Cursor`
+    /// except that it returns any error to the caller.
+    pub fn close(mut self) -> Result<()> {
+        self.finish_inner()
+    }
+}
+
+fn read_bind_result(conn: &mut InnerConnection) -> Result<()> {
+    match try!(conn.read_message()) {
+        BindComplete => Ok(()),
+        ErrorResponse { fields } => {
+            try!(conn.wait_for_ready());
+            DbError::new(fields)
+        }
+        _ => {
+            conn.desynchronized = true;
+            Err(Error::IoError(bad_response()))
+        }
+    }
+}
+
+// Whether the error indicates that a prepared statement must be prepared again,
+// e.g. after its result type was changed by DDL or it was removed by
+// `DISCARD ALL`.
+fn is_stale_plan(err: &DbError) -> bool {
+    match *err.code() {
+        SqlState::InvalidSqlStatementName => true,
+        SqlState::FeatureNotSupported => {
+            err.message() == "cached plan must not change result type"
+        }
+        _ => false,
+    }
+}
+
+fn param_count_error(expected: usize, actual: usize) -> Error {
+    misuse(format!("expected {} parameters but got {}", expected, actual))
+}
+
+fn param_error(index: usize, ty: Type, param: &ToSql, err: Error) -> Error {
+    ValueError::wrap(ValueLocation::Parameter(index), ty, param.rust_type_name(), err)
+}
+
 /// Information about a column of the result of a query.
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct Column {
     name: String,
-    type_: Type
+    type_: Type,
+    table_oid: Oid,
+    column_id: i16,
+    type_size: i16,
+    type_modifier: i32,
+    format: Format,
 }
 
 impl ColumnNew for Column {
-    fn new(name: String, type_: Type) -> Column {
+    fn new(desc: RowDescriptionEntry, type_: Type) -> Column {
         Column {
-            name: name,
+            name: desc.name,
             type_: type_,
+            table_oid: desc.table_oid,
+            column_id: desc.column_id,
+            type_size: desc.type_size,
+            type_modifier: desc.type_modifier,
+            format: Format::from_u16(desc.format as u16),
         }
     }
 }
@@ -503,9 +1543,282 @@ impl Column {
     pub fn type_(&self) -> &Type {
         &self.type_
     }
+
+    /// The OID of the table the column's values come from, if they come
+    /// directly from a table column.
+    pub fn table_oid(&self) -> Option<Oid> {
+        if self.table_oid == 0 {
+            None
+        } else {
+            Some(self.table_oid)
+        }
+    }
+
+    /// The attribute number of the table column the column's values come
+    /// from, if they come directly from a table column.
+    pub fn column_id(&self) -> Option<i16> {
+        if self.column_id == 0 {
+            None
+        } else {
+            Some(self.column_id)
+        }
+    }
+
+    /// The size in bytes of the column's type, as in `pg_type.typlen`.
+    ///
+    /// Negative values indicate variable length types.
+    pub fn type_size(&self) -> i16 {
+        self.type_size
+    }
+
+    /// The type modifier of the column, as in `pg_attribute.atttypmod`.
+    ///
+    /// The meaning of the modifier depends on the type. -1 indicates that
+    /// there is no modifier. The `character_maximum_length`,
+    /// `numeric_precision`, `numeric_scale` and `datetime_precision` methods
+    /// decode the modifiers of the built in types which use them.
+    pub fn type_modifier(&self) -> i32 {
+        self.type_modifier
+    }
+
+    /// The format the server reported for the column's values.
+    ///
+    /// The columns of a prepared statement always report `Format::Text`,
+    /// since the format is not chosen until the statement is executed. The
+    /// columns returned by `Portal::describe` report the format the portal's
+    /// values are actually sent in.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// The declared maximum length of a `CHAR`, `VARCHAR`, `BIT` or
+    /// `VARBIT` column, or of the elements of an array of one of those types.
+    pub fn character_maximum_length(&self) -> Option<i32> {
+        if self.type_modifier < 0 {
+            return None;
+        }
+        match *self.element_type() {
+            Type::Bpchar | Type::Varchar => Some(self.type_modifier - 4),
+            Type::Bit | Type::Varbit => Some(self.type_modifier),
+            _ => None,
+        }
+    }
+
+    /// The declared precision of a `NUMERIC` column, or of the elements of an
+    /// array of `NUMERIC`s.
+    pub fn numeric_precision(&self) -> Option<i32> {
+        match *self.element_type() {
+            Type::Numeric if self.type_modifier >= 4 => {
+                Some(((self.type_modifier - 4) >> 16) & 0xffff)
+            }
+            _ => None,
+        }
+    }
+
+    /// The declared scale of a `NUMERIC` column, or of the elements of an
+    /// array of `NUMERIC`s.
+    ///
+    /// The scale may be negative on Postgres 15 and later.
+    pub fn numeric_scale(&self) -> Option<i32> {
+        match *self.element_type() {
+            // The scale is stored as an 11 bit signed integer.
+            Type::Numeric if self.type_modifier >= 4 => {
+                Some((((self.type_modifier - 4) & 0x7ff) ^ 1024) - 1024)
+            }
+            _ => None,
+        }
+    }
+
+    /// The declared fractional seconds precision of a `TIME`, `TIMETZ`,
+    /// `TIMESTAMP`, `TIMESTAMPTZ` or `INTERVAL` column, or of the elements of
+    /// an array of one of those types.
+    pub fn datetime_precision(&self) -> Option<i32> {
+        if self.type_modifier < 0 {
+            return None;
+        }
+        match *self.element_type() {
+            Type::Time | Type::Timetz | Type::Timestamp | Type::TimestampTZ => {
+                Some(self.type_modifier)
+            }
+            // The upper bits hold the interval's fields, and a precision of
+            // 0xffff means it was not specified.
+            Type::Interval => {
+                match self.type_modifier & 0xffff {
+                    0xffff => None,
+                    precision => Some(precision),
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn element_type(&self) -> &Type {
+        match *self.type_.kind() {
+            Kind::Array(ref member) => member,
+            _ => &self.type_,
+        }
+    }
+}
+
+/// The table column from which the values of a result column come.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct ColumnOrigin {
+    schema: String,
+    table: String,
+    column: String,
+    not_null: bool,
+}
+
+impl ColumnOriginNew for ColumnOrigin {
+    fn new(schema: String, table: String, column: String, not_null: bool) -> ColumnOrigin {
+        ColumnOrigin {
+            schema: schema,
+            table: table,
+            column: column,
+            not_null: not_null,
+        }
+    }
+}
+
+impl ColumnOrigin {
+    /// The name of the schema containing the table.
+    pub fn schema(&self) -> &str {
+        &self.schema
+    }
+
+    /// The name of the table.
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+
+    /// The name of the column in the table.
+    pub fn column(&self) -> &str {
+        &self.column
+    }
+
+    /// Determines if the table column has a `NOT NULL` constraint.
+    ///
+    /// Note that a query can still produce `NULL`s from such a column, for
+    /// example through an outer join.
+    pub fn not_null(&self) -> bool {
+        self.not_null
+    }
+}
+
+/// The kind of command reported by a `CommandTag`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CommandKind {
+    /// An `INSERT` command.
+    Insert,
+    /// A `DELETE` command.
+    Delete,
+    /// An `UPDATE` command.
+    Update,
+    /// A `MERGE` command.
+    Merge,
+    /// A `SELECT` or `CREATE TABLE AS` command.
+    Select,
+    /// A `MOVE` command.
+    Move,
+    /// A `FETCH` command.
+    Fetch,
+    /// A `COPY` command.
+    Copy,
+    /// Any other command, such as `CREATE TABLE` or `BEGIN`.
+    Other,
+}
+
+/// The tag sent by the server when a command completes, such as `INSERT 0 1`
+/// or `CREATE TABLE`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandTag {
+    tag: String,
+}
+
+impl CommandTagNew for CommandTag {
+    fn new(tag: String) -> CommandTag {
+        CommandTag {
+            tag: tag,
+        }
+    }
+}
+
+impl fmt::Display for CommandTag {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(&self.tag)
+    }
+}
+
+impl CommandTag {
+    /// Returns the raw tag.
+    ///
+    /// The tag is empty if the query string was empty.
+    pub fn as_str(&self) -> &str {
+        &self.tag
+    }
+
+    /// Returns the name of the command, without any trailing counts.
+    ///
+    /// For example, the command of `INSERT 0 1` is `INSERT`.
+    pub fn command(&self) -> &str {
+        let mut command = &*self.tag;
+        while let Some(idx) = command.rfind(' ') {
+            if !is_count(&command[idx + 1..]) {
+                break;
+            }
+            command = &command[..idx];
+        }
+        command
+    }
+
+    /// Returns the kind of the command.
+    pub fn kind(&self) -> CommandKind {
+        match self.command() {
+            "INSERT" => CommandKind::Insert,
+            "DELETE" => CommandKind::Delete,
+            "UPDATE" => CommandKind::Update,
+            "MERGE" => CommandKind::Merge,
+            "SELECT" => CommandKind::Select,
+            "MOVE" => CommandKind::Move,
+            "FETCH" => CommandKind::Fetch,
+            "COPY" => CommandKind::Copy,
+            _ => CommandKind::Other,
+        }
+    }
+
+    /// Returns the number of rows processed by the command, if the command
+    /// reports one.
+    ///
+    /// Commands like `CREATE TABLE` do not report a row count.
+    pub fn rows(&self) -> Option<u64> {
+        match self.tag.rfind(' ') {
+            Some(idx) => self.tag[idx + 1..].parse().ok(),
+            None => None,
+        }
+    }
+
+    /// Returns the OID of the inserted row for an `INSERT` command.
+    ///
+    /// The server reports an OID only when a single row was inserted into a
+    /// table created `WITH OIDS`, which is not supported by Postgres 12 and
+    /// later. Otherwise, the reported OID is 0 and `None` is returned.
+    pub fn insert_oid(&self) -> Option<Oid> {
+        let mut parts = self.tag.split(' ');
+        if parts.next() != Some("INSERT") {
+            return None;
+        }
+        match parts.next().and_then(|oid| oid.parse().ok()) {
+            Some(0) | None => None,
+            Some(oid) => Some(oid),
+        }
+    }
+}
+
+fn is_count(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
 }
 
-/// The format of a portion of COPY query data.
+/// The format of a portion of COPY query data or of the values of a column.
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Format {
     /// A text based format.
@@ -527,13 +1840,16 @@ impl Format {
 ///
 /// # Warning
 ///
-/// The underlying connection may not be used while a `CopyOutReader` exists.
-/// Any attempt to do so will panic.
+/// The underlying connection may not be used by this thread while a
+/// `CopyOutReader` exists. Any attempt to do so will return an
+/// `Error::Misuse`, while other threads will block until the `CopyOutReader`
+/// is dropped. Accessors which cannot return an error, like
+/// `Connection::parameter`, will panic.
 pub struct CopyOutReader<'a> {
-    conn: RefMut<'a, InnerConnection>,
+    conn: ConnGuard<'a>,
     format: Format,
     column_formats: Vec<Format>,
-    buf: Cursor<Vec<u8>>,
+    buf: io::Cursor<Vec<u8>>,
     finished: bool,
 }
 
@@ -556,7 +1872,7 @@ impl<'a> CopyOutReader<'a> {
 
     /// Returns session info for the associated connection.
     pub fn session_info<'b>(&'b self) -> SessionInfo<'b> {
-        SessionInfo::new(&*self.conn)
+        SessionInfo::new(&self.conn.parameters)
     }
 
     /// Consumes the `CopyOutReader`, throwing away any unread data.
@@ -582,7 +1898,7 @@ impl<'a> CopyOutReader<'a> {
         }
 
         match try!(self.conn.read_message()) {
-            BCopyData { data } => self.buf = Cursor::new(data),
+            BCopyData { data } => self.buf = io::Cursor::new(data),
             BCopyDone => {
                 self.finished = true;
                 match try!(self.conn.read_message()) {
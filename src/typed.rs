@@ -0,0 +1,194 @@
+//! Statically typed prepared statements.
+
+use debug_builders::DebugStruct;
+use std::any;
+use std::fmt;
+use std::marker::PhantomData;
+
+use error::{Error, ValueError, ValueLocation};
+use rows::{Row, FromRow};
+use stmt::{Statement, Column};
+use types::{Type, ToSql, FromSql};
+use {Result, ValueErrorNew, misuse};
+
+/// A tuple of values which can be passed as the parameters of a
+/// `TypedStatement`.
+///
+/// This is implemented for tuples of up to 12 `ToSql` values.
+pub trait Params {
+    /// Checks that the tuple's values can be converted to parameters of the
+    /// specified types.
+    fn check(types: &[Type]) -> Result<()>;
+
+    /// Returns the tuple's values as parameters.
+    fn to_params(&self) -> Vec<&ToSql>;
+}
+
+/// A tuple of values which can be created from the rows returned by a
+/// `TypedStatement`.
+///
+/// This is implemented for tuples of up to 12 `FromSql` values.
+pub trait RowType: Sized {
+    /// Checks that the tuple's values can be created from the specified
+    /// columns.
+    fn check(columns: &[Column]) -> Result<()>;
+
+    /// Creates the tuple from a row.
+    fn from_row(row: &Row) -> Result<Self>;
+}
+
+fn count_error(what: &str, expected: usize, actual: usize) -> Error {
+    misuse(format!("expected {} {} but the statement has {}", expected, what, actual))
+}
+
+macro_rules! count {
+    () => (0);
+    ($head:ident $($tail:ident)*) => (1 + count!($($tail)*));
+}
+
+macro_rules! tuple_impls {
+    ($($idx:tt => $t:ident),*) => {
+        impl<$($t: ToSql),*> Params for ($($t,)*) {
+            fn check(types: &[Type]) -> Result<()> {
+                const LEN: usize = count!($($t)*);
+                if types.len() != LEN {
+                    return Err(count_error("parameters", LEN, types.len()));
+                }
+                $(
+                    if !<$t as ToSql>::accepts(&types[$idx]) {
+                        let err = Error::WrongType(types[$idx].clone());
+                        return Err(ValueError::wrap(ValueLocation::Parameter($idx),
+                                                    types[$idx].clone(),
+                                                    any::type_name::<$t>(),
+                                                    err));
+                    }
+                )*
+                Ok(())
+            }
+
+            fn to_params(&self) -> Vec<&ToSql> {
+                vec![$(&self.$idx as &ToSql),*]
+            }
+        }
+
+        impl<$($t: FromSql),*> RowType for ($($t,)*) {
+            fn check(columns: &[Column]) -> Result<()> {
+                const LEN: usize = count!($($t)*);
+                if columns.len() != LEN {
+                    return Err(count_error("columns", LEN, columns.len()));
+                }
+                $(
+                    let ty = columns[$idx].type_();
+                    if !<$t as FromSql>::accepts(ty) {
+                        let location = ValueLocation::Column {
+                            index: $idx,
+                            name: columns[$idx].name().to_owned(),
+                        };
+                        return Err(ValueError::wrap(location,
+                                                    ty.clone(),
+                                                    any::type_name::<$t>(),
+                                                    Error::WrongType(ty.clone())));
+                    }
+                )*
+                Ok(())
+            }
+
+            fn from_row(row: &Row) -> Result<($($t,)*)> {
+                FromRow::from_row(row)
+            }
+        }
+
+        impl<$($t: FromSql),*> FromRow for ($($t,)*) {
+            fn from_row(row: &Row) -> Result<($($t,)*)> {
+                const LEN: usize = count!($($t)*);
+                if row.len() != LEN {
+                    return Err(misuse(format!("expected {} columns but the row has {}",
+                                              LEN,
+                                              row.len())));
+                }
+                Ok(($(try!(row.get_opt::<_, $t>($idx)),)*))
+            }
+        }
+    }
+}
+
+tuple_impls!();
+tuple_impls!(0 => A);
+tuple_impls!(0 => A, 1 => B);
+tuple_impls!(0 => A, 1 => B, 2 => C);
+tuple_impls!(0 => A, 1 => B, 2 => C, 3 => D);
+tuple_impls!(0 => A, 1 => B, 2 => C, 3 => D, 4 => E);
+tuple_impls!(0 => A, 1 => B, 2 => C, 3 => D, 4 => E, 5 => F);
+tuple_impls!(0 => A, 1 => B, 2 => C, 3 => D, 4 => E, 5 => F, 6 => G);
+tuple_impls!(0 => A, 1 => B, 2 => C, 3 => D, 4 => E, 5 => F, 6 => G, 7 => H);
+tuple_impls!(0 => A, 1 => B, 2 => C, 3 => D, 4 => E, 5 => F, 6 => G, 7 => H, 8 => I);
+tuple_impls!(0 => A, 1 => B, 2 => C, 3 => D, 4 => E, 5 => F, 6 => G, 7 => H, 8 => I, 9 => J);
+tuple_impls!(0 => A, 1 => B, 2 => C, 3 => D, 4 => E, 5 => F, 6 => G, 7 => H, 8 => I, 9 => J,
+             10 => K);
+tuple_impls!(0 => A, 1 => B, 2 => C, 3 => D, 4 => E, 5 => F, 6 => G, 7 => H, 8 => I, 9 => J,
+             10 => K, 11 => L);
+
+/// A prepared statement whose parameter and row types have been checked
+/// against the types reported by the server.
+///
+/// `P` is a tuple of the statement's parameter types and `R` is a tuple of
+/// the types of its columns. Once the statement has been created, values of
+/// those types can always be passed to and read from it. Reading a `NULL`
+/// value into a type other than `Option` is still an error.
+///
+/// ## Example
+///
+/// ```rust,no_run
+/// # use postgres::{Connection, SslMode};
+/// # let conn = Connection::connect("", &SslMode::None).unwrap();
+/// let stmt = conn.prepare_checked::<(i32,), (String, Option<i32>)>(
+///     "SELECT name, age FROM person WHERE id = $1").unwrap();
+/// for (name, age) in stmt.query(&(1,)).unwrap() {
+///     println!("{}: {:?}", name, age);
+/// }
+/// ```
+pub struct TypedStatement<'conn, P, R> {
+    stmt: Statement<'conn>,
+    _p: PhantomData<fn(P) -> R>,
+}
+
+impl<'conn, P, R> fmt::Debug for TypedStatement<'conn, P, R> {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        DebugStruct::new(fmt, "TypedStatement")
+            .field("statement", &self.stmt)
+            .finish()
+    }
+}
+
+impl<'conn, P: Params, R: RowType> TypedStatement<'conn, P, R> {
+    /// Checks a prepared statement's parameter and column types, returning a
+    /// `TypedStatement` if they are compatible with `P` and `R`.
+    ///
+    /// Returns an `Error::Value` wrapping `Error::WrongType` if a parameter
+    /// or column type is not compatible, and an `Error::Misuse` if the number
+    /// of parameters or columns does not match.
+    pub fn new(stmt: Statement<'conn>) -> Result<TypedStatement<'conn, P, R>> {
+        try!(P::check(stmt.param_types()));
+        try!(R::check(stmt.columns()));
+        Ok(TypedStatement {
+            stmt: stmt,
+            _p: PhantomData,
+        })
+    }
+
+    /// Returns the underlying statement.
+    pub fn statement(&self) -> &Statement<'conn> {
+        &self.stmt
+    }
+
+    /// Like `Statement::execute`.
+    pub fn execute(&self, params: &P) -> Result<u64> {
+        self.stmt.execute(&params.to_params())
+    }
+
+    /// Like `Statement::query`, except that the rows are returned as tuples.
+    pub fn query(&self, params: &P) -> Result<Vec<R>> {
+        let rows = try!(self.stmt.query(&params.to_params()));
+        rows.iter().map(|row| R::from_row(&row)).collect()
+    }
+}
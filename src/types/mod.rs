@@ -8,7 +8,7 @@ use std::io;
 use byteorder::{ReadBytesExt, WriteBytesExt, BigEndian};
 
 pub use self::slice::Slice;
-use {Result, SessionInfoNew, InnerConnection, OtherNew, TypeNew};
+use {Result, SessionInfoNew, OtherNew, TypeNew};
 use error::Error;
 use util;
 
@@ -57,13 +57,13 @@ mod chrono;
 
 /// A structure providing information for conversion methods.
 pub struct SessionInfo<'a> {
-    conn: &'a InnerConnection,
+    parameters: &'a HashMap<String, String>,
 }
 
 impl<'a> SessionInfoNew<'a> for SessionInfo<'a> {
-    fn new(conn: &'a InnerConnection) -> SessionInfo<'a> {
+    fn new(parameters: &'a HashMap<String, String>) -> SessionInfo<'a> {
         SessionInfo {
-            conn: conn
+            parameters: parameters
         }
     }
 }
@@ -72,7 +72,7 @@ impl<'a> SessionInfo<'a> {
     /// Returns the value of the specified Postgres backend parameter, such
     /// as `timezone` or `server_version`.
     pub fn parameter(&self, param: &str) -> Option<&'a str> {
-        self.conn.parameters.get(param).map(|s| &**s)
+        self.parameters.get(param).map(|s| &**s)
     }
 }
 
@@ -113,7 +113,7 @@ macro_rules! as_expr {
 }
 
 macro_rules! make_postgres_type {
-    ($(#[$doc:meta] $oid:tt => $variant:ident: $kind:expr),+) => (
+    ($(#[$doc:meta] $oid:tt => $variant:ident: $name:expr, $kind:expr),+) => (
         /// A Postgres type.
         #[derive(PartialEq, Eq, Clone)]
         pub enum Type {
@@ -153,6 +153,18 @@ macro_rules! make_postgres_type {
                 }
             }
 
+            /// Returns the name of the `Type`, as it appears in the
+            /// `pg_type` catalog.
+            ///
+            /// Array types are named after their element types with a
+            /// leading underscore, for example `_int4`.
+            pub fn name(&self) -> &str {
+                match *self {
+                    $(Type::$variant => $name,)+
+                    Type::Other(ref u) => u.name(),
+                }
+            }
+
             /// The kind of this type.
             pub fn kind(&self) -> &Kind {
                 match *self {
@@ -172,317 +184,317 @@ macro_rules! make_postgres_type {
 // Values from pg_type.h
 make_postgres_type! {
     #[doc="BOOL - boolean, 'true'/'false'"]
-    16 => Bool: Kind::Simple,
+    16 => Bool: "bool", Kind::Simple,
     #[doc="BYTEA - variable-length string, binary values escaped"]
-    17 => Bytea: Kind::Simple,
+    17 => Bytea: "bytea", Kind::Simple,
     #[doc="\"char\" - single character"]
-    18 => Char: Kind::Simple,
+    18 => Char: "char", Kind::Simple,
     #[doc="NAME - 63-byte type for storing system identifiers"]
-    19 => Name: Kind::Simple,
+    19 => Name: "name", Kind::Simple,
     #[doc="INT8/BIGINT - ~18 digit integer, 8-byte storage"]
-    20 => Int8: Kind::Simple,
+    20 => Int8: "int8", Kind::Simple,
     #[doc="INT2/SMALLINT - -32 thousand to 32 thousand, 2-byte storage"]
-    21 => Int2: Kind::Simple,
+    21 => Int2: "int2", Kind::Simple,
     #[doc="INT2VECTOR - array of int2, used in system tables"]
-    22 => Int2Vector: Kind::Array(Type::Int2),
+    22 => Int2Vector: "int2vector", Kind::Array(Type::Int2),
     #[doc="INT4/INT - -2 billion to 2 billion integer, 4-byte storage"]
-    23 => Int4: Kind::Simple,
+    23 => Int4: "int4", Kind::Simple,
     #[doc="REGPROC - registered procedure"]
-    24 => Regproc: Kind::Simple,
+    24 => Regproc: "regproc", Kind::Simple,
     #[doc="TEXT - variable-length string, no limit specified"]
-    25 => Text: Kind::Simple,
+    25 => Text: "text", Kind::Simple,
     #[doc="OID - object identifier(oid), maximum 4 billion"]
-    26 => Oid: Kind::Simple,
+    26 => Oid: "oid", Kind::Simple,
     #[doc="TID - (block, offset), physical location of tuple"]
-    27 => Tid: Kind::Simple,
+    27 => Tid: "tid", Kind::Simple,
     #[doc="XID - transaction id"]
-    28 => Xid: Kind::Simple,
+    28 => Xid: "xid", Kind::Simple,
     #[doc="CID - command identifier type, sequence in transaction id"]
-    29 => Cid: Kind::Simple,
+    29 => Cid: "cid", Kind::Simple,
     #[doc="OIDVECTOR - array of oids, used in system tables"]
-    30 => OidVector: Kind::Array(Type::Oid),
+    30 => OidVector: "oidvector", Kind::Array(Type::Oid),
     #[doc="PG_TYPE"]
-    71 => PgType: Kind::Simple,
+    71 => PgType: "pg_type", Kind::Simple,
     #[doc="PG_ATTRIBUTE"]
-    75 => PgAttribute: Kind::Simple,
+    75 => PgAttribute: "pg_attribute", Kind::Simple,
     #[doc="PG_PROC"]
-    81 => PgProc: Kind::Simple,
+    81 => PgProc: "pg_proc", Kind::Simple,
     #[doc="PG_CLASS"]
-    83 => PgClass: Kind::Simple,
+    83 => PgClass: "pg_class", Kind::Simple,
     #[doc="JSON"]
-    114 => Json: Kind::Simple,
+    114 => Json: "json", Kind::Simple,
     #[doc="XML - XML content"]
-    142 => Xml: Kind::Simple,
+    142 => Xml: "xml", Kind::Simple,
     #[doc="XML[]"]
-    143 => XmlArray: Kind::Array(Type::Xml),
+    143 => XmlArray: "_xml", Kind::Array(Type::Xml),
     #[doc="PG_NODE_TREE - string representing an internal node tree"]
-    194 => PgNodeTree: Kind::Simple,
+    194 => PgNodeTree: "pg_node_tree", Kind::Simple,
     #[doc="JSON[]"]
-    199 => JsonArray: Kind::Array(Type::Json),
+    199 => JsonArray: "_json", Kind::Array(Type::Json),
     #[doc="SMGR - storage manager"]
-    210 => Smgr: Kind::Simple,
+    210 => Smgr: "smgr", Kind::Simple,
     #[doc="POINT - geometric point '(x, y)'"]
-    600 => Point: Kind::Simple,
+    600 => Point: "point", Kind::Simple,
     #[doc="LSEG - geometric line segment '(pt1,pt2)'"]
-    601 => Lseg: Kind::Simple,
+    601 => Lseg: "lseg", Kind::Simple,
     #[doc="PATH - geometric path '(pt1,...)'"]
-    602 => Path: Kind::Simple,
+    602 => Path: "path", Kind::Simple,
     #[doc="BOX - geometric box '(lower left,upper right)'"]
-    603 => Box: Kind::Simple,
+    603 => Box: "box", Kind::Simple,
     #[doc="POLYGON - geometric polygon '(pt1,...)'"]
-    604 => Polygon: Kind::Simple,
+    604 => Polygon: "polygon", Kind::Simple,
     #[doc="LINE - geometric line"]
-    628 => Line: Kind::Simple,
+    628 => Line: "line", Kind::Simple,
     #[doc="LINE[]"]
-    629 => LineArray: Kind::Array(Type::Line),
+    629 => LineArray: "_line", Kind::Array(Type::Line),
     #[doc="CIDR - network IP address/netmask, network address"]
-    650 => Cidr: Kind::Simple,
+    650 => Cidr: "cidr", Kind::Simple,
     #[doc="CIDR[]"]
-    651 => CidrArray: Kind::Array(Type::Cidr),
+    651 => CidrArray: "_cidr", Kind::Array(Type::Cidr),
     #[doc="FLOAT4/REAL - single-precision floating point number, 4-byte storage"]
-    700 => Float4: Kind::Simple,
+    700 => Float4: "float4", Kind::Simple,
     #[doc="FLOAT8/DOUBLE PRECISION - double-precision floating point number, 8-byte storage"]
-    701 => Float8: Kind::Simple,
+    701 => Float8: "float8", Kind::Simple,
     #[doc="ABSTIME - absolute, limited-range date and time (Unix system time)"]
-    702 => Abstime: Kind::Simple,
+    702 => Abstime: "abstime", Kind::Simple,
     #[doc="RELTIME - relative, limited-range date and time (Unix delta time)"]
-    703 => Reltime: Kind::Simple,
+    703 => Reltime: "reltime", Kind::Simple,
     #[doc="TINTERVAL - (abstime,abstime), time interval"]
-    704 => Tinterval: Kind::Simple,
+    704 => Tinterval: "tinterval", Kind::Simple,
     #[doc="UNKNOWN"]
-    705 => Unknown: Kind::Simple,
+    705 => Unknown: "unknown", Kind::Simple,
     #[doc="CIRCLE - geometric circle '(center,radius)'"]
-    718 => Circle: Kind::Simple,
+    718 => Circle: "circle", Kind::Simple,
     #[doc="CIRCLE[]"]
-    719 => CircleArray: Kind::Array(Type::Circle),
+    719 => CircleArray: "_circle", Kind::Array(Type::Circle),
     #[doc="MONEY - monetary amounts, $d,ddd.cc"]
-    790 => Money: Kind::Simple,
+    790 => Money: "money", Kind::Simple,
     #[doc="MONEY[]"]
-    791 => MoneyArray: Kind::Array(Type::Money),
+    791 => MoneyArray: "_money", Kind::Array(Type::Money),
     #[doc="MACADDR - XX:XX:XX:XX:XX:XX, MAC address"]
-    829 => Macaddr: Kind::Simple,
+    829 => Macaddr: "macaddr", Kind::Simple,
     #[doc="INET - IP address/netmask, host address, netmask optional"]
-    869 => Inet: Kind::Simple,
+    869 => Inet: "inet", Kind::Simple,
     #[doc="BOOL[]"]
-    1000 => BoolArray: Kind::Array(Type::Bool),
+    1000 => BoolArray: "_bool", Kind::Array(Type::Bool),
     #[doc="BYTEA[]"]
-    1001 => ByteaArray: Kind::Array(Type::Bytea),
+    1001 => ByteaArray: "_bytea", Kind::Array(Type::Bytea),
     #[doc="\"char\"[]"]
-    1002 => CharArray: Kind::Array(Type::Char),
+    1002 => CharArray: "_char", Kind::Array(Type::Char),
     #[doc="NAME[]"]
-    1003 => NameArray: Kind::Array(Type::Name),
+    1003 => NameArray: "_name", Kind::Array(Type::Name),
     #[doc="INT2[]"]
-    1005 => Int2Array: Kind::Array(Type::Int2),
+    1005 => Int2Array: "_int2", Kind::Array(Type::Int2),
     #[doc="INT2VECTOR[]"]
-    1006 => Int2VectorArray: Kind::Array(Type::Int2Vector),
+    1006 => Int2VectorArray: "_int2vector", Kind::Array(Type::Int2Vector),
     #[doc="INT4[]"]
-    1007 => Int4Array: Kind::Array(Type::Int4),
+    1007 => Int4Array: "_int4", Kind::Array(Type::Int4),
     #[doc="REGPROC[]"]
-    1008 => RegprocArray: Kind::Array(Type::Regproc),
+    1008 => RegprocArray: "_regproc", Kind::Array(Type::Regproc),
     #[doc="TEXT[]"]
-    1009 => TextArray: Kind::Array(Type::Text),
+    1009 => TextArray: "_text", Kind::Array(Type::Text),
     #[doc="TID[]"]
-    1010 => TidArray: Kind::Array(Type::Tid),
+    1010 => TidArray: "_tid", Kind::Array(Type::Tid),
     #[doc="XID[]"]
-    1011 => XidArray: Kind::Array(Type::Xid),
+    1011 => XidArray: "_xid", Kind::Array(Type::Xid),
     #[doc="CID[]"]
-    1012 => CidArray: Kind::Array(Type::Cid),
+    1012 => CidArray: "_cid", Kind::Array(Type::Cid),
     #[doc="OIDVECTOR[]"]
-    1013 => OidVectorArray: Kind::Array(Type::OidVector),
+    1013 => OidVectorArray: "_oidvector", Kind::Array(Type::OidVector),
     #[doc="BPCHAR[]"]
-    1014 => BpcharArray: Kind::Array(Type::Bpchar),
+    1014 => BpcharArray: "_bpchar", Kind::Array(Type::Bpchar),
     #[doc="VARCHAR[]"]
-    1015 => VarcharArray: Kind::Array(Type::Varchar),
+    1015 => VarcharArray: "_varchar", Kind::Array(Type::Varchar),
     #[doc="INT8[]"]
-    1016 => Int8Array: Kind::Array(Type::Int8),
+    1016 => Int8Array: "_int8", Kind::Array(Type::Int8),
     #[doc="POINT[]"]
-    1017 => PointArray: Kind::Array(Type::Point),
+    1017 => PointArray: "_point", Kind::Array(Type::Point),
     #[doc="LSEG[]"]
-    1018 => LsegArray: Kind::Array(Type::Lseg),
+    1018 => LsegArray: "_lseg", Kind::Array(Type::Lseg),
     #[doc="PATH[]"]
-    1019 => PathArray: Kind::Array(Type::Path),
+    1019 => PathArray: "_path", Kind::Array(Type::Path),
     #[doc="BOX[]"]
-    1020 => BoxArray: Kind::Array(Type::Box),
+    1020 => BoxArray: "_box", Kind::Array(Type::Box),
     #[doc="FLOAT4[]"]
-    1021 => Float4Array: Kind::Array(Type::Float4),
+    1021 => Float4Array: "_float4", Kind::Array(Type::Float4),
     #[doc="FLOAT8[]"]
-    1022 => Float8Array: Kind::Array(Type::Float8),
+    1022 => Float8Array: "_float8", Kind::Array(Type::Float8),
     #[doc="ABSTIME[]"]
-    1023 => AbstimeArray: Kind::Array(Type::Abstime),
+    1023 => AbstimeArray: "_abstime", Kind::Array(Type::Abstime),
     #[doc="RELTIME[]"]
-    1024 => ReltimeArray: Kind::Array(Type::Reltime),
+    1024 => ReltimeArray: "_reltime", Kind::Array(Type::Reltime),
     #[doc="TINTERVAL[]"]
-    1025 => TintervalArray: Kind::Array(Type::Tinterval),
+    1025 => TintervalArray: "_tinterval", Kind::Array(Type::Tinterval),
     #[doc="POLYGON[]"]
-    1027 => PolygonArray: Kind::Array(Type::Polygon),
+    1027 => PolygonArray: "_polygon", Kind::Array(Type::Polygon),
     #[doc="OID[]"]
-    1028 => OidArray: Kind::Array(Type::Oid),
+    1028 => OidArray: "_oid", Kind::Array(Type::Oid),
     #[doc="ACLITEM - access control list"]
-    1033 => Aclitem: Kind::Simple,
+    1033 => Aclitem: "aclitem", Kind::Simple,
     #[doc="ACLITEM[]"]
-    1034 => AclitemArray: Kind::Array(Type::Aclitem),
+    1034 => AclitemArray: "_aclitem", Kind::Array(Type::Aclitem),
     #[doc="MACADDR[]"]
-    1040 => MacaddrArray: Kind::Array(Type::Macaddr),
+    1040 => MacaddrArray: "_macaddr", Kind::Array(Type::Macaddr),
     #[doc="INET[]"]
-    1041 => InetArray: Kind::Array(Type::Inet),
+    1041 => InetArray: "_inet", Kind::Array(Type::Inet),
     #[doc="BPCHAR - char(length), blank-padded string, fixed storage length"]
-    1042 => Bpchar: Kind::Simple,
+    1042 => Bpchar: "bpchar", Kind::Simple,
     #[doc="VARCHAR - varchar(length), non-blank-padded string, variable storage length"]
-    1043 => Varchar: Kind::Simple,
+    1043 => Varchar: "varchar", Kind::Simple,
     #[doc="DATE - date"]
-    1082 => Date: Kind::Simple,
+    1082 => Date: "date", Kind::Simple,
     #[doc="TIME - time of day"]
-    1083 => Time: Kind::Simple,
+    1083 => Time: "time", Kind::Simple,
     #[doc="TIMESTAMP - date and time"]
-    1114 => Timestamp: Kind::Simple,
+    1114 => Timestamp: "timestamp", Kind::Simple,
     #[doc="TIMESTAMP[]"]
-    1115 => TimestampArray: Kind::Array(Type::Timestamp),
+    1115 => TimestampArray: "_timestamp", Kind::Array(Type::Timestamp),
     #[doc="DATE[]"]
-    1182 => DateArray: Kind::Array(Type::Date),
+    1182 => DateArray: "_date", Kind::Array(Type::Date),
     #[doc="TIME[]"]
-    1183 => TimeArray: Kind::Array(Type::Time),
+    1183 => TimeArray: "_time", Kind::Array(Type::Time),
     #[doc="TIMESTAMPTZ - date and time with time zone"]
-    1184 => TimestampTZ: Kind::Simple,
+    1184 => TimestampTZ: "timestamptz", Kind::Simple,
     #[doc="TIMESTAMPTZ[]"]
-    1185 => TimestampTZArray: Kind::Array(Type::TimestampTZ),
+    1185 => TimestampTZArray: "_timestamptz", Kind::Array(Type::TimestampTZ),
     #[doc="INTERVAL - @ &lt;number&gt; &lt;units&gt;, time interval"]
-    1186 => Interval: Kind::Simple,
+    1186 => Interval: "interval", Kind::Simple,
     #[doc="INTERVAL[]"]
-    1187 => IntervalArray: Kind::Array(Type::Interval),
+    1187 => IntervalArray: "_interval", Kind::Array(Type::Interval),
     #[doc="NUMERIC[]"]
-    1231 => NumericArray: Kind::Array(Type::Numeric),
+    1231 => NumericArray: "_numeric", Kind::Array(Type::Numeric),
     #[doc="CSTRING[]"]
-    1263 => CstringArray: Kind::Array(Type::Cstring),
+    1263 => CstringArray: "_cstring", Kind::Array(Type::Cstring),
     #[doc="TIMETZ - time of day with time zone"]
-    1266 => Timetz: Kind::Simple,
+    1266 => Timetz: "timetz", Kind::Simple,
     #[doc="TIMETZ[]"]
-    1270 => TimetzArray: Kind::Array(Type::Timetz),
+    1270 => TimetzArray: "_timetz", Kind::Array(Type::Timetz),
     #[doc="BIT - fixed-length bit string"]
-    1560 => Bit: Kind::Simple,
+    1560 => Bit: "bit", Kind::Simple,
     #[doc="BIT[]"]
-    1561 => BitArray: Kind::Array(Type::Bit),
+    1561 => BitArray: "_bit", Kind::Array(Type::Bit),
     #[doc="VARBIT - variable-length bit string"]
-    1562 => Varbit: Kind::Simple,
+    1562 => Varbit: "varbit", Kind::Simple,
     #[doc="VARBIT[]"]
-    1563 => VarbitArray: Kind::Array(Type::Varbit),
+    1563 => VarbitArray: "_varbit", Kind::Array(Type::Varbit),
     #[doc="NUMERIC - numeric(precision, decimal), arbitrary precision number"]
-    1700 => Numeric: Kind::Simple,
+    1700 => Numeric: "numeric", Kind::Simple,
     #[doc="REFCURSOR - reference to cursor (portal name)"]
-    1790 => Refcursor: Kind::Simple,
+    1790 => Refcursor: "refcursor", Kind::Simple,
     #[doc="REFCURSOR[]"]
-    2201 => RefcursorArray: Kind::Array(Type::Refcursor),
+    2201 => RefcursorArray: "_refcursor", Kind::Array(Type::Refcursor),
     #[doc="REGPROCEDURE - registered procedure (with args)"]
-    2202 => Regprocedure: Kind::Simple,
+    2202 => Regprocedure: "regprocedure", Kind::Simple,
     #[doc="REGOPER - registered operator"]
-    2203 => Regoper: Kind::Simple,
+    2203 => Regoper: "regoper", Kind::Simple,
     #[doc="REGOPERATOR - registered operator (with args)"]
-    2204 => Regoperator: Kind::Simple,
+    2204 => Regoperator: "regoperator", Kind::Simple,
     #[doc="REGCLASS - registered class"]
-    2205 => Regclass: Kind::Simple,
+    2205 => Regclass: "regclass", Kind::Simple,
     #[doc="REGTYPE - registered type"]
-    2206 => Regtype: Kind::Simple,
+    2206 => Regtype: "regtype", Kind::Simple,
     #[doc="REGPROCEDURE[]"]
-    2207 => RegprocedureArray: Kind::Array(Type::Regprocedure),
+    2207 => RegprocedureArray: "_regprocedure", Kind::Array(Type::Regprocedure),
     #[doc="REGOPER[]"]
-    2208 => RegoperArray: Kind::Array(Type::Regoper),
+    2208 => RegoperArray: "_regoper", Kind::Array(Type::Regoper),
     #[doc="REGOPERATOR[]"]
-    2209 => RegoperatorArray: Kind::Array(Type::Regoperator),
+    2209 => RegoperatorArray: "_regoperator", Kind::Array(Type::Regoperator),
     #[doc="REGCLASS[]"]
-    2210 => RegclassArray: Kind::Array(Type::Regclass),
+    2210 => RegclassArray: "_regclass", Kind::Array(Type::Regclass),
     #[doc="REGTYPE[]"]
-    2211 => RegtypeArray: Kind::Array(Type::Regtype),
+    2211 => RegtypeArray: "_regtype", Kind::Array(Type::Regtype),
     #[doc="RECORD"]
-    2249 => Record: Kind::Simple,
+    2249 => Record: "record", Kind::Simple,
     #[doc="CSTRING"]
-    2275 => Cstring: Kind::Simple,
+    2275 => Cstring: "cstring", Kind::Simple,
     #[doc="ANY"]
-    2276 => Any: Kind::Simple,
+    2276 => Any: "any", Kind::Simple,
     #[doc="ANY[]"]
-    2277 => AnyArray: Kind::Array(Type::Any),
+    2277 => AnyArray: "anyarray", Kind::Array(Type::Any),
     #[doc="VOID"]
-    2278 => Void: Kind::Simple,
+    2278 => Void: "void", Kind::Simple,
     #[doc="TRIGGER"]
-    2279 => Trigger: Kind::Simple,
+    2279 => Trigger: "trigger", Kind::Simple,
     #[doc="LANGUAGE_HANDLER"]
-    2280 => LanguageHandler: Kind::Simple,
+    2280 => LanguageHandler: "language_handler", Kind::Simple,
     #[doc="INTERNAL"]
-    2281 => Internal: Kind::Simple,
+    2281 => Internal: "internal", Kind::Simple,
     #[doc="OPAQUE"]
-    2282 => Opaque: Kind::Simple,
+    2282 => Opaque: "opaque", Kind::Simple,
     #[doc="ANYELEMENT"]
-    2283 => Anyelement: Kind::Simple,
+    2283 => Anyelement: "anyelement", Kind::Simple,
     #[doc="RECORD[]"]
-    2287 => RecordArray: Kind::Array(Type::Record),
+    2287 => RecordArray: "_record", Kind::Array(Type::Record),
     #[doc="ANYNONARRAY"]
-    2776 => Anynonarray: Kind::Simple,
+    2776 => Anynonarray: "anynonarray", Kind::Simple,
     #[doc="TXID_SNAPSHOT[]"]
-    2949 => TxidSnapshotArray: Kind::Array(Type::TxidSnapshot),
+    2949 => TxidSnapshotArray: "_txid_snapshot", Kind::Array(Type::TxidSnapshot),
     #[doc="UUID - UUID datatype"]
-    2950 => Uuid: Kind::Simple,
+    2950 => Uuid: "uuid", Kind::Simple,
     #[doc="TXID_SNAPSHOT - txid snapshot"]
-    2970 => TxidSnapshot: Kind::Simple,
+    2970 => TxidSnapshot: "txid_snapshot", Kind::Simple,
     #[doc="UUID[]"]
-    2951 => UuidArray: Kind::Array(Type::Uuid),
+    2951 => UuidArray: "_uuid", Kind::Array(Type::Uuid),
     #[doc="FDW_HANDLER"]
-    3115 => FdwHandler: Kind::Simple,
+    3115 => FdwHandler: "fdw_handler", Kind::Simple,
     #[doc="PG_LSN - PostgreSQL LSN datatype"]
-    3320 => PgLsn: Kind::Simple,
+    3320 => PgLsn: "pg_lsn", Kind::Simple,
     #[doc="PG_LSN[]"]
-    3321 => PgLsnArray: Kind::Array(Type::PgLsn),
+    3321 => PgLsnArray: "_pg_lsn", Kind::Array(Type::PgLsn),
     #[doc="ANYENUM"]
-    3500 => Anyenum: Kind::Simple,
+    3500 => Anyenum: "anyenum", Kind::Simple,
     #[doc="TSVECTOR - text representation for text search"]
-    3614 => Tsvector: Kind::Simple,
+    3614 => Tsvector: "tsvector", Kind::Simple,
     #[doc="TSQUERY - query representation for text search"]
-    3615 => Tsquery: Kind::Simple,
+    3615 => Tsquery: "tsquery", Kind::Simple,
     #[doc="GTSVECTOR - GiST index internal text representation for text search"]
-    3642 => Gtsvector: Kind::Simple,
+    3642 => Gtsvector: "gtsvector", Kind::Simple,
     #[doc="TSVECTOR[]"]
-    3643 => TsvectorArray: Kind::Array(Type::Tsvector),
+    3643 => TsvectorArray: "_tsvector", Kind::Array(Type::Tsvector),
     #[doc="GTSVECTOR[]"]
-    3644 => GtsvectorArray: Kind::Array(Type::Gtsvector),
+    3644 => GtsvectorArray: "_gtsvector", Kind::Array(Type::Gtsvector),
     #[doc="TSQUERY[]"]
-    3645 => TsqueryArray: Kind::Array(Type::Tsquery),
+    3645 => TsqueryArray: "_tsquery", Kind::Array(Type::Tsquery),
     #[doc="REGCONFIG - registered text search configuration"]
-    3734 => Regconfig: Kind::Simple,
+    3734 => Regconfig: "regconfig", Kind::Simple,
     #[doc="REGCONFIG[]"]
-    3735 => RegconfigArray: Kind::Array(Type::Regconfig),
+    3735 => RegconfigArray: "_regconfig", Kind::Array(Type::Regconfig),
     #[doc="REGDICTIONARY - registered text search dictionary"]
-    3769 => Regdictionary: Kind::Simple,
+    3769 => Regdictionary: "regdictionary", Kind::Simple,
     #[doc="REGDICTIONARY[]"]
-    3770 => RegdictionaryArray: Kind::Array(Type::Regdictionary),
+    3770 => RegdictionaryArray: "_regdictionary", Kind::Array(Type::Regdictionary),
     #[doc="JSONB"]
-    3802 => Jsonb: Kind::Simple,
+    3802 => Jsonb: "jsonb", Kind::Simple,
     #[doc="ANYRANGE"]
-    3831 => Anyrange: Kind::Simple,
+    3831 => Anyrange: "anyrange", Kind::Simple,
     #[doc="JSONB[]"]
-    3807 => JsonbArray: Kind::Array(Type::Jsonb),
+    3807 => JsonbArray: "_jsonb", Kind::Array(Type::Jsonb),
     #[doc="INT4RANGE - range of integers"]
-    3904 => Int4Range: Kind::Range(Type::Int4),
+    3904 => Int4Range: "int4range", Kind::Range(Type::Int4),
     #[doc="INT4RANGE[]"]
-    3905 => Int4RangeArray: Kind::Array(Type::Int4Range),
+    3905 => Int4RangeArray: "_int4range", Kind::Array(Type::Int4Range),
     #[doc="NUMRANGE - range of numerics"]
-    3906 => NumRange: Kind::Range(Type::Numeric),
+    3906 => NumRange: "numrange", Kind::Range(Type::Numeric),
     #[doc="NUMRANGE[]"]
-    3907 => NumRangeArray: Kind::Array(Type::NumRange),
+    3907 => NumRangeArray: "_numrange", Kind::Array(Type::NumRange),
     #[doc="TSRANGE - range of timestamps without time zone"]
-    3908 => TsRange: Kind::Range(Type::Timestamp),
+    3908 => TsRange: "tsrange", Kind::Range(Type::Timestamp),
     #[doc="TSRANGE[]"]
-    3909 => TsRangeArray: Kind::Array(Type::TsRange),
+    3909 => TsRangeArray: "_tsrange", Kind::Array(Type::TsRange),
     #[doc="TSTZRANGE - range of timestamps with time zone"]
-    3910 => TstzRange: Kind::Range(Type::TimestampTZ),
+    3910 => TstzRange: "tstzrange", Kind::Range(Type::TimestampTZ),
     #[doc="TSTZRANGE[]"]
-    3911 => TstzRangeArray: Kind::Array(Type::TstzRange),
+    3911 => TstzRangeArray: "_tstzrange", Kind::Array(Type::TstzRange),
     #[doc="DATERANGE - range of dates"]
-    3912 => DateRange: Kind::Range(Type::Date),
+    3912 => DateRange: "daterange", Kind::Range(Type::Date),
     #[doc="DATERANGE[]"]
-    3913 => DateRangeArray: Kind::Array(Type::DateRange),
+    3913 => DateRangeArray: "_daterange", Kind::Array(Type::DateRange),
     #[doc="INT8RANGE - range of bigints"]
-    3926 => Int8Range: Kind::Range(Type::Int8),
+    3926 => Int8Range: "int8range", Kind::Range(Type::Int8),
     #[doc="INT8RANGE[]"]
-    3927 => Int8RangeArray: Kind::Array(Type::Int8Range),
+    3927 => Int8RangeArray: "_int8range", Kind::Array(Type::Int8Range),
     #[doc="EVENT_TRIGGER"]
-    3838 => EventTrigger: Kind::Simple
+    3838 => EventTrigger: "event_trigger", Kind::Simple
 }
 
 /// Information about an unknown type.
@@ -562,12 +574,12 @@ impl error::Error for WasNull {
 /// crates. These are disabled by default; to opt into one of these
 /// implementations, activate the Cargo feature corresponding to the crate's
 /// name. For example, the `serde` feature enables the implementation for the
-/// `serde::json::Value` type.
+/// `serde_json::Value` type.
 ///
 /// | Rust type                           | Postgres type(s)                    |
 /// |-------------------------------------|-------------------------------------|
 /// | serialize::json::Json               | JSON, JSONB                         |
-/// | serde::json::Value                  | JSON, JSONB                         |
+/// | serde_json::Value                   | JSON, JSONB                         |
 /// | time::Timespec                      | TIMESTAMP, TIMESTAMP WITH TIME ZONE |
 /// | chrono::NaiveDateTime               | TIMESTAMP                           |
 /// | chrono::DateTime&lt;UTC&gt;         | TIMESTAMP WITH TIME ZONE            |
@@ -769,12 +781,12 @@ pub enum IsNull {
 /// crates. These are disabled by default; to opt into one of these
 /// implementations, activate the Cargo feature corresponding to the crate's
 /// name. For example, the `serde` feature enables the implementation for the
-/// `serde::json::Value` type.
+/// `serde_json::Value` type.
 ///
 /// | Rust type                           | Postgres type(s)                    |
 /// |-------------------------------------|-------------------------------------|
 /// | serialize::json::Json               | JSON, JSONB                         |
-/// | serde::json::Value                  | JSON, JSONB                         |
+/// | serde_json::Value                   | JSON, JSONB                         |
 /// | time::Timespec                      | TIMESTAMP, TIMESTAMP WITH TIME ZONE |
 /// | chrono::NaiveDateTime               | TIMESTAMP                           |
 /// | chrono::DateTime&lt;UTC&gt;         | TIMESTAMP WITH TIME ZONE            |
@@ -812,6 +824,14 @@ pub trait ToSql: fmt::Debug {
     /// `to_sql_checked!()` macro.
     fn to_sql_checked(&self, ty: &Type, out: &mut Write, ctx: &SessionInfo)
                       -> Result<IsNull>;
+
+    /// Returns the name of the Rust type of `self`, for use in error
+    /// messages.
+    ///
+    /// Implementations should not override this method.
+    fn rust_type_name(&self) -> &'static str {
+        ::std::any::type_name::<Self>()
+    }
 }
 
 impl<'a, T> ToSql for &'a T where T: ToSql {
@@ -1,9 +1,7 @@
-extern crate serde;
-
 use std::error;
 use std::io::prelude::*;
 use byteorder::{ReadBytesExt, WriteBytesExt};
-use self::serde::json::{self, Value};
+use serde_json::{self, Value};
 
 use Result;
 use error::Error;
@@ -18,7 +16,7 @@ impl FromSql for Value {
                 return Err(Error::Conversion(err));
             }
         }
-        json::de::from_reader(raw).map_err(|err| Error::Conversion(Box::new(err)))
+        serde_json::from_reader(raw).map_err(|err| Error::Conversion(Box::new(err)))
     }
 
     accepts!(Type::Json, Type::Jsonb);
@@ -31,7 +29,7 @@ impl ToSql for Value {
             try!(out.write_u8(1));
         }
 
-        try!(write!(out, "{:?}", self));
+        try!(write!(out, "{}", self));
 
         Ok(IsNull::No)
     }
@@ -1,10 +1,6 @@
 use std::io;
 use std::io::prelude::*;
 
-pub fn parse_update_count(tag: String) -> u64 {
-    tag.split(' ').last().unwrap().parse().unwrap_or(0)
-}
-
 pub fn read_all<R: Read>(r: &mut R, mut buf: &mut [u8]) -> io::Result<()> {
     let mut start = 0;
     while start != buf.len() {
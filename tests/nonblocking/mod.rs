@@ -0,0 +1,269 @@
+use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use postgres::error::Error;
+use postgres::error::SqlState::UndefinedTable;
+use postgres::nonblocking::{Async, Connection, Operation};
+use postgres::types::Type;
+
+// A real event loop would wait on the socket instead of spinning.
+fn wait<O: Operation>(conn: &mut Connection, mut op: O) -> postgres::Result<O::Item> {
+    loop {
+        match try!(op.poll(conn)) {
+            Async::Ready(item) => return Ok(item),
+            Async::NotReady => thread::sleep(Duration::from_millis(1)),
+        }
+    }
+}
+
+fn connect(url: &str) -> Connection {
+    let mut connect = or_panic!(Connection::connect(url));
+    loop {
+        match or_panic!(connect.poll()) {
+            Async::Ready(conn) => return conn,
+            Async::NotReady => thread::sleep(Duration::from_millis(1)),
+        }
+    }
+}
+
+#[test]
+fn test_nonblocking_query() {
+    let mut conn = connect("postgres://postgres@localhost");
+    assert!(conn.parameter("server_version").is_some());
+
+    let op = or_panic!(conn.prepare("SELECT $1::INT, $2::TEXT[]"));
+    let stmt = or_panic!(wait(&mut conn, op));
+    assert_eq!(&[Type::Int4, Type::TextArray], stmt.param_types());
+
+    let op = or_panic!(conn.prepare("SELECT $1::INT + 1 AS n, NULL::TEXT"));
+    let stmt = or_panic!(wait(&mut conn, op));
+    assert_eq!("n", stmt.columns()[0].name());
+    let op = or_panic!(stmt.query(&mut conn, &[&41i32]));
+    let rows = or_panic!(wait(&mut conn, op));
+    assert_eq!(1, rows.len());
+    assert_eq!(42i32, rows[0].get("n"));
+    assert_eq!(None::<String>, rows[0].get(1));
+
+    let op = or_panic!(stmt.close(&mut conn));
+    or_panic!(wait(&mut conn, op));
+}
+
+#[test]
+fn test_nonblocking_unknown_types() {
+    let mut conn = connect("postgres://postgres@localhost");
+    let op = or_panic!(conn.batch_execute("CREATE TYPE pg_temp.nb_mood AS ENUM ('happy', 'sad')"));
+    or_panic!(wait(&mut conn, op));
+
+    let op = or_panic!(conn.prepare("SELECT $1::nb_mood[]"));
+    let stmt = or_panic!(wait(&mut conn, op));
+    match stmt.param_types()[0] {
+        Type::Other(ref other) => assert_eq!("_nb_mood", other.name()),
+        ref ty => panic!("unexpected type {:?}", ty),
+    }
+}
+
+#[test]
+fn test_nonblocking_errors() {
+    let mut conn = connect("postgres://postgres@localhost");
+    let op = or_panic!(conn.prepare("SELECT * FROM nb_no_such_table"));
+    match wait(&mut conn, op) {
+        Err(Error::DbError(ref e)) if e.code() == &UndefinedTable => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+
+    let op = or_panic!(conn.batch_execute("SELECT 1"));
+    assert!(conn.batch_execute("SELECT 2").is_err());
+    or_panic!(wait(&mut conn, op));
+    assert!(!conn.is_busy());
+}
+
+#[test]
+fn test_nonblocking_transaction() {
+    let mut conn = connect("postgres://postgres@localhost");
+    let op = or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT)"));
+    or_panic!(wait(&mut conn, op));
+    let op = or_panic!(conn.prepare("INSERT INTO foo (id) VALUES ($1)"));
+    let insert = or_panic!(wait(&mut conn, op));
+
+    let op = or_panic!(conn.transaction());
+    let trans = or_panic!(wait(&mut conn, op));
+    assert!(!conn.is_active());
+    let op = or_panic!(insert.execute(&mut conn, &[&1i32]));
+    assert_eq!(1, or_panic!(wait(&mut conn, op)));
+
+    let op = or_panic!(trans.transaction(&mut conn));
+    let nested = or_panic!(wait(&mut conn, op));
+    assert!(!trans.is_active(&conn));
+    let op = or_panic!(insert.execute(&mut conn, &[&2i32]));
+    or_panic!(wait(&mut conn, op));
+    let op = or_panic!(nested.rollback(&mut conn));
+    or_panic!(wait(&mut conn, op));
+    assert!(trans.is_active(&conn));
+
+    let op = or_panic!(trans.commit(&mut conn));
+    or_panic!(wait(&mut conn, op));
+    assert!(conn.is_active());
+
+    let op = or_panic!(conn.prepare("SELECT id FROM foo"));
+    let stmt = or_panic!(wait(&mut conn, op));
+    let op = or_panic!(stmt.query(&mut conn, &[]));
+    let ids: Vec<i32> = or_panic!(wait(&mut conn, op)).iter().map(|r| r.get(0)).collect();
+    assert_eq!(vec![1], ids);
+}
+
+#[test]
+fn test_nonblocking_copy() {
+    let mut conn = connect("postgres://postgres@localhost");
+    let op = or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT, name TEXT)"));
+    or_panic!(wait(&mut conn, op));
+
+    let op = or_panic!(conn.prepare("COPY foo (id, name) FROM STDIN"));
+    let stmt = or_panic!(wait(&mut conn, op));
+    let mut copy = or_panic!(stmt.copy_in(&mut conn, &[]));
+    copy.write(b"1\tjoe\n2\t");
+    copy.write(b"bob\n");
+    copy.finish();
+    assert_eq!(2, or_panic!(wait(&mut conn, copy)));
+
+    let mut copy = or_panic!(stmt.copy_in(&mut conn, &[]));
+    copy.write(b"3\tsam\n");
+    copy.fail("never mind");
+    assert!(wait(&mut conn, copy).is_err());
+
+    let op = or_panic!(conn.prepare("COPY (SELECT id, name FROM foo ORDER BY id) TO STDOUT"));
+    let stmt = or_panic!(wait(&mut conn, op));
+    let mut copy = or_panic!(stmt.copy_out(&mut conn, &[]));
+    let mut data = vec![];
+    while let Some(chunk) = or_panic!(wait(&mut conn, &mut copy)) {
+        data.extend_from_slice(&chunk);
+    }
+    assert_eq!(&b"1\tjoe\n2\tbob\n"[..], &data[..]);
+    assert_eq!(2, copy.rows());
+
+    let op = or_panic!(conn.prepare("SELECT 1"));
+    let stmt = or_panic!(wait(&mut conn, op));
+    let copy = or_panic!(stmt.copy_in(&mut conn, &[]));
+    assert!(wait(&mut conn, copy).is_err());
+    assert!(!conn.is_desynchronized());
+}
+
+#[test]
+fn test_nonblocking_execute_copy() {
+    let mut conn = connect("postgres://postgres@localhost");
+    let op = or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT)"));
+    or_panic!(wait(&mut conn, op));
+
+    let op = or_panic!(conn.prepare("COPY foo (id) FROM STDIN"));
+    let stmt = or_panic!(wait(&mut conn, op));
+    let op = or_panic!(stmt.execute(&mut conn, &[]));
+    assert!(wait(&mut conn, op).is_err());
+    let op = or_panic!(stmt.query(&mut conn, &[]));
+    assert!(wait(&mut conn, op).is_err());
+
+    let op = or_panic!(conn.batch_execute("COPY foo (id) FROM STDIN"));
+    assert!(wait(&mut conn, op).is_err());
+
+    let op = or_panic!(conn.prepare("SELECT 1"));
+    let stmt = or_panic!(wait(&mut conn, op));
+    let op = or_panic!(stmt.query(&mut conn, &[]));
+    assert_eq!(1, or_panic!(wait(&mut conn, op)).len());
+    assert!(!conn.is_desynchronized());
+}
+
+#[test]
+fn test_nonblocking_notifications() {
+    let mut conn = connect("postgres://postgres@localhost");
+    let op = or_panic!(conn.batch_execute("LISTEN nb_channel"));
+    or_panic!(wait(&mut conn, op));
+    assert_eq!(Async::NotReady, or_panic!(conn.poll_notification()).map(|_| ()));
+
+    let mut other = connect("postgres://postgres@localhost");
+    let op = or_panic!(other.batch_execute("NOTIFY nb_channel, 'hello'"));
+    or_panic!(wait(&mut other, op));
+
+    let notification = loop {
+        match or_panic!(conn.poll_notification()) {
+            Async::Ready(notification) => break notification,
+            Async::NotReady => thread::sleep(Duration::from_millis(1)),
+        }
+    };
+    assert_eq!("nb_channel", notification.channel);
+    assert_eq!("hello", notification.payload);
+}
+
+pub fn message(tag: u8, body: &[u8]) -> Vec<u8> {
+    let len = body.len() as u32 + 4;
+    let mut buf = vec![tag, (len >> 24) as u8, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+    buf.extend_from_slice(body);
+    buf
+}
+
+// Reads a startup message or a regular frontend message.
+pub fn read_message(stream: &mut TcpStream, startup: bool) -> Vec<u8> {
+    let mut header = vec![0; if startup { 4 } else { 5 }];
+    stream.read_exact(&mut header).unwrap();
+    let len = header[header.len() - 4..].iter().fold(0, |len, &b| len << 8 | b as usize);
+    let mut body = vec![0; len - 4];
+    stream.read_exact(&mut body).unwrap();
+    header.extend(body);
+    header
+}
+
+#[test]
+fn test_nonblocking_fake_server() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server = thread::spawn(move || {
+        let mut stream = listener.accept().unwrap().0;
+        read_message(&mut stream, true);
+
+        let mut out = vec![];
+        out.extend(message(b'R', &[0, 0, 0, 0]));
+        out.extend(message(b'S', b"server_version\x009.4.0\x00"));
+        out.extend(message(b'K', &[0, 0, 0, 7, 0, 0, 0, 9]));
+        out.extend(message(b'Z', b"I"));
+        // Trickle the handshake out to exercise partial reads
+        for byte in out {
+            stream.write_all(&[byte]).unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let query = read_message(&mut stream, false);
+        assert_eq!(b'Q', query[0]);
+        assert_eq!(&b"LISTEN foo\x00"[..], &query[5..]);
+        let mut out = vec![];
+        out.extend(message(b'C', b"LISTEN\x00"));
+        out.extend(message(b'Z', b"I"));
+        stream.write_all(&out).unwrap();
+
+        let notification = message(b'A', b"\x00\x00\x00\x07foo\x00bar\x00");
+        let (first, second) = notification.split_at(6);
+        stream.write_all(first).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        stream.write_all(second).unwrap();
+
+        assert_eq!(b'X', read_message(&mut stream, false)[0]);
+    });
+
+    let mut conn = connect(&format!("postgres://user@127.0.0.1:{}", port));
+    assert_eq!(Some("9.4.0".to_owned()), conn.parameter("server_version"));
+    assert_eq!(7, conn.cancel_data().process_id);
+
+    let op = or_panic!(conn.batch_execute("LISTEN foo"));
+    or_panic!(wait(&mut conn, op));
+    let notification = loop {
+        match or_panic!(conn.poll_notification()) {
+            Async::Ready(notification) => break notification,
+            Async::NotReady => thread::sleep(Duration::from_millis(1)),
+        }
+    };
+    assert_eq!(7, notification.pid);
+    assert_eq!("foo", notification.channel);
+    assert_eq!("bar", notification.payload);
+
+    drop(conn);
+    server.join().unwrap();
+}
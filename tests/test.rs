@@ -1,30 +1,52 @@
+#[macro_use]
 extern crate postgres;
 extern crate rustc_serialize as serialize;
 extern crate url;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde_derive;
 #[cfg(feature = "openssl")]
 extern crate openssl;
 
 #[cfg(feature = "openssl")]
 use openssl::ssl::{SslContext, SslMethod};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::io;
 use std::io::prelude::*;
+use std::net::{TcpListener, TcpStream};
 
 use postgres::{HandleNotice,
+               DEFAULT_STATEMENT_CACHE_CAPACITY,
+               StatementCacheStats,
+               CommandKind,
                Notification,
                Connection,
                GenericConnection,
                SslMode,
                IntoConnectParams,
-               IsolationLevel};
-use postgres::error::{Error, ConnectError, DbError};
-use postgres::types::{Type, Kind};
+               IsolationLevel,
+               QueryProtocol,
+               Compatibility,
+               SharedConnection,
+               Statement};
+use postgres::stmt::{CursorOptions, Format};
+use postgres::pipeline::PipelineResult;
+use postgres::rows::{Rows, DropBehavior, FromRow};
+use postgres::error::{Error, ConnectError, DbError, ValueLocation};
+use postgres::types::{Type, Kind, ToSql, Oid};
 use postgres::error::SqlState::{SyntaxError,
                                 QueryCanceled,
                                 UndefinedTable,
                                 InvalidCatalogName,
                                 InvalidPassword,
-                                CardinalityViolation};
+                                CardinalityViolation,
+                                UniqueViolation,
+                                InFailedSqlTransaction,
+                                UndefinedObject,
+                                InvalidSqlStatementName,
+                                FeatureNotSupported};
 use postgres::error::ErrorPosition::Normal;
 use postgres::rows::RowIndex;
 
@@ -37,6 +59,7 @@ macro_rules! or_panic {
     )
 }
 
+mod nonblocking;
 mod types;
 
 #[test]
@@ -279,20 +302,26 @@ fn test_nested_transactions_finish() {
 }
 
 #[test]
-#[should_panic(expected = "active transaction")]
 fn test_conn_trans_when_nested() {
     let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
     let _trans = or_panic!(conn.transaction());
-    conn.transaction().unwrap();
+    let res = conn.transaction();
+    match res {
+        Err(Error::Misuse(ref msg)) if msg.contains("active transaction") => {}
+        res => panic!("unexpected result {:?}", res),
+    }
 }
 
 #[test]
-#[should_panic(expected = "active transaction")]
 fn test_trans_with_nested_trans() {
     let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
     let trans = or_panic!(conn.transaction());
     let _trans2 = or_panic!(trans.transaction());
-    trans.transaction().unwrap();
+    let res = trans.transaction();
+    match res {
+        Err(Error::Misuse(ref msg)) if msg.contains("active transaction") => {}
+        res => panic!("unexpected result {:?}", res),
+    }
 }
 
 #[test]
@@ -403,14 +432,17 @@ fn test_lazy_query() {
 }
 
 #[test]
-#[should_panic(expected = "same `Connection` as")]
 fn test_lazy_query_wrong_conn() {
     let conn1 = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
     let conn2 = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
 
     let trans = or_panic!(conn1.transaction());
     let stmt = or_panic!(conn2.prepare("SELECT 1::INT"));
-    stmt.lazy_query(&trans, &[], 1).unwrap();
+    let res = stmt.lazy_query(&trans, &[], 1);
+    match res {
+        Err(Error::Misuse(ref msg)) if msg.contains("same `Connection` as") => {}
+        res => panic!("unexpected result {:?}", res),
+    }
 }
 
 #[test]
@@ -449,23 +481,36 @@ fn test_execute_counts() {
 fn test_wrong_param_type() {
     let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
     match conn.execute("SELECT $1::VARCHAR", &[&1i32]) {
-        Err(Error::WrongType(_)) => {}
+        Err(Error::Value(ref e)) => {
+            assert_eq!(&ValueLocation::Parameter(0), e.location());
+            assert_eq!(&Type::Varchar, e.pg_type());
+            assert_eq!("i32", e.rust_type());
+            match *e.error() {
+                Error::WrongType(Type::Varchar) => {}
+                ref e => panic!("unexpected error {:?}", e),
+            }
+        }
         res => panic!("unexpected result {:?}", res)
     }
 }
 
 #[test]
-#[should_panic(expected = "expected 2 parameters but got 1")]
 fn test_too_few_params() {
     let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
-    let _ = conn.execute("SELECT $1::INT, $2::INT", &[&1i32]);
+    match conn.execute("SELECT $1::INT, $2::INT", &[&1i32]) {
+        Err(Error::Misuse(ref msg)) if msg == "expected 2 parameters but got 1" => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    or_panic!(conn.execute("SELECT 1", &[]));
 }
 
 #[test]
-#[should_panic(expected = "expected 2 parameters but got 3")]
 fn test_too_many_params() {
     let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
-    let _ = conn.execute("SELECT $1::INT, $2::INT", &[&1i32, &2i32, &3i32]);
+    match conn.execute("SELECT $1::INT, $2::INT", &[&1i32, &2i32, &3i32]) {
+        Err(Error::Misuse(ref msg)) if msg == "expected 2 parameters but got 3" => {}
+        res => panic!("unexpected result {:?}", res),
+    }
 }
 
 #[test]
@@ -506,7 +551,12 @@ fn test_get_was_null() {
     let result = or_panic!(stmt.query(&[]));
 
     match result.iter().next().unwrap().get_opt::<usize, i32>(0) {
-        Err(Error::Conversion(..)) => {}
+        Err(Error::Value(ref e)) => {
+            match *e.error() {
+                Error::Conversion(..) => {}
+                ref e => panic!("unexpected error {:?}", e),
+            }
+        }
         res => panic!("unexpected result {:?}", res),
     };
 }
@@ -806,6 +856,7 @@ fn test_copy_out_partial_read() {
 fn test_generic_connection() {
     fn f<T>(t: &T) where T: GenericConnection {
         or_panic!(t.execute("SELECT 1", &[]));
+        assert_eq!(1, or_panic!(t.query_scalar::<i32>("SELECT 1", &[])));
     }
 
     let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
@@ -893,7 +944,16 @@ fn test_get_opt_wrong_type() {
     let res = stmt.query(&[]).unwrap();
     match res.iter().next().unwrap().get_opt::<_, String>(0) {
         Ok(_) => panic!("unexpected success"),
-        Err(Error::WrongType(Type::Int4)) => {}
+        Err(Error::Value(ref e)) => {
+            let location = ValueLocation::Column { index: 0, name: "int4".to_owned() };
+            assert_eq!(&location, e.location());
+            assert_eq!(&Type::Int4, e.pg_type());
+            assert!(e.rust_type().ends_with("String"));
+            match *e.error() {
+                Error::WrongType(Type::Int4) => {}
+                ref e => panic!("unexpected error {:?}", e),
+            }
+        }
         Err(e) => panic!("unexpected error {}", e),
     }
 }
@@ -937,9 +997,1369 @@ fn test_row_case_insensitive() {
     let conn = Connection::connect("postgres://postgres@localhost", &SslMode::None).unwrap();
     conn.batch_execute("CREATE TEMPORARY TABLE foo (foo INT, \"bAr\" INT, \"Bar\" INT);").unwrap();
     let stmt = conn.prepare("SELECT * FROM foo").unwrap();
-    assert_eq!(Some(0), "foo".idx(&stmt));
-    assert_eq!(Some(0), "FOO".idx(&stmt));
-    assert_eq!(Some(1), "bar".idx(&stmt));
-    assert_eq!(Some(1), "bAr".idx(&stmt));
-    assert_eq!(Some(2), "Bar".idx(&stmt));
+    assert_eq!(Some(0), "foo".idx(stmt.columns()));
+    assert_eq!(Some(0), "FOO".idx(stmt.columns()));
+    assert_eq!(Some(1), "bar".idx(stmt.columns()));
+    assert_eq!(Some(1), "bAr".idx(stmt.columns()));
+    assert_eq!(Some(2), "Bar".idx(stmt.columns()));
+}
+
+#[test]
+fn test_simple_query_protocol() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    conn.set_query_protocol(QueryProtocol::Simple);
+    or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT, name TEXT, data BYTEA)"));
+    let stmt = or_panic!(conn.prepare("INSERT INTO foo (id, name, data) VALUES ($1, $2, $3)"));
+    assert_eq!(1, or_panic!(stmt.execute(&[&1i32, &"it's a \\ test", &vec![0u8, 39, 92]])));
+    assert_eq!(1, or_panic!(conn.execute("INSERT INTO foo (id, name) VALUES ($1, $2)",
+                                         &[&2i32, &None::<String>])));
+
+    let stmt = or_panic!(conn.prepare("SELECT id, name, data, '$1' AS \"$1\" FROM foo \
+                                       WHERE id > $1 -- $2
+                                       ORDER BY id"));
+    let rows = or_panic!(stmt.query(&[&0i64]));
+    assert_eq!(4, rows.columns().len());
+    assert_eq!(&Type::Int4, rows.columns()[0].type_());
+    assert_eq!(vec![(1, Some("it's a \\ test".to_owned()), Some(vec![0u8, 39, 92])),
+                    (2, None, None)],
+               rows.iter()
+                   .map(|r| (r.get::<_, i32>(0), r.get(1), r.get(2)))
+                   .collect::<Vec<_>>());
+    assert_eq!("$1", rows.get(0).get::<_, String>("$1"));
+
+    let stmt = or_panic!(conn.prepare("SELECT $1::FLOAT8[], $2::TIMESTAMP, $3::DATE"));
+    let rows = or_panic!(stmt.query(&[&"{1.5,NULL,3}", &"2015-02-03 04:05:06.07 BC",
+                                      &"1999-12-31"]));
+    assert_eq!(&Type::Float8Array, rows.columns()[0].type_());
+    assert_eq!("2015-02-03 04:05:06.07 BC",
+               or_panic!(conn.prepare("SELECT $1::TIMESTAMP::TEXT"))
+                   .query(&[&"2015-02-03 04:05:06.07 BC"]).unwrap().get(0).get::<_, String>(0));
+}
+
+#[test]
+fn test_simple_query_protocol_copy() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    conn.set_query_protocol(QueryProtocol::Simple);
+    or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT)"));
+    let stmt = or_panic!(conn.prepare("COPY foo (id) FROM STDIN"));
+    assert_eq!(2, or_panic!(stmt.copy_in(&[], &mut &b"1\n2\n"[..])));
+
+    let stmt = or_panic!(conn.prepare("COPY foo (id) TO STDOUT"));
+    let mut buf = vec![];
+    let mut reader = or_panic!(stmt.copy_out(&[]));
+    or_panic!(reader.read_to_end(&mut buf));
+    or_panic!(reader.finish());
+    assert_eq!(&b"1\n2\n"[..], &*buf);
+
+    let stmt = or_panic!(conn.prepare("SELECT $1::INT"));
+    match stmt.query(&[&"a"]) {
+        Err(Error::DbError(..)) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    assert_eq!(3, or_panic!(conn.prepare("SELECT $1::INT")).query(&[&3i32]).unwrap()
+                     .get(0).get::<_, i32>(0));
+}
+
+#[test]
+fn test_simple_query_protocol_unsupported_type() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    conn.set_query_protocol(QueryProtocol::Simple);
+    match conn.query("SELECT 1.5::NUMERIC", &[]) {
+        Err(Error::Conversion(..)) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    assert_eq!("1.5", or_panic!(conn.query("SELECT 1.5::NUMERIC::TEXT", &[])).get(0)
+                          .get::<_, String>(0));
+}
+
+#[test]
+fn test_lenient_compatibility() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    assert_eq!(Compatibility::Strict, conn.compatibility());
+    conn.set_compatibility(Compatibility::Lenient);
+    or_panic!(conn.batch_execute("CREATE TYPE pg_temp.mood AS ENUM ('sad', 'happy')"));
+    let stmt = or_panic!(conn.prepare("SELECT 'happy'::mood"));
+    match *stmt.columns()[0].type_() {
+        Type::Other(ref other) => assert_eq!("mood", other.name()),
+        ref t => panic!("unexpected type {:?}", t),
+    }
+
+    match conn.execute("SELECT 1/0", &[]) {
+        Err(Error::DbError(ref e)) => {
+            assert!(e.file().is_some());
+            assert!(e.line().is_some());
+            assert!(e.routine().is_some());
+        }
+        res => panic!("unexpected result {:?}", res),
+    }
+}
+
+// Answers simple queries for a column of type `type_oid`, whose definition is
+// looked up with the response in `type_info`.
+fn fake_lenient_query(stream: &mut TcpStream, type_oid: u32, type_info: &[u8]) {
+    let query = nonblocking::read_message(stream, false);
+    assert_eq!(&b"SELECT v\x00"[..], &query[5..]);
+    let mut description = b"\x00\x01v\x00\x00\x00\x00\x00\x00\x00".to_vec();
+    description.extend_from_slice(&[(type_oid >> 24) as u8, (type_oid >> 16) as u8,
+                                    (type_oid >> 8) as u8, type_oid as u8]);
+    description.extend_from_slice(b"\xff\xff\xff\xff\xff\xff\x00\x00");
+    let mut out = vec![];
+    out.extend(nonblocking::message(b'T', &description));
+    out.extend(nonblocking::message(b'D', b"\x00\x01\x00\x00\x00\x03abc"));
+    out.extend(nonblocking::message(b'C', b"SELECT 1\x00"));
+    out.extend(nonblocking::message(b'Z', b"I"));
+    stream.write_all(&out).unwrap();
+
+    assert_eq!(b'Q', nonblocking::read_message(stream, false)[0]);
+    stream.write_all(type_info).unwrap();
+}
+
+fn fake_error(code: &str) -> Vec<u8> {
+    let mut out = nonblocking::message(b'E', format!("SERROR\x00C{}\x00Mfailed\x00\x00", code)
+                                                 .as_bytes());
+    out.extend(nonblocking::message(b'Z', b"I"));
+    out
+}
+
+#[test]
+fn test_lenient_compatibility_fallback() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server = thread::spawn(move || {
+        let mut stream = listener.accept().unwrap().0;
+        nonblocking::read_message(&mut stream, true);
+        let mut out = vec![];
+        out.extend(nonblocking::message(b'R', &[0, 0, 0, 0]));
+        out.extend(nonblocking::message(b'S', b"client_encoding\x00UTF8\x00"));
+        out.extend(nonblocking::message(b'Z', b"I"));
+        stream.write_all(&out).unwrap();
+
+        // The catalogs are missing, so a placeholder type is used
+        fake_lenient_query(&mut stream, 90000, &fake_error("42P01"));
+        assert_eq!(b'Q', nonblocking::read_message(&mut stream, false)[0]);
+        stream.write_all(&fake_error("42P01")).unwrap();
+
+        // The placeholder isn't cached, so the type is looked up again
+        let mut info = vec![];
+        info.extend(nonblocking::message(b'D', b"\x00\x03\x00\x00\x00\x04mood\
+                                                 \x00\x00\x00\x010\xff\xff\xff\xff"));
+        info.extend(nonblocking::message(b'C', b"SELECT 1\x00"));
+        info.extend(nonblocking::message(b'Z', b"I"));
+        fake_lenient_query(&mut stream, 90000, &info);
+
+        // Other errors aren't replaced by a placeholder
+        fake_lenient_query(&mut stream, 90001, &fake_error("25P02"));
+        assert_eq!(b'Q', nonblocking::read_message(&mut stream, false)[0]);
+        stream.write_all(&fake_error("25P02")).unwrap();
+
+        assert_eq!(b'X', nonblocking::read_message(&mut stream, false)[0]);
+    });
+
+    let conn = or_panic!(Connection::connect(&*format!("postgres://user@127.0.0.1:{}", port),
+                                             &SslMode::None));
+    conn.set_query_protocol(QueryProtocol::Simple);
+    conn.set_compatibility(Compatibility::Lenient);
+
+    let rows = or_panic!(conn.query("SELECT v", &[]));
+    match *rows.columns()[0].type_() {
+        Type::Other(ref other) => {
+            assert_eq!("90000", other.name());
+            assert_eq!(&Kind::Simple, other.kind());
+        }
+        ref t => panic!("unexpected type {:?}", t),
+    }
+    assert_eq!("abc", rows.get(0).get::<_, String>(0));
+
+    let rows = or_panic!(conn.query("SELECT v", &[]));
+    match *rows.columns()[0].type_() {
+        Type::Other(ref other) => assert_eq!("mood", other.name()),
+        ref t => panic!("unexpected type {:?}", t),
+    }
+
+    match conn.query("SELECT v", &[]) {
+        Err(Error::DbError(ref e)) if e.code() == &InFailedSqlTransaction => {
+            // The server didn't report where the error was raised
+            assert_eq!(None, e.file());
+            assert_eq!(None, e.line());
+            assert_eq!(None, e.routine());
+        }
+        res => panic!("unexpected result {:?}", res),
+    }
+
+    drop(conn);
+    server.join().unwrap();
+}
+
+#[test]
+fn test_shared_connection() {
+    let conn = Arc::new(or_panic!(Connection::connect("postgres://postgres@localhost",
+                                                      &SslMode::None)));
+    let threads = (0..4i32).map(|i| {
+        let conn = conn.clone();
+        thread::spawn(move || {
+            let stmt = or_panic!(conn.prepare("SELECT $1::INT"));
+            for _ in 0..10 {
+                let rows = or_panic!(stmt.query(&[&i]));
+                assert_eq!(i, rows.get(0).get::<_, i32>(0));
+            }
+        })
+    }).collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
+#[test]
+fn test_reentrant_use_errors() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let stmt = or_panic!(conn.prepare("COPY (SELECT 1) TO STDOUT"));
+    let reader = or_panic!(stmt.copy_out(&[]));
+    match conn.execute("SELECT 1", &[]) {
+        Err(Error::Misuse(ref msg)) if msg.contains("already in use") => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    assert!(conn.notifications().next().is_none());
+    conn.cancel_data();
+
+    // Accessors and settings don't need the connection
+    assert_eq!(Some("UTF8".to_owned()), conn.parameter("client_encoding"));
+    assert!(conn.is_active());
+    assert!(!conn.is_desynchronized());
+    conn.set_compatibility(Compatibility::Lenient);
+    assert_eq!(Compatibility::Lenient, conn.compatibility());
+    conn.set_statement_cache_capacity(1);
+    assert_eq!(1, conn.statement_cache_capacity());
+
+    or_panic!(reader.finish());
+    or_panic!(conn.prepare_cached("SELECT 1"));
+    or_panic!(conn.prepare_cached("SELECT 2"));
+    assert_eq!(1, conn.statement_cache_stats().len);
+    or_panic!(conn.execute("SELECT 1", &[]));
+}
+
+struct OwnedHandles {
+    stmt: Statement<'static>,
+    conn: Arc<Connection>,
+}
+
+fn query_ids(conn: &Connection) -> Rows {
+    let stmt = or_panic!(conn.prepare("SELECT id FROM foo ORDER BY id"));
+    or_panic!(stmt.query(&[]))
+}
+
+#[test]
+fn test_transaction_connection_outlives_transaction() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let conn_ref = {
+        let trans = or_panic!(conn.transaction());
+        trans.connection()
+    };
+    assert!(conn_ref.is_active());
+}
+
+#[test]
+fn test_owned_handles() {
+    let conn = Arc::new(or_panic!(Connection::connect("postgres://postgres@localhost",
+                                                      &SslMode::None)));
+    or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT)"));
+    let handles = OwnedHandles {
+        stmt: or_panic!(conn.prepare_owned("INSERT INTO foo (id) VALUES ($1)")),
+        conn: conn.clone(),
+    };
+    drop(conn);
+
+    let trans = or_panic!(handles.conn.transaction_owned());
+    assert!(!trans.connection().is_active());
+    or_panic!(handles.stmt.execute(&[&1i32]));
+    or_panic!(trans.prepare_cached("INSERT INTO foo (id) VALUES ($1)")).execute(&[&2i32]).unwrap();
+    or_panic!(trans.commit());
+
+    let rows: Rows<'static> = {
+        let stmt = or_panic!(handles.conn.prepare_owned("SELECT id FROM foo ORDER BY id"));
+        or_panic!(stmt.query(&[]))
+    };
+    assert_eq!(vec![1i32, 2], rows.iter().map(|r| r.get(0)).collect::<Vec<i32>>());
+    assert_eq!(vec![1i32, 2],
+               query_ids(&handles.conn).into_iter().map(|r| r.get(0)).collect::<Vec<i32>>());
+}
+
+#[test]
+fn test_pipeline() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT PRIMARY KEY)"));
+    let insert = or_panic!(conn.prepare("INSERT INTO foo (id) VALUES ($1)"));
+    let select = or_panic!(conn.prepare("SELECT id FROM foo ORDER BY id"));
+
+    let mut pipeline = conn.pipeline();
+    pipeline.execute(&insert, &[&1i32])
+            .execute(&insert, &[&1i32])
+            .execute(&insert, &[&2i32])
+            .query(&select, &[]);
+    assert_eq!(4, pipeline.len());
+    let mut results = or_panic!(pipeline.run()).into_iter();
+
+    match results.next().unwrap() {
+        Ok(PipelineResult::Execute(1)) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    match results.next().unwrap() {
+        Err(Error::DbError(ref e)) if e.code() == &UniqueViolation => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    match results.next().unwrap() {
+        Ok(PipelineResult::Execute(1)) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    match results.next().unwrap() {
+        Ok(PipelineResult::Query(rows)) => {
+            assert_eq!(vec![1, 2], rows.iter().map(|r| r.get(0)).collect::<Vec<i32>>());
+        }
+        res => panic!("unexpected result {:?}", res),
+    }
+    assert!(results.next().is_none());
+    assert!(conn.pipeline().run().unwrap().is_empty());
+}
+
+#[test]
+fn test_pipeline_transaction() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT PRIMARY KEY)"));
+    let trans = or_panic!(conn.transaction());
+    let insert = or_panic!(trans.prepare("INSERT INTO foo (id) VALUES ($1)"));
+
+    let mut pipeline = trans.pipeline();
+    pipeline.execute(&insert, &[&1i32])
+            .execute(&insert, &[&1i32])
+            .execute(&insert, &[&2i32]);
+    let results = or_panic!(pipeline.run());
+    assert!(results[0].is_ok());
+    match results[2] {
+        Err(Error::DbError(ref e)) if e.code() == &InFailedSqlTransaction => {}
+        ref res => panic!("unexpected result {:?}", res),
+    }
+    drop(results);
+    drop(insert);
+    or_panic!(trans.finish());
+}
+
+#[test]
+fn test_pipeline_foreign_statement() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let other = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let stmt = or_panic!(conn.prepare("SELECT $1::INT"));
+    let foreign = or_panic!(other.prepare("SELECT $1::INT"));
+
+    let mut pipeline = conn.pipeline();
+    pipeline.query(&stmt, &[&1i32]).query(&foreign, &[&2i32]).query(&stmt, &[&3i32]);
+    let mut results = or_panic!(pipeline.run()).into_iter();
+    match results.next() {
+        Some(Ok(PipelineResult::Query(ref rows))) => assert_eq!(1, rows.get(0).get::<_, i32>(0)),
+        res => panic!("unexpected result {:?}", res),
+    }
+    match results.next() {
+        Some(Err(Error::Misuse(ref msg))) if msg.contains("connection") => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    match results.next() {
+        Some(Ok(PipelineResult::Query(ref rows))) => assert_eq!(3, rows.get(0).get::<_, i32>(0)),
+        res => panic!("unexpected result {:?}", res),
+    }
+    assert!(!conn.is_desynchronized());
+    assert!(!other.is_desynchronized());
+}
+
+#[test]
+fn test_pipeline_simple_query_protocol() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    conn.set_query_protocol(QueryProtocol::Simple);
+    let stmt = or_panic!(conn.prepare("SELECT $1::INT, 'foo'::TEXT"));
+    let copy = or_panic!(conn.prepare("COPY (SELECT 1) TO STDOUT"));
+
+    let mut pipeline = conn.pipeline();
+    pipeline.query(&stmt, &[&1i32]).query(&stmt, &[&2i32]);
+    let results = or_panic!(pipeline.run());
+    for (i, result) in results.into_iter().enumerate() {
+        match result {
+            Ok(PipelineResult::Query(rows)) => {
+                assert_eq!(i as i32 + 1, rows.get(0).get::<_, i32>(0));
+                assert_eq!("foo", rows.get(0).get::<_, String>(1));
+            }
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+
+    let mut pipeline = conn.pipeline();
+    pipeline.query(&stmt, &[&1i32]).execute(&copy, &[]).query(&stmt, &[&2i32]);
+    let mut results = or_panic!(pipeline.run()).into_iter();
+    match results.next() {
+        Some(Ok(PipelineResult::Query(ref rows))) => assert_eq!(1, rows.get(0).get::<_, i32>(0)),
+        res => panic!("unexpected result {:?}", res),
+    }
+    match results.next() {
+        Some(Err(Error::Misuse(ref msg))) if msg.contains("COPY") => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    match results.next() {
+        Some(Ok(PipelineResult::Query(ref rows))) => assert_eq!(2, rows.get(0).get::<_, i32>(0)),
+        res => panic!("unexpected result {:?}", res),
+    }
+    assert!(!conn.is_desynchronized());
+}
+
+#[test]
+fn test_pipeline_many_executions() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let stmt = or_panic!(conn.prepare("SELECT repeat('x', 10000), $1::INT"));
+    let ids = (0..1000).collect::<Vec<i32>>();
+    let params = ids.iter().map(|id| [id as &ToSql]).collect::<Vec<_>>();
+
+    let mut pipeline = conn.pipeline();
+    for params in &params {
+        pipeline.query(&stmt, params);
+    }
+    let results = or_panic!(pipeline.run());
+    assert_eq!(1000, results.len());
+    for (i, result) in results.into_iter().enumerate() {
+        match result {
+            Ok(PipelineResult::Query(rows)) => assert_eq!(i as i32, rows.get(0).get::<_, i32>(1)),
+            res => panic!("unexpected result {:?}", res),
+        }
+    }
+}
+
+#[test]
+fn test_execute_many() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT PRIMARY KEY)"));
+    let stmt = or_panic!(conn.prepare("INSERT INTO foo (id) VALUES ($1)"));
+
+    let ids = (0..300).collect::<Vec<i32>>();
+    let counts = or_panic!(stmt.execute_many(ids.iter().map(|id| [id as &ToSql])));
+    assert_eq!(vec![1; 300], counts);
+    let count: i64 = or_panic!(or_panic!(conn.prepare("SELECT COUNT(*) FROM foo")).query(&[])).get(0).get(0);
+    assert_eq!(300, count);
+
+    assert_eq!(Vec::<u64>::new(), or_panic!(stmt.execute_many(Vec::<[&ToSql; 1]>::new())));
+}
+
+#[test]
+fn test_execute_many_error() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT PRIMARY KEY)"));
+    let stmt = or_panic!(conn.prepare("INSERT INTO foo (id) VALUES ($1)"));
+
+    let mut ids = (0..300).collect::<Vec<i32>>();
+    ids[200] = 5;
+    let err = stmt.execute_many(ids.iter().map(|id| [id as &ToSql])).unwrap_err();
+    assert_eq!(200, err.index());
+    match *err.error() {
+        Error::DbError(ref e) if e.code() == &UniqueViolation => {}
+        ref e => panic!("unexpected error {:?}", e),
+    }
+    let count: i64 = or_panic!(or_panic!(conn.prepare("SELECT COUNT(*) FROM foo")).query(&[])).get(0).get(0);
+    assert_eq!(0, count);
+
+    let err = stmt.execute_many(vec![[&1i32 as &ToSql], [&"foo"]]).unwrap_err();
+    assert_eq!(1, err.index());
+    match *err.error() {
+        Error::Value(ref e) if e.location() == &ValueLocation::Parameter(0) => {}
+        ref e => panic!("unexpected error {:?}", e),
+    }
+    assert!(conn.is_active());
+
+    // A conversion failure after earlier batches were sent rolls them back
+    let mut params = ids.iter().map(|id| vec![id as &ToSql]).collect::<Vec<_>>();
+    params[200] = vec![&"foo"];
+    let err = stmt.execute_many(params).unwrap_err();
+    assert_eq!(200, err.index());
+    match *err.error() {
+        Error::Value(ref e) if e.location() == &ValueLocation::Parameter(0) => {}
+        ref e => panic!("unexpected error {:?}", e),
+    }
+    let count: i64 = or_panic!(or_panic!(conn.prepare("SELECT COUNT(*) FROM foo")).query(&[])).get(0).get(0);
+    assert_eq!(0, count);
+}
+
+#[test]
+fn test_execute_many_simple_query_protocol() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    conn.set_query_protocol(QueryProtocol::Simple);
+    or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT PRIMARY KEY)"));
+    let stmt = or_panic!(conn.prepare("INSERT INTO foo (id) VALUES ($1)"));
+
+    let counts = or_panic!(stmt.execute_many(vec![[&1i32 as &ToSql], [&2i32]]));
+    assert_eq!(vec![1, 1], counts);
+    let err = stmt.execute_many(vec![[&3i32 as &ToSql], [&1i32], [&4i32]]).unwrap_err();
+    assert_eq!(1, err.index());
+    let count: i64 = or_panic!(or_panic!(conn.prepare("SELECT COUNT(*) FROM foo")).query(&[])).get(0).get(0);
+    assert_eq!(2, count);
+}
+
+#[test]
+fn test_prepare_typed() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let stmt = or_panic!(conn.prepare_typed("SELECT $1, $2", &[Type::Int8]));
+    assert_eq!(&[Type::Int8, Type::Text], stmt.param_types());
+    let rows = or_panic!(stmt.query(&[&5i64, &"foo"]));
+    assert_eq!(5i64, rows.get(0).get(0));
+
+    let trans = or_panic!(conn.transaction());
+    let stmt = or_panic!(trans.prepare_typed("SELECT $1", &[Type::Float8]));
+    assert_eq!(&[Type::Float8], stmt.param_types());
+}
+
+#[test]
+fn test_prepare_typed_cached() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let int4 = or_panic!(conn.prepare_typed_cached("SELECT $1", &[Type::Int4]));
+    let int8 = or_panic!(conn.prepare_typed_cached("SELECT $1", &[Type::Int8]));
+    let untyped = or_panic!(conn.prepare_cached("SELECT $1"));
+    assert_eq!(&[Type::Int4], int4.param_types());
+    assert_eq!(&[Type::Int8], int8.param_types());
+    assert_eq!(&[Type::Text], untyped.param_types());
+    drop((int4, int8, untyped));
+
+    let int8 = or_panic!(conn.prepare_typed_cached("SELECT $1", &[Type::Int8]));
+    assert_eq!(&[Type::Int8], int8.param_types());
+}
+
+#[test]
+fn test_type_by_name() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    or_panic!(conn.batch_execute("CREATE TYPE pg_temp.mood AS ENUM ('happy', 'sad')"));
+    assert_eq!(Type::Int4, or_panic!(conn.type_by_name("integer")));
+    assert_eq!(Type::TextArray, or_panic!(conn.type_by_name("pg_catalog.text[]")));
+
+    let mood = or_panic!(conn.type_by_name("mood"));
+    match mood {
+        Type::Other(ref other) => {
+            assert_eq!("mood", other.name());
+            assert_eq!(&Kind::Simple, other.kind());
+        }
+        ref ty => panic!("unexpected type {:?}", ty),
+    }
+    let stmt = or_panic!(conn.prepare_typed("SELECT $1 = 'sad'", &[mood.clone()]));
+    assert_eq!(&[mood], stmt.param_types());
+
+    match conn.type_by_name("no_such_type") {
+        Err(Error::DbError(ref e)) if e.code() == &UndefinedObject => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+}
+
+#[test]
+fn test_prepare_typed_simple_query_protocol() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    conn.set_query_protocol(QueryProtocol::Simple);
+    let stmt = or_panic!(conn.prepare_typed("SELECT pg_typeof($1)::TEXT", &[Type::Int8]));
+    let rows = or_panic!(stmt.query(&[&1i64]));
+    assert_eq!("bigint", rows.get(0).get::<_, String>(0));
+}
+
+struct Person {
+    name: String,
+    age: i32,
+}
+
+named_params!(Person { name, age });
+
+#[test]
+fn test_prepare_named() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let stmt = or_panic!(conn.prepare_named("SELECT :a::INT + @b, ':c', 1 AS \"@d\", $$:e$$, \
+                                             (ARRAY[1, 2, 3])[2:3]::TEXT -- :f
+                                             /* @g */, :a::INT"));
+    assert_eq!(&["a".to_owned(), "b".to_owned()][..], stmt.names());
+
+    let params: &[(&str, &ToSql)] = &[("a", &1i32), ("b", &2i32)];
+    let rows = or_panic!(stmt.query(params));
+    let row = rows.get(0);
+    assert_eq!(3, row.get::<_, i32>(0));
+    assert_eq!(":c", row.get::<_, String>(1));
+    assert_eq!("@d", &*row.columns()[2].name());
+    assert_eq!(":e", row.get::<_, String>(3));
+    assert_eq!("{2,3}", row.get::<_, String>(4));
+    assert_eq!(1, row.get::<_, i32>(5));
+}
+
+#[test]
+fn test_prepare_named_map_and_struct() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    or_panic!(conn.execute("CREATE TEMPORARY TABLE foo (name TEXT, age INT)", &[]));
+    let stmt = or_panic!(conn.prepare_named("INSERT INTO foo (name, age) VALUES (:name, :age)"));
+
+    let mut params: HashMap<&str, &ToSql> = HashMap::new();
+    params.insert("name", &"alice");
+    params.insert("age", &30i32);
+    assert_eq!(1, or_panic!(stmt.execute(&params)));
+
+    let person = Person { name: "bob".to_owned(), age: 40 };
+    assert_eq!(1, or_panic!(stmt.execute(&person)));
+
+    let stmt = or_panic!(conn.prepare_named("SELECT name FROM foo WHERE age > :age ORDER BY age"));
+    let params: &[(&str, &ToSql)] = &[("age", &0i32)];
+    let names = or_panic!(stmt.query(params)).iter().map(|r| r.get(0)).collect::<Vec<String>>();
+    assert_eq!(vec!["alice".to_owned(), "bob".to_owned()], names);
+}
+
+#[test]
+fn test_prepare_named_errors() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    match conn.prepare_named("SELECT :a, $1") {
+        Err(Error::Misuse(_)) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+
+    let stmt = or_panic!(conn.prepare_named("SELECT :a::INT, :b::INT"));
+    let params: &[(&str, &ToSql)] = &[("a", &1i32)];
+    match stmt.query(params) {
+        Err(Error::Misuse(ref msg)) => assert_eq!("missing named parameters: b", msg),
+        res => panic!("unexpected result {:?}", res),
+    }
+    let params: &[(&str, &ToSql)] = &[("a", &1i32), ("b", &2i32), ("c", &3i32)];
+    match stmt.query(params) {
+        Err(Error::Misuse(ref msg)) => assert_eq!("unused named parameters: c", msg),
+        res => panic!("unexpected result {:?}", res),
+    }
+}
+
+#[test]
+fn test_prepare_named_simple_query_protocol() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    conn.set_query_protocol(QueryProtocol::Simple);
+    let trans = or_panic!(conn.transaction());
+    let stmt = or_panic!(trans.prepare_named("SELECT :a::TEXT || ':a' || @a::TEXT"));
+    let params: &[(&str, &ToSql)] = &[("a", &"x")];
+    let rows = or_panic!(stmt.query(params));
+    assert_eq!("x:ax", rows.get(0).get::<_, String>(0));
+}
+
+#[test]
+fn test_simple_query() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let results = or_panic!(conn.simple_query("CREATE TEMPORARY TABLE foo (id INT, name TEXT);
+                                               INSERT INTO foo VALUES (1, 'a'), (2, NULL);
+                                               ;
+                                               SELECT * FROM foo ORDER BY id"));
+    assert_eq!(3, results.len());
+
+    assert_eq!("CREATE TABLE", results[0].tag());
+    assert!(results[0].columns().is_empty());
+    assert_eq!("INSERT 0 2", results[1].tag());
+
+    assert_eq!("SELECT 2", results[2].tag());
+    let columns = results[2].columns();
+    assert_eq!(("id", &Type::Int4), (columns[0].name(), columns[0].type_()));
+    assert_eq!(("name", &Type::Text), (columns[1].name(), columns[1].type_()));
+    assert_eq!(&[vec![Some("1".to_owned()), Some("a".to_owned())],
+                 vec![Some("2".to_owned()), None]][..],
+               results[2].rows());
+}
+
+#[test]
+fn test_simple_query_error() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    match conn.simple_query("SELECT 1; SELECT * FROM nonexistent; SELECT 2") {
+        Err(Error::DbError(ref e)) if e.code() == &UndefinedTable => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    let results = or_panic!(conn.simple_query("SELECT 1"));
+    assert_eq!(&[vec![Some("1".to_owned())]][..], results[0].rows());
+}
+
+#[test]
+fn test_connection_query() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT PRIMARY KEY);
+                                  INSERT INTO foo (id) VALUES (1), (2)"));
+
+    let rows = or_panic!(conn.query("SELECT id FROM foo WHERE id > $1 ORDER BY id", &[&0i32]));
+    assert_eq!(vec![1, 2], rows.iter().map(|r| r.get(0)).collect::<Vec<i32>>());
+
+    let row = or_panic!(conn.query_one("SELECT id FROM foo WHERE id = $1", &[&2i32]));
+    assert_eq!(2, row.get::<_, i32>(0));
+    match conn.query_one("SELECT id FROM foo WHERE id = 3", &[]) {
+        Err(Error::RowCount(0)) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    match conn.query_one("SELECT id FROM foo", &[]) {
+        Err(Error::RowCount(2)) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+
+    assert!(or_panic!(conn.query_opt("SELECT id FROM foo WHERE id = 3", &[])).is_none());
+    let row = or_panic!(conn.query_opt("SELECT id FROM foo WHERE id = 1", &[])).unwrap();
+    assert_eq!(1, row.get::<_, i32>(0));
+
+    assert_eq!(2, or_panic!(conn.query_scalar::<i64>("SELECT COUNT(*) FROM foo", &[])));
+}
+
+#[test]
+fn test_transaction_query() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let trans = or_panic!(conn.transaction());
+    or_panic!(trans.execute("CREATE TEMPORARY TABLE foo (id INT)", &[]));
+    or_panic!(trans.execute("INSERT INTO foo (id) VALUES (1)", &[]));
+    let rows = or_panic!(trans.query("SELECT id FROM foo", &[]));
+    let row = or_panic!(trans.query_one("SELECT id FROM foo", &[]));
+    drop(trans);
+    assert_eq!(1, rows.get(0).get::<_, i32>(0));
+    assert_eq!(1, row.get::<_, i32>(0));
+}
+
+#[test]
+fn test_connection_query_simple_query_protocol() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    conn.set_query_protocol(QueryProtocol::Simple);
+    assert_eq!("foo", or_panic!(conn.query_scalar::<String>("SELECT $1::TEXT", &[&"foo"])));
+}
+
+#[test]
+fn test_prepare_checked() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    or_panic!(conn.execute("CREATE TEMPORARY TABLE foo (id INT, name TEXT)", &[]));
+
+    let insert = or_panic!(conn.prepare_checked::<(i32, Option<String>), ()>(
+        "INSERT INTO foo (id, name) VALUES ($1, $2)"));
+    assert_eq!(1, or_panic!(insert.execute(&(1, Some("a".to_owned())))));
+    assert_eq!(1, or_panic!(insert.execute(&(2, None))));
+
+    let select = or_panic!(conn.prepare_checked::<(i32,), (i32, Option<String>)>(
+        "SELECT id, name FROM foo WHERE id >= $1 ORDER BY id"));
+    assert_eq!(vec![(1, Some("a".to_owned())), (2, None)], or_panic!(select.query(&(0,))));
+}
+
+#[test]
+fn test_prepare_checked_mismatch() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    match conn.prepare_checked::<(String,), (i32,)>("SELECT $1::INT") {
+        Err(Error::Value(ref e)) if e.location() == &ValueLocation::Parameter(0) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    match conn.prepare_checked::<(i32,), (String,)>("SELECT $1::INT") {
+        Err(Error::Value(ref e)) if e.pg_type() == &Type::Int4 => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    match conn.prepare_checked::<(), (i32,)>("SELECT $1::INT") {
+        Err(Error::Misuse(_)) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    let res = conn.prepare_checked::<(i32,), (i32, i32)>("SELECT $1::INT");
+    match res {
+        Err(Error::Misuse(_)) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+}
+
+#[test]
+fn test_stream() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let stmt = or_panic!(conn.prepare("SELECT generate_series(1, $1)"));
+    let mut stream = or_panic!(stmt.stream(&[&5i32]));
+    assert_eq!(Type::Int4, *stream.columns()[0].type_());
+    assert!(conn.execute("SELECT 1", &[]).is_err());
+
+    let values = stream.by_ref().map(|r| or_panic!(r).get(0)).collect::<Vec<i32>>();
+    assert_eq!(vec![1, 2, 3, 4, 5], values);
+    assert!(stream.next().is_none());
+    drop(stream);
+
+    assert_eq!(1, or_panic!(conn.query_scalar::<i32>("SELECT 1", &[])));
+}
+
+#[test]
+fn test_stream_drop() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let stmt = or_panic!(conn.prepare("SELECT generate_series(1, 10000)"));
+    {
+        let mut stream = or_panic!(stmt.stream(&[]));
+        assert_eq!(1, or_panic!(stream.next().unwrap()).get::<_, i32>(0));
+    }
+    assert_eq!(1, or_panic!(conn.query_scalar::<i32>("SELECT 1", &[])));
+
+    let stmt = or_panic!(conn.prepare("SELECT generate_series(1, 1000000000)"));
+    let mut stream = or_panic!(stmt.stream(&[]));
+    stream.set_drop_behavior(DropBehavior::Cancel);
+    assert_eq!(1, or_panic!(stream.next().unwrap()).get::<_, i32>(0));
+    or_panic!(stream.finish());
+    assert!(!conn.is_desynchronized());
+    assert_eq!(1, or_panic!(conn.query_scalar::<i32>("SELECT 1", &[])));
+}
+
+#[test]
+fn test_stream_error() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let stmt = or_panic!(conn.prepare("SELECT 1 / (3 - generate_series(1, 5))"));
+    let mut stream = or_panic!(stmt.stream(&[]));
+    assert!(stream.next().unwrap().is_ok());
+    assert!(stream.next().unwrap().is_ok());
+    match stream.next() {
+        Some(Err(Error::DbError(_))) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    assert!(stream.next().is_none());
+    drop(stream);
+    assert_eq!(1, or_panic!(conn.query_scalar::<i32>("SELECT 1", &[])));
+}
+
+#[test]
+fn test_portal() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let trans = or_panic!(conn.transaction());
+    let stmt = or_panic!(trans.prepare("SELECT generate_series(1, $1) AS n"));
+    let portal = or_panic!(stmt.bind(&[&5i32]));
+
+    let columns = or_panic!(portal.describe());
+    assert_eq!(("n", &Type::Int4), (columns[0].name(), columns[0].type_()));
+
+    let (rows, suspended) = or_panic!(portal.execute(2));
+    assert!(suspended);
+    assert_eq!(vec![1, 2], rows.iter().map(|r| r.get(0)).collect::<Vec<i32>>());
+    let (rows, suspended) = or_panic!(portal.execute(2));
+    assert!(suspended);
+    assert_eq!(vec![3, 4], rows.iter().map(|r| r.get(0)).collect::<Vec<i32>>());
+    let (rows, suspended) = or_panic!(portal.execute(0));
+    assert!(!suspended);
+    assert_eq!(vec![5], rows.iter().map(|r| r.get(0)).collect::<Vec<i32>>());
+
+    let name = portal.name().to_owned();
+    or_panic!(portal.close());
+    let stmt = or_panic!(trans.prepare(&format!("FETCH ALL FROM \"{}\"", name)));
+    assert!(stmt.query(&[]).is_err());
+}
+
+#[test]
+fn test_portal_outside_transaction() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let stmt = or_panic!(conn.prepare("SELECT 1"));
+    let res = stmt.bind(&[]);
+    match res {
+        Err(Error::Misuse(_)) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+}
+
+#[test]
+fn test_cursor() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let trans = or_panic!(conn.transaction());
+    let stmt = or_panic!(trans.prepare("SELECT generate_series(1, $1)"));
+    let cursor = or_panic!(stmt.declare_cursor(&[&10i32], CursorOptions::default()));
+
+    let rows = or_panic!(cursor.fetch(3));
+    assert_eq!(vec![1, 2, 3], rows.iter().map(|r| r.get(0)).collect::<Vec<i32>>());
+    assert_eq!(2, or_panic!(cursor.move_relative(2)));
+    let rows = or_panic!(cursor.fetch(100));
+    assert_eq!(vec![6, 7, 8, 9, 10], rows.iter().map(|r| r.get(0)).collect::<Vec<i32>>());
+    assert_eq!(0, or_panic!(cursor.fetch(1)).len());
+
+    let name = cursor.name().to_owned();
+    or_panic!(cursor.close());
+    assert!(trans.execute(&format!("MOVE 1 IN {}", name), &[]).is_err());
+}
+
+#[test]
+fn test_cursor_dropped_after_transaction() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT)"));
+    let stmt = or_panic!(conn.prepare("SELECT generate_series(1, 10)"));
+
+    let trans = or_panic!(conn.transaction());
+    let cursor = or_panic!(stmt.declare_cursor(&[], CursorOptions::default()));
+    or_panic!(trans.commit());
+
+    let trans = or_panic!(conn.transaction());
+    or_panic!(trans.execute("INSERT INTO foo (id) VALUES (1)", &[]));
+    drop(cursor);
+    or_panic!(trans.execute("INSERT INTO foo (id) VALUES (2)", &[]));
+    or_panic!(trans.commit());
+
+    let trans = or_panic!(conn.transaction());
+    let savepoint = or_panic!(trans.transaction());
+    let cursor = or_panic!(stmt.declare_cursor(&[], CursorOptions::default()));
+    or_panic!(savepoint.finish());
+    drop(cursor);
+    or_panic!(trans.execute("INSERT INTO foo (id) VALUES (3)", &[]));
+
+    let savepoint = or_panic!(trans.transaction());
+    let cursor = or_panic!(stmt.declare_cursor(&[], CursorOptions::default()));
+    or_panic!(savepoint.commit());
+    assert_eq!(1, or_panic!(cursor.fetch(1)).len());
+    drop(cursor);
+    or_panic!(trans.commit());
+
+    let count: i64 = or_panic!(conn.query("SELECT COUNT(*) FROM foo", &[])).get(0).get(0);
+    assert_eq!(3, count);
+}
+
+#[test]
+fn test_cursor_scroll_with_hold() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let stmt = or_panic!(conn.prepare("SELECT generate_series(1, 10)"));
+    let options = CursorOptions { scroll: true, hold: true };
+    let cursor = or_panic!(stmt.declare_cursor(&[], options));
+
+    or_panic!(conn.execute("SELECT 1", &[]));
+    assert_eq!(1, or_panic!(cursor.move_absolute(8)));
+    let rows = or_panic!(cursor.fetch_backward(3));
+    assert_eq!(vec![7, 6, 5], rows.iter().map(|r| r.get(0)).collect::<Vec<i32>>());
+    assert_eq!(1, or_panic!(cursor.move_absolute(-1)));
+    let rows = or_panic!(cursor.fetch(0));
+    assert_eq!(vec![10], rows.iter().map(|r| r.get(0)).collect::<Vec<i32>>());
+}
+
+#[test]
+fn test_cursor_simple_query_protocol() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    conn.set_query_protocol(QueryProtocol::Simple);
+    let trans = or_panic!(conn.transaction());
+    let stmt = or_panic!(trans.prepare("SELECT generate_series(1, $1)"));
+    let cursor = or_panic!(stmt.declare_cursor(&[&3i32], CursorOptions::default()));
+    let rows = or_panic!(cursor.fetch(5));
+    assert_eq!(vec![1, 2, 3], rows.iter().map(|r| r.get(0)).collect::<Vec<i32>>());
+}
+
+#[test]
+fn test_command_tag() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let tag = or_panic!(conn.execute_tagged("CREATE TEMPORARY TABLE foo (id INT)", &[]));
+    assert_eq!("CREATE TABLE", tag.as_str());
+    assert_eq!("CREATE TABLE", tag.command());
+    assert_eq!(CommandKind::Other, tag.kind());
+    assert_eq!(None, tag.rows());
+
+    let tag = or_panic!(conn.execute_tagged("INSERT INTO foo VALUES (1), (2), (3)", &[]));
+    assert_eq!("INSERT", tag.command());
+    assert_eq!(CommandKind::Insert, tag.kind());
+    assert_eq!(Some(3), tag.rows());
+    assert_eq!(None, tag.insert_oid());
+
+    let stmt = or_panic!(conn.prepare("UPDATE foo SET id = id + 1 WHERE id > $1"));
+    let tag = or_panic!(stmt.execute_tagged(&[&1i32]));
+    assert_eq!((CommandKind::Update, Some(2)), (tag.kind(), tag.rows()));
+    assert_eq!("UPDATE 2", tag.to_string());
+
+    let rows = or_panic!(conn.query("SELECT * FROM foo", &[]));
+    let tag = rows.command_tag().unwrap();
+    assert_eq!((CommandKind::Select, Some(3)), (tag.kind(), tag.rows()));
+
+    let stmt = or_panic!(conn.prepare("COPY foo (id) FROM STDIN"));
+    let tag = or_panic!(stmt.copy_in_tagged(&[], &mut &b"4\n5\n"[..]));
+    assert_eq!((CommandKind::Copy, Some(2)), (tag.kind(), tag.rows()));
+
+    let tag = or_panic!(conn.execute_tagged("", &[]));
+    assert_eq!(("", None), (tag.as_str(), tag.rows()));
+}
+
+#[test]
+fn test_command_tag_simple_query_protocol() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    conn.set_query_protocol(QueryProtocol::Simple);
+    or_panic!(conn.execute("CREATE TEMPORARY TABLE foo (id INT)", &[]));
+    let tag = or_panic!(conn.execute_tagged("DELETE FROM foo WHERE id = $1", &[&1i32]));
+    assert_eq!((CommandKind::Delete, Some(0)), (tag.kind(), tag.rows()));
+
+    let rows = or_panic!(conn.query("SELECT generate_series(1, 4)", &[]));
+    assert_eq!(Some(4), rows.command_tag().and_then(|t| t.rows()));
+
+    let results = or_panic!(conn.simple_query("SELECT 1; BEGIN; ROLLBACK"));
+    assert_eq!(CommandKind::Select, results[0].command_tag().kind());
+    assert_eq!("BEGIN", results[1].command_tag().command());
+    assert_eq!(None, results[2].command_tag().rows());
+}
+
+#[test]
+fn test_lazy_query_inactive_transaction() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let trans = or_panic!(conn.transaction());
+    let stmt = or_panic!(trans.prepare("SELECT 1::INT"));
+    let _trans2 = or_panic!(trans.transaction());
+    let res = stmt.lazy_query(&trans, &[], 1);
+    match res {
+        Err(Error::Misuse(ref msg)) if msg.contains("active transaction") => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+}
+
+#[test]
+fn test_too_few_params_simple_query_protocol() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    conn.set_query_protocol(QueryProtocol::Simple);
+    match conn.query("SELECT $1::INT, $2::INT", &[&1i32]) {
+        Err(Error::Misuse(ref msg)) if msg == "expected 2 parameters but got 1" => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    or_panic!(conn.execute("SELECT 1", &[]));
+}
+
+#[test]
+fn test_value_error_display() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let rows = or_panic!(conn.query("SELECT 1::INT AS a, NULL::TEXT AS b", &[]));
+    let err = rows.get(0).get_opt::<_, String>("b").unwrap_err();
+    let message = err.to_string();
+    assert!(message.contains("column 1 (\"b\")"), message);
+    assert!(message.contains("Postgres type text"), message);
+    assert!(message.contains("Rust type alloc::string::String"), message);
+
+    conn.set_query_protocol(QueryProtocol::Simple);
+    let err = conn.execute("SELECT $1, $2", &[&1i32, &"\0"]).unwrap_err();
+    match err {
+        Error::Value(ref e) => {
+            assert_eq!(&ValueLocation::Parameter(1), e.location());
+            assert_eq!(&Type::Unknown, e.pg_type());
+            assert_eq!("&str", e.rust_type());
+        }
+        ref e => panic!("unexpected error {:?}", e),
+    }
+}
+
+fn prepared_statements(conn: &Connection) -> Vec<String> {
+    let rows = or_panic!(conn.query("SELECT statement FROM pg_prepared_statements \
+                                     ORDER BY statement", &[]));
+    rows.iter().map(|r| r.get(0)).collect()
+}
+
+#[test]
+fn test_statement_cache_eviction() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    assert_eq!(DEFAULT_STATEMENT_CACHE_CAPACITY, conn.statement_cache_capacity());
+    conn.set_statement_cache_capacity(2);
+
+    or_panic!(conn.prepare_cached("SELECT 1"));
+    or_panic!(conn.prepare_cached("SELECT 2"));
+    or_panic!(conn.prepare_cached("SELECT 1"));
+    // Evicts "SELECT 2", which is closed once the new statement is dropped
+    let stmt = or_panic!(conn.prepare_cached("SELECT 3"));
+    assert_eq!(vec!["SELECT 1", "SELECT 2", "SELECT 3"], prepared_statements(&conn));
+    drop(stmt);
+    assert_eq!(vec!["SELECT 1", "SELECT 3"], prepared_statements(&conn));
+    or_panic!(conn.prepare_cached("SELECT 1"));
+
+    let stats = conn.statement_cache_stats();
+    assert_eq!(StatementCacheStats { len: 2, capacity: 2, hits: 2, misses: 3, evictions: 1 },
+               stats);
+
+    or_panic!(conn.clear_statement_cache());
+    assert!(prepared_statements(&conn).is_empty());
+    assert_eq!(0, conn.statement_cache_stats().len);
+}
+
+#[test]
+fn test_statement_cache_evicts_in_use() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let stmt = or_panic!(conn.prepare_cached("SELECT 1::INT"));
+    conn.set_statement_cache_capacity(0);
+    or_panic!(conn.prepare_cached("SELECT 2"));
+    assert_eq!(vec!["SELECT 1::INT"], prepared_statements(&conn));
+
+    let value: i32 = or_panic!(stmt.query(&[])).get(0).get(0);
+    assert_eq!(1, value);
+    drop(stmt);
+    assert!(prepared_statements(&conn).is_empty());
+}
+
+#[test]
+fn test_statement_cache_in_failed_transaction() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    conn.set_statement_cache_capacity(1);
+    let stmt = or_panic!(conn.prepare_cached("SELECT 1"));
+    or_panic!(conn.prepare_cached("SELECT 2"));
+
+    let trans = or_panic!(conn.transaction());
+    assert!(trans.execute("SELECT * FROM nonexistent", &[]).is_err());
+    drop(stmt);
+    or_panic!(trans.prepare_cached("SELECT 2"));
+    drop(trans);
+    assert_eq!(vec!["SELECT 2"], prepared_statements(&conn));
+}
+
+#[test]
+fn test_prepare_cached_reprepares_after_alter() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT); INSERT INTO foo VALUES (1)"));
+
+    let stmt = or_panic!(conn.prepare_cached("SELECT * FROM foo"));
+    assert_eq!(1, or_panic!(stmt.query(&[])).columns().len());
+    or_panic!(conn.batch_execute("ALTER TABLE foo ADD COLUMN name TEXT DEFAULT 'joe'"));
+
+    let rows = or_panic!(stmt.query(&[]));
+    assert_eq!(2, rows.columns().len());
+    assert_eq!("joe", rows.get(0).get::<_, String>(1));
+    assert_eq!(1, or_panic!(stmt.execute(&[])));
+
+    let rows = or_panic!(or_panic!(conn.prepare_cached("SELECT * FROM foo")).query(&[]));
+    assert_eq!(2, rows.columns().len());
+    drop(stmt);
+    assert_eq!(vec!["SELECT * FROM foo"], prepared_statements(&conn));
+}
+
+#[test]
+fn test_prepare_cached_reprepare_refreshes_columns() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT); INSERT INTO foo VALUES (1)"));
+
+    let stmt = or_panic!(conn.prepare_cached("SELECT id FROM foo"));
+    assert_eq!(&Type::Int4, stmt.columns()[0].type_());
+    or_panic!(conn.batch_execute("ALTER TABLE foo ALTER COLUMN id TYPE BIGINT"));
+
+    let rows = or_panic!(stmt.query(&[]));
+    assert_eq!(1, rows.get(0).get::<_, i64>(0));
+    assert_eq!(&Type::Int4, stmt.columns()[0].type_());
+    assert_eq!(&Type::Int8, stmt.current_columns()[0].type_());
+}
+
+#[test]
+fn test_prepare_cached_reprepares_after_discard() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let stmt = or_panic!(conn.prepare_cached("SELECT 1::INT"));
+    or_panic!(conn.batch_execute("DISCARD ALL"));
+
+    let value: i32 = or_panic!(stmt.query(&[])).get(0).get(0);
+    assert_eq!(1, value);
+    assert_eq!(vec!["SELECT 1::INT"], prepared_statements(&conn));
+}
+
+#[test]
+fn test_prepare_cached_invalidated_in_transaction() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT)"));
+    or_panic!(conn.prepare_cached("SELECT * FROM foo"));
+    or_panic!(conn.batch_execute("ALTER TABLE foo ADD COLUMN name TEXT"));
+
+    let trans = or_panic!(conn.transaction());
+    match trans.prepare_cached("SELECT * FROM foo").and_then(|s| s.query(&[]).map(|_| ())) {
+        Err(Error::DbError(ref e)) if e.code() == &FeatureNotSupported => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    drop(trans);
+
+    let stmt = or_panic!(conn.prepare_cached("SELECT * FROM foo"));
+    assert_eq!(2, stmt.columns().len());
+    assert_eq!(2, conn.statement_cache_stats().misses);
+}
+
+#[test]
+fn test_deferred_statement_close() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let stmt = or_panic!(conn.prepare("SELECT 1"));
+    drop(or_panic!(conn.prepare("SELECT 2")));
+    assert_eq!(vec!["SELECT 1"], prepared_statements(&conn));
+    or_panic!(stmt.finish());
+    assert!(prepared_statements(&conn).is_empty());
+
+    let trans = or_panic!(conn.transaction());
+    let stmt = or_panic!(trans.prepare("SELECT 3"));
+    assert!(trans.execute("SELECT * FROM nonexistent", &[]).is_err());
+    drop(stmt);
+    or_panic!(trans.finish());
+    assert!(prepared_statements(&conn).is_empty());
+}
+
+#[test]
+fn test_deferred_portal_close() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let trans = or_panic!(conn.transaction());
+    let stmt = or_panic!(trans.prepare("SELECT * FROM generate_series(1, 10)"));
+    let rows = or_panic!(stmt.lazy_query(&trans, &[], 2));
+    drop(rows);
+    let portal = or_panic!(stmt.bind(&[]));
+    let cursors = or_panic!(trans.query("SELECT name FROM pg_cursors WHERE name <> ''", &[]));
+    let cursors = cursors.iter().map(|r| r.get(0)).collect::<Vec<String>>();
+    assert_eq!(vec![portal.name().to_owned()], cursors);
+}
+
+#[test]
+fn test_deferred_close_while_in_use() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let stmt = or_panic!(conn.prepare("SELECT 1"));
+    let copy = or_panic!(conn.prepare("COPY (SELECT 1) TO STDOUT"));
+    let reader = or_panic!(copy.copy_out(&[]));
+    drop(stmt);
+    or_panic!(reader.finish());
+    drop(copy);
+    assert!(prepared_statements(&conn).is_empty());
+}
+
+#[test]
+fn test_deferred_close_error() {
+    struct Handler(Arc<Mutex<Vec<DbError>>>);
+
+    impl HandleNotice for Handler {
+        fn handle_notice(&mut self, notice: DbError) {
+            self.0.lock().unwrap().push(notice);
+        }
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let server = thread::spawn(move || {
+        let mut stream = listener.accept().unwrap().0;
+        nonblocking::read_message(&mut stream, true);
+        let mut out = vec![];
+        out.extend(nonblocking::message(b'R', &[0, 0, 0, 0]));
+        out.extend(nonblocking::message(b'Z', b"I"));
+        stream.write_all(&out).unwrap();
+
+        // Parse, Describe, Sync
+        for &tag in &[b'P', b'D', b'S'] {
+            assert_eq!(tag, nonblocking::read_message(&mut stream, false)[0]);
+        }
+        let mut out = vec![];
+        out.extend(nonblocking::message(b'1', b""));
+        out.extend(nonblocking::message(b't', b"\x00\x00"));
+        out.extend(nonblocking::message(b'n', b""));
+        out.extend(nonblocking::message(b'Z', b"I"));
+        stream.write_all(&out).unwrap();
+
+        // The deferred Close and its Sync precede the query
+        for &tag in &[b'C', b'S', b'Q'] {
+            assert_eq!(tag, nonblocking::read_message(&mut stream, false)[0]);
+        }
+        let mut out = fake_error("26000");
+        out.extend(nonblocking::message(b'C', b"SELECT 1\x00"));
+        out.extend(nonblocking::message(b'Z', b"I"));
+        stream.write_all(&out).unwrap();
+
+        assert_eq!(b'X', nonblocking::read_message(&mut stream, false)[0]);
+    });
+
+    let conn = or_panic!(Connection::connect(&*format!("postgres://user@127.0.0.1:{}", port),
+                                             &SslMode::None));
+    let notices = Arc::new(Mutex::new(vec![]));
+    conn.set_notice_handler(Box::new(Handler(notices.clone())));
+    drop(or_panic!(conn.prepare("SELECT 1")));
+    or_panic!(conn.batch_execute("SELECT 1"));
+
+    {
+        let notices = notices.lock().unwrap();
+        assert_eq!(1, notices.len());
+        assert_eq!(&InvalidSqlStatementName, notices[0].code());
+    }
+
+    drop(conn);
+    server.join().unwrap();
+}
+
+#[test]
+fn test_column_metadata() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (
+                                      id INT,
+                                      name VARCHAR(255),
+                                      code CHAR(3)[],
+                                      amount NUMERIC(12, 2),
+                                      created TIMESTAMP(3),
+                                      elapsed INTERVAL SECOND(2),
+                                      data TEXT
+                                  )"));
+    let table_oid: Oid = or_panic!(conn.query_scalar("SELECT 'foo'::regclass::oid", &[]));
+
+    let stmt = or_panic!(conn.prepare("SELECT *, 1::INT2 AS literal FROM foo"));
+    let columns = stmt.columns();
+    assert_eq!(Some(table_oid), columns[0].table_oid());
+    assert_eq!(Some(2), columns[1].column_id());
+    assert_eq!(4, columns[0].type_size());
+    assert_eq!(-1, columns[1].type_size());
+    assert_eq!(Format::Text, columns[0].format());
+    assert_eq!(Some(255), columns[1].character_maximum_length());
+    assert_eq!(Some(3), columns[2].character_maximum_length());
+    assert_eq!(Some(12), columns[3].numeric_precision());
+    assert_eq!(Some(2), columns[3].numeric_scale());
+    assert_eq!(Some(3), columns[4].datetime_precision());
+    assert_eq!(Some(2), columns[5].datetime_precision());
+    assert_eq!(-1, columns[6].type_modifier());
+    assert_eq!(None, columns[6].character_maximum_length());
+    assert_eq!(None, columns[7].table_oid());
+    assert_eq!(None, columns[7].column_id());
+
+    let trans = or_panic!(conn.transaction());
+    let portal = or_panic!(stmt.bind(&[]));
+    assert_eq!(Format::Binary, or_panic!(portal.describe())[0].format());
+    drop(portal);
+    or_panic!(trans.finish());
+}
+
+#[test]
+fn test_column_origins() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT NOT NULL, name TEXT)"));
+    let schema: String = or_panic!(conn.query_scalar("SELECT nspname::TEXT FROM pg_namespace \
+                                                      WHERE oid = pg_my_temp_schema()", &[]));
+
+    let stmt = or_panic!(conn.prepare("SELECT name, 1, id FROM foo"));
+    let origins = or_panic!(stmt.column_origins());
+    assert_eq!(3, origins.len());
+    let name = origins[0].as_ref().unwrap();
+    assert_eq!(schema, name.schema());
+    assert_eq!("foo", name.table());
+    assert_eq!("name", name.column());
+    assert!(!name.not_null());
+    assert!(origins[1].is_none());
+    let id = origins[2].as_ref().unwrap();
+    assert_eq!("id", id.column());
+    assert!(id.not_null());
+
+    // Origins are cached for the lifetime of the connection
+    or_panic!(conn.batch_execute("ALTER TABLE foo RENAME COLUMN id TO foo_id"));
+    assert_eq!(origins, or_panic!(stmt.column_origins()));
+}
+
+#[derive(Debug, PartialEq)]
+struct User {
+    id: i32,
+    name: String,
+    nickname: Option<String>,
+}
+
+from_row!(User { id, name, nickname });
+
+#[test]
+fn test_from_row() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    or_panic!(conn.batch_execute("CREATE TEMPORARY TABLE foo (id INT, name TEXT, nickname TEXT);
+                                  INSERT INTO foo VALUES (1, 'joe', NULL), (2, 'bob', 'bobby')"));
+
+    let rows = or_panic!(conn.query("SELECT name, nickname, id FROM foo ORDER BY id", &[]));
+    let people = or_panic!(rows.into_typed::<User>());
+    assert_eq!(vec![User { id: 1, name: "joe".to_owned(), nickname: None },
+                    User { id: 2, name: "bob".to_owned(), nickname: Some("bobby".to_owned()) }],
+               people);
+
+    let rows = or_panic!(conn.query("SELECT id, name FROM foo ORDER BY id", &[]));
+    assert_eq!(vec![(1, "joe".to_owned()), (2, "bob".to_owned())],
+               or_panic!(rows.into_typed::<(i32, String)>()));
+
+    let rows = or_panic!(conn.query("SELECT id, name FROM foo", &[]));
+    match User::from_row(&rows.get(0)) {
+        Err(Error::Conversion(ref err)) => assert!(err.to_string().contains("`User::nickname`")),
+        res => panic!("unexpected result {:?}", res),
+    }
+    match rows.into_typed::<(i32,)>() {
+        Err(Error::Misuse(_)) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+
+    let rows = or_panic!(conn.query("SELECT id, id AS name, name AS nickname FROM foo", &[]));
+    match rows.into_typed::<User>() {
+        Err(Error::Value(ref e)) => {
+            assert_eq!(&ValueLocation::Column { index: 1, name: "name".to_owned() }, e.location());
+        }
+        res => panic!("unexpected result {:?}", res),
+    }
+}
+
+#[test]
+fn test_lazy_rows_into_typed() {
+    let conn = or_panic!(Connection::connect("postgres://postgres@localhost", &SslMode::None));
+    let trans = or_panic!(conn.transaction());
+    let stmt = or_panic!(trans.prepare("SELECT i, i::TEXT, NULL::TEXT \
+                                        FROM generate_series(1, 5) AS s (i)"));
+    let rows = or_panic!(stmt.lazy_query(&trans, &[], 2));
+    let values = or_panic!(rows.into_typed::<(i32, String, Option<String>)>()
+                               .collect::<postgres::Result<Vec<_>>>());
+    assert_eq!(5, values.len());
+    assert_eq!((5, "5".to_owned(), None), values[4]);
 }
@@ -215,7 +215,12 @@ fn test_slice_wrong_type() {
     let stmt = conn.prepare("SELECT * FROM foo WHERE id = ANY($1)").unwrap();
     match stmt.query(&[&Slice(&["hi"])]) {
         Ok(_) => panic!("Unexpected success"),
-        Err(Error::WrongType(..)) => {}
+        Err(Error::Value(ref e)) => {
+            match *e.error() {
+                Error::WrongType(..) => {}
+                ref e => panic!("Unexpected error {:?}", e),
+            }
+        }
         Err(e) => panic!("Unexpected error {:?}", e),
     }
 }
@@ -227,7 +232,12 @@ fn test_slice_range() {
     let stmt = conn.prepare("SELECT $1::INT8RANGE").unwrap();
     match stmt.query(&[&Slice(&[1i64])]) {
         Ok(_) => panic!("Unexpected success"),
-        Err(Error::WrongType(..)) => {}
+        Err(Error::Value(ref e)) => {
+            match *e.error() {
+                Error::WrongType(..) => {}
+                ref e => panic!("Unexpected error {:?}", e),
+            }
+        }
         Err(e) => panic!("Unexpected error {:?}", e),
     }
 }
@@ -1,22 +1,153 @@
-extern crate serde;
+extern crate serde_json;
 
-use self::serde::json::{self, Value};
+use std::collections::HashMap;
+
+use self::serde_json::Value;
+use postgres::{Connection, SslMode};
+use postgres::error::{Error, ValueLocation};
 use types::test_type;
 
 #[test]
 fn test_json_params() {
-    test_type("JSON", &[(Some(json::from_str::<Value>("[10, 11, 12]").unwrap()),
+    test_type("JSON", &[(Some(serde_json::from_str::<Value>("[10, 11, 12]").unwrap()),
                         "'[10, 11, 12]'"),
-                       (Some(json::from_str::<Value>("{\"f\": \"asd\"}").unwrap()),
+                       (Some(serde_json::from_str::<Value>("{\"f\": \"asd\"}").unwrap()),
                         "'{\"f\": \"asd\"}'"),
                        (None, "NULL")])
 }
 
 #[test]
 fn test_jsonb_params() {
-    test_type("JSONB", &[(Some(json::from_str::<Value>("[10, 11, 12]").unwrap()),
+    test_type("JSONB", &[(Some(serde_json::from_str::<Value>("[10, 11, 12]").unwrap()),
                           "'[10, 11, 12]'"),
-                         (Some(json::from_str::<Value>("{\"f\": \"asd\"}").unwrap()),
+                         (Some(serde_json::from_str::<Value>("{\"f\": \"asd\"}").unwrap()),
                           "'{\"f\": \"asd\"}'"),
                          (None, "NULL")])
 }
+
+#[test]
+fn test_deserialize_row_tuple() {
+    let conn = Connection::connect("postgres://postgres@localhost", &SslMode::None).unwrap();
+    let rows = conn.query("SELECT 1::INT, 'hello'::TEXT, NULL::BIGINT", &[]).unwrap();
+    let row: (i32, String, Option<i64>) = rows.get(0).deserialize().unwrap();
+    assert_eq!((1, "hello".to_owned(), None), row);
+}
+
+#[test]
+fn test_deserialize_row_map() {
+    let conn = Connection::connect("postgres://postgres@localhost", &SslMode::None).unwrap();
+    let rows = conn.query("SELECT 1::INT AS a, NULL::INT AS b", &[]).unwrap();
+    let row: HashMap<String, Option<i32>> = rows.get(0).deserialize().unwrap();
+    let mut expected = HashMap::new();
+    expected.insert("a".to_owned(), Some(1));
+    expected.insert("b".to_owned(), None);
+    assert_eq!(expected, row);
+}
+
+#[test]
+fn test_deserialize_row_nested_json() {
+    let conn = Connection::connect("postgres://postgres@localhost", &SslMode::None).unwrap();
+    let rows = conn.query("SELECT 'a'::TEXT, '[1, 2, 3]'::JSONB", &[]).unwrap();
+    let row: (String, Vec<i32>) = rows.get(0).deserialize().unwrap();
+    assert_eq!(("a".to_owned(), vec![1, 2, 3]), row);
+}
+
+#[test]
+fn test_deserialize_row_wrong_type() {
+    let conn = Connection::connect("postgres://postgres@localhost", &SslMode::None).unwrap();
+    let rows = conn.query("SELECT 'a'::TEXT AS name", &[]).unwrap();
+    match rows.get(0).deserialize::<(i32,)>() {
+        Err(Error::Value(ref err)) => {
+            assert_eq!(ValueLocation::Column { index: 0, name: "name".to_owned() },
+                       *err.location());
+        }
+        res => panic!("unexpected result {:?}", res),
+    }
+}
+
+#[test]
+fn test_deserialize_row_column_count_mismatch() {
+    let conn = Connection::connect("postgres://postgres@localhost", &SslMode::None).unwrap();
+    let rows = conn.query("SELECT 1::INT, 2::INT", &[]).unwrap();
+    match rows.get(0).deserialize::<(i32,)>() {
+        Err(Error::Conversion(_)) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+    match rows.get(0).deserialize::<(i32, i32, i32)>() {
+        Err(Error::Conversion(_)) => {}
+        res => panic!("unexpected result {:?}", res),
+    }
+}
+
+#[test]
+fn test_deserialize_row_text_types() {
+    let conn = Connection::connect("postgres://postgres@localhost", &SslMode::None).unwrap();
+    let rows = conn.query("SELECT 12345.678::NUMERIC, -0.00012::NUMERIC, 100000000.0001::NUMERIC,
+                                  'NaN'::NUMERIC, '2015-03-04 05:06:07'::TIMESTAMP",
+                          &[])
+                   .unwrap();
+    let row: (String, String, String, String, String) = rows.get(0).deserialize().unwrap();
+    assert_eq!(("12345.678".to_owned(),
+                "-0.00012".to_owned(),
+                "100000000.0001".to_owned(),
+                "NaN".to_owned(),
+                "2015-03-04 05:06:07.000000".to_owned()),
+               row);
+
+    let rows = conn.query("SELECT 12345.678::NUMERIC, 10000::NUMERIC", &[]).unwrap();
+    let row: (f64, i32) = rows.get(0).deserialize().unwrap();
+    assert_eq!((12345.678, 10000), row);
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Address {
+    city: String,
+    zip: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Person {
+    id: i32,
+    name: String,
+    nickname: Option<String>,
+    address: Address,
+}
+
+#[test]
+fn test_deserialize_row_struct() {
+    let conn = Connection::connect("postgres://postgres@localhost", &SslMode::None).unwrap();
+    conn.batch_execute("CREATE TEMPORARY TABLE person (
+                            id INT,
+                            name TEXT,
+                            nickname TEXT,
+                            address JSONB
+                        )")
+        .unwrap();
+
+    let address = Address {
+        city: "Springfield".to_owned(),
+        zip: None,
+    };
+    let json = serde_json::to_value(&address).unwrap();
+    conn.execute("INSERT INTO person VALUES (1, 'joe', NULL, $1)", &[&json]).unwrap();
+
+    let rows = conn.query("SELECT address, name, id, nickname FROM person", &[]).unwrap();
+    let person: Person = rows.get(0).deserialize().unwrap();
+    assert_eq!(Person {
+                   id: 1,
+                   name: "joe".to_owned(),
+                   nickname: None,
+                   address: address,
+               },
+               person);
+
+    let rows = conn.query("SELECT id, name, address FROM person", &[]).unwrap();
+    let person: Person = rows.get(0).deserialize().unwrap();
+    assert_eq!(None, person.nickname);
+
+    let rows = conn.query("SELECT id, name FROM person", &[]).unwrap();
+    match rows.get(0).deserialize::<Person>() {
+        Err(Error::Conversion(ref err)) => assert!(err.to_string().contains("`address`")),
+        res => panic!("unexpected result {:?}", res),
+    }
+}